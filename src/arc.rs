@@ -0,0 +1,44 @@
+use crate::common::*;
+use crate::types::*;
+use serde_json::Value;
+
+impl Arc {
+    pub fn from_value(val: &Value) -> E<Arc> {
+        let mut val = bailif!(tagged_array("arc", &val), "Arc::from_value failes to decode")?;
+        if let Some(Value::Array(v)) = val.next() {
+            if v.len() != 7 {
+                bailfmt!("arc expected array of 7 numbers, got: {:?}", v)
+            } else {
+                let coord5: Coord5 = serde_json::from_value(Value::Array(v[0..5].to_vec()))?;
+                let cx: i32 = serde_json::from_value(v[5].clone())?;
+                let cy: i32 = serde_json::from_value(v[6].clone())?;
+                Ok(Arc { coord5, cx, cy })
+            }
+        } else {
+            bailfmt!("arc expected array, got: {:?}", val)
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn arc1() {
+        let expect = Arc { coord5: Coord5 { x: -10, y: -4, r: FlipY, dx: 14, dy: 12 }, cx: 9, cy: 3 };
+        let val = json!(["arc", [-10, -4, 6, 14, 12, 9, 3]]);
+        assert_eq!(Arc::from_value(&val), Ok(expect));
+    }
+
+    #[test]
+    fn arc2() {
+        let val = json!(["arc", [-10, -4, 6, 14, 12]]);
+        assert!(Arc::from_value(&val).is_err());
+    }
+}
@@ -34,7 +34,7 @@ macro_rules! bailif {
     };
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub struct Bail {
     pub line: u32, // line number
     pub col: u32,  //
@@ -13,3 +13,12 @@ pub fn tagged_array<'a>(tag: &str, val: &'a Value) -> E<Iter<'a, Value>> {
         bailfmt!("{:?} tagged array expects array, got:", val.as_str())
     }
 }
+
+// Jade writes numeric properties either as numbers or as strings, {"width":"4"}.
+pub fn numeric_property(val: &Value) -> Option<u64> {
+    match val {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        _ => None,
+    }
+}
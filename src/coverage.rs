@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::types::*;
+use crate::vector::Role;
+
+// How much a test exercises, for grading and review: which input
+// combinations its vectors assert, vectors that repeat or contradict one
+// another, and sampled outputs that are never seen both low and high.
+
+#[derive(Debug, PartialEq)]
+pub struct Coverage {
+    pub input_bits: usize,
+    // distinct combinations of asserted values, counting only those where
+    // every input is 0 or 1, so a - or Z never adds one.
+    pub combinations: usize,
+    // (earlier, later) vectors that are the same in every column.
+    pub duplicates: Vec<(Pos, Pos)>,
+    // (earlier, later) vectors with the same inputs but an output expected
+    // L in one and H in the other. Only looked for in combinational tests.
+    pub contradictions: Vec<(Pos, Pos)>,
+    // sampled bits never expected to be both L and H.
+    pub one_sided: Vec<String>,
+}
+
+impl Coverage {
+    // the share of all input combinations the test asserts.
+    pub fn fraction(&self) -> f64 {
+        self.combinations as f64 / 2f64.powi(self.input_bits as i32)
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f,
+                 "{} of 2^{} input combinations ({:.2}%)",
+                 self.combinations,
+                 self.input_bits,
+                 100.0 * self.fraction())?;
+        for (a, b) in &self.duplicates {
+            writeln!(f, "line {} repeats line {}", b.line, a.line)?;
+        }
+        for (a, b) in &self.contradictions {
+            writeln!(f, "line {} contradicts line {}", b.line, a.line)?;
+        }
+        if !self.one_sided.is_empty() {
+            writeln!(f, "never both L and H: {}", self.one_sided.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl ModTest {
+    // combinational says a vector's outputs depend only on its inputs, so
+    // vectors with equal inputs must agree on them.
+    pub fn coverage(&self, combinational: bool) -> E<Coverage> {
+        // which columns are inputs and which outputs, and the output names.
+        let (mut inputs, mut outputs, mut names) = (vec![], vec![], vec![]);
+        let mut col = 0;
+        for name in self.column_groups() {
+            let role = self.group_role(name);
+            for bit in self.group_bits(name)? {
+                if role != Some(Role::Expected) {
+                    inputs.push(col);
+                }
+                if role != Some(Role::Driven) {
+                    outputs.push(col);
+                    names.push(bit);
+                }
+                col += 1;
+            }
+        }
+
+        let mut seen_inputs: HashMap<Vec<BinVal>, Vec<&TestLine>> = HashMap::new();
+        let mut combinations = HashSet::new();
+        let mut duplicates = vec![];
+        let mut contradictions = vec![];
+        let mut levels = vec![(false, false); outputs.len()];
+        for tl in &self.test_lines {
+            if tl.bin_vals.len() != col {
+                let msg = format!("Test line {}: expected {} values, got {}", tl.pos.line, col, tl.bin_vals.len());
+                return bail!(msg);
+            }
+            let key: Vec<BinVal> = inputs.iter().map(|i| tl.bin_vals[*i]).collect();
+            if key.iter().all(|v| matches!(v, L | H)) {
+                combinations.insert(key.clone());
+            }
+            let earlier = seen_inputs.entry(key).or_default();
+            if let Some(same) = earlier.iter().find(|e| e.bin_vals == tl.bin_vals) {
+                duplicates.push((same.pos, tl.pos));
+            } else if combinational {
+                let clash = |e: &&&TestLine| {
+                    outputs.iter().any(|o| matches!((e.bin_vals[*o], tl.bin_vals[*o]), (L, H) | (H, L)))
+                };
+                if let Some(other) = earlier.iter().find(clash) {
+                    contradictions.push((other.pos, tl.pos));
+                }
+            }
+            earlier.push(tl);
+
+            for (k, o) in outputs.iter().enumerate() {
+                match tl.bin_vals[*o] {
+                    L => levels[k].0 = true,
+                    H => levels[k].1 = true,
+                    _ => {}
+                }
+            }
+        }
+
+        let one_sided = names.into_iter().zip(levels).filter(|(_, (l, h))| !(*l && *h)).map(|(n, _)| n).collect();
+        Ok(Coverage { input_bits: inputs.len(),
+                      combinations: combinations.len(),
+                      duplicates,
+                      contradictions,
+                      one_sided })
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn bool_unit() {
+        let test = ModTest::from_file(Path::new("./test-data/modtests/bool.test")).unwrap();
+        let got = test.coverage(true).unwrap();
+        assert_eq!(got.input_bits, 68);
+        assert_eq!(got.combinations, 16);
+        assert!(got.duplicates.is_empty() && got.contradictions.is_empty());
+        assert!(got.one_sided.is_empty());
+    }
+
+    #[test]
+    fn and2() {
+        let src = ".group inputs A B\n.group outputs Y\n.cycle assert inputs sample outputs\n\
+                   00 L\n11 H\n00 L\n11 L\n0- -\n10 L\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = test.coverage(true).unwrap();
+        let line = |line| Pos { line, col: 1 };
+        assert_eq!(got,
+                   Coverage { input_bits: 2,
+                              combinations: 3,
+                              duplicates: vec![(line(4), line(6))],
+                              contradictions: vec![(line(5), line(7))],
+                              one_sided: vec![] });
+        assert_eq!(got.to_string(),
+                   "3 of 2^2 input combinations (75.00%)\nline 6 repeats line 4\nline 7 contradicts line 5\n");
+
+        // a register's output can differ for the same inputs.
+        let got = test.coverage(false).unwrap();
+        assert!(got.contradictions.is_empty());
+
+        // - stands for values already counted, it never takes the share past all of them.
+        let src = ".group inputs A\n.group outputs Y\n.cycle assert inputs sample outputs\n0 L\n1 H\n- -\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = test.coverage(true).unwrap();
+        assert_eq!(got.combinations, 2);
+        assert!(got.fraction() <= 1.0, "{}", got);
+
+        let test = ModTest::from_str(".group inputs A\n.group outputs Y Z\n.cycle assert inputs sample outputs\n0 LL\n1 H-\n")
+                   .unwrap();
+        assert_eq!(test.coverage(true).unwrap().one_sided, vec!["Z".to_string()]);
+    }
+}
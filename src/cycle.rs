@@ -0,0 +1,266 @@
+use crate::types::*;
+
+// The .cycle line is run once per test vector. Expanding it against each
+// vector gives the schedule every backend (testbenches, the simulator)
+// walks through:
+//
+//   .cycle assert inputs tran 99n sample outputs tran 1n
+//   0 1 1      -> drive in1=0 in2=1, wait 99ns, check out=1, wait 1ns
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Step {
+    Drive(String, Vec<(String, BinVal)>),
+    Release(String, Vec<String>),
+    Check(String, Vec<(String, BinVal)>),
+    Wait(Duration),
+    Set(String, bool),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cycle {
+    pub line: usize,
+    pub comment: Option<String>,
+    pub steps: Vec<Step>,
+}
+
+impl ModTest {
+    // the bit names of a group, "A[1:0] B" -> A[1] A[0] B
+    pub fn group_bits(&self, name: &str) -> E<Vec<String>> {
+        match self.groups.get(name) {
+            Some(group) => group.bits(),
+            None => bailfmt!("Unknown group in .cycle: {}", name),
+        }
+    }
+
+    // the groups that own test vector columns, in the order the .cycle line
+    // first mentions them.
+    pub fn column_groups(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        if let Some(CycleLine(actions, _)) = &self.cycle_line {
+            for action in actions {
+                match action {
+                    Action::Assert(g) | Action::Deassert(g) | Action::Sample(g) => {
+                        if !names.contains(&g.as_str()) {
+                            names.push(g);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        names
+    }
+
+    // supplies above half of the first .power value read as a one.
+    fn logic_level(&self, volts: f64) -> bool {
+        let vdd = self.power.first().map(|p| p.volts).unwrap_or(1.0);
+        volts >= vdd / 2.0
+    }
+
+    // the bits sig=value sets, those of a group when sig names one.
+    fn set_bits(&self, sig: &Sig) -> E<Vec<String>> {
+        if let SigSimple(name) = sig {
+            if self.groups.contains(name) {
+                return self.group_bits(name);
+            }
+        }
+        let mut bits = vec![];
+        for bit in sig.bits() {
+            match bit {
+                Bit::Name(name) => bits.push(name),
+                Bit::Const(_) => return bailfmt!("Can't set a constant: {}", sig),
+            }
+        }
+        Ok(bits)
+    }
+
+    // the steps of sig=volts, every bit at the same level, or of sig=0x2c,
+    // the number's bits spread over the signal's, least significant last.
+    fn set_steps(&self, action: &Action, steps: &mut Vec<Step>) -> E<()> {
+        match action {
+            Action::SetSignal(sig, volts) => {
+                for bit in self.set_bits(sig)? {
+                    steps.push(Step::Set(bit, self.logic_level(*volts)));
+                }
+            }
+            Action::SetBus(sig, n) => {
+                let bits = self.set_bits(sig)?;
+                if bits.len() < 64 && n >> bits.len() != 0 {
+                    return bailfmt!("{} doesn't fit in {}", n, sig);
+                }
+                let width = bits.len();
+                for (i, bit) in bits.into_iter().enumerate() {
+                    let shift = width - 1 - i;
+                    steps.push(Step::Set(bit, shift < 64 && (n >> shift) & 1 == 1));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn cycles(&self) -> E<Vec<Cycle>> {
+        let actions = match &self.cycle_line {
+            Some(CycleLine(actions, _)) => actions,
+            None => return bail!("Test has no .cycle line"),
+        };
+
+        let mut columns = vec![];
+        let mut width = 0;
+        for name in self.column_groups() {
+            let bits = self.group_bits(name)?;
+            width += bits.len();
+            columns.push((name, bits));
+        }
+
+        // .initial and .reset run ahead of the first vector.
+        let mut prelude = vec![];
+        for d in &self.directives {
+            match &d.directive {
+                Directive::Initial(values) => {
+                    for (sig, volts) in values {
+                        self.set_steps(&Action::SetSignal(sig.clone(), *volts), &mut prelude)?;
+                    }
+                }
+                Directive::Reset(actions) => {
+                    for action in actions {
+                        match action {
+                            Action::Tran(d) => prelude.push(Step::Wait(*d)),
+                            a => self.set_steps(a, &mut prelude)?,
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut cycles = vec![];
+        let mut after = 0;
+        for tl in &self.test_lines {
+            if tl.bin_vals.len() != width {
+                let msg = format!("Test line {}: expected {} values, got {}", tl.pos.line, width, tl.bin_vals.len());
+                return bail!(msg);
+            }
+
+            // the slice of this vector that belongs to a group.
+            let values = |group: &str| -> Vec<(String, BinVal)> {
+                let mut start = 0;
+                for (name, bits) in &columns {
+                    if *name == group {
+                        return bits.iter().cloned().zip(tl.bin_vals[start..start + bits.len()].iter().cloned()).collect();
+                    }
+                    start += bits.len();
+                }
+                vec![]
+            };
+
+            // .repeat and .pause between the last vector and this one.
+            let mut repeat = 1;
+            let mut lead = std::mem::take(&mut prelude);
+            for d in self.directives.iter().filter(|d| d.pos.line > after && d.pos.line < tl.pos.line) {
+                match &d.directive {
+                    Directive::Repeat(n) => repeat = *n,
+                    Directive::Pause(d) => lead.push(Step::Wait(*d)),
+                    _ => {}
+                }
+            }
+            after = tl.pos.line;
+
+            let mut steps = vec![];
+            for action in actions {
+                steps.push(match action {
+                    Action::Assert(g) => Step::Drive(g.clone(), values(g)),
+                    Action::Deassert(g) => Step::Release(g.clone(), values(g).into_iter().map(|(bit, _)| bit).collect()),
+                    Action::Sample(g) => Step::Check(g.clone(), values(g)),
+                    Action::Tran(d) => Step::Wait(*d),
+                    Action::SetSignal(..) | Action::SetBus(..) => {
+                        self.set_steps(action, &mut steps)?;
+                        continue;
+                    }
+                });
+            }
+            // the lead in only comes before the first run of a repeated vector.
+            lead.extend(steps.iter().cloned());
+            cycles.push(Cycle { line: tl.pos.line, comment: tl.comment.clone(), steps: lead });
+            for _ in 1..repeat {
+                cycles.push(Cycle { line: tl.pos.line, comment: tl.comment.clone(), steps: steps.clone() });
+            }
+        }
+        Ok(cycles)
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles1() {
+        let src = ".group inputs A[1:0]\n.group outputs Z\n.cycle CLK=1 assert inputs tran 9n sample outputs\n\n01 H // one\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = test.cycles().unwrap();
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].line, 5);
+        assert_eq!(got[0].comment, Some(" one".to_string()));
+        assert_eq!(got[0].steps,
+                   vec![Step::Set("CLK".to_string(), true),
+                        Step::Drive("inputs".to_string(), vec![("A[1]".to_string(), L), ("A[0]".to_string(), H)]),
+                        Step::Wait(Duration::NanoSecond(9.0)),
+                        Step::Check("outputs".to_string(), vec![("Z".to_string(), H)])]);
+    }
+
+    #[test]
+    fn cycles2() {
+        let src = ".group inputs A B\n.cycle assert inputs\n011\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert!(test.cycles().is_err());
+
+        let src = ".group inputs A B\n.cycle assert outputs\n01\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert!(test.cycles().is_err());
+    }
+
+    #[test]
+    fn directives() {
+        let src = ".group inputs A\n.cycle assert inputs tran 1n\n.reset RST=1 tran 5n RST=0\n.initial Q=0\n\
+                   0\n.pause 3n\n.repeat 2\n1\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = test.cycles().unwrap();
+        let drive = |v| Step::Drive("inputs".to_string(), vec![("A".to_string(), v)]);
+        let wait = |n| Step::Wait(Duration::NanoSecond(n));
+        assert_eq!(got.len(), 3);
+        assert_eq!(got[0].steps,
+                   vec![Step::Set("RST".to_string(), true),
+                        wait(5.0),
+                        Step::Set("RST".to_string(), false),
+                        Step::Set("Q".to_string(), false),
+                        drive(L),
+                        wait(1.0)]);
+        assert_eq!(got[1].steps, vec![wait(3.0), drive(H), wait(1.0)]);
+        assert_eq!(got[2].steps, vec![drive(H), wait(1.0)]);
+        assert_eq!(got[2].line, 8);
+    }
+
+    #[test]
+    fn set_values() {
+        let src = ".power Vdd=3.3\n.group inputs A[2:0]\n.cycle inputs=0b101 CLK=3.3 B[1:0]=0x1 EN=100m\n";
+        let mut test = ModTest::from_str(src).unwrap();
+        test.test_lines.push(TestLine { bin_vals: vec![], comment: None, pos: Pos::default() });
+        let set = |bit: &str, v| Step::Set(bit.to_string(), v);
+        assert_eq!(test.cycles().unwrap()[0].steps,
+                   vec![set("A[2]", true),
+                        set("A[1]", false),
+                        set("A[0]", true),
+                        set("CLK", true),
+                        set("B[1]", false),
+                        set("B[0]", true),
+                        set("EN", false)]);
+
+        let mut test = ModTest::from_str(".cycle B[1:0]=0x4\n").unwrap();
+        test.test_lines.push(TestLine { bin_vals: vec![], comment: None, pos: Pos::default() });
+        assert!(test.cycles().is_err());
+    }
+}
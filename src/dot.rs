@@ -0,0 +1,373 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::gates::*;
+use crate::netlist::*;
+use crate::types::*;
+
+// Graphviz views of a project:
+//
+//   dot -Tsvg hierarchy.dot > hierarchy.svg
+//
+// The hierarchy graph has a node per module and an edge per (parent, child)
+// pair labelled with the instance count. Modules are clustered by their
+// path (/user/fast/and4 sits in /user/fast), edges on a recursion cycle are
+// red and modules unreachable from the top are dashed.
+//
+// The net graph is one module flattened to gates, clustered by instance
+// path, with a small point node per net.
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+// the parent paths of a module name, "/user/fast/and4" -> ["/user", "/user/fast"]
+fn path_prefixes(name: &str) -> Vec<String> {
+    let parts: Vec<&str> = name.trim_start_matches('/').split('/').collect();
+    (1..parts.len()).map(|i| format!("/{}", parts[..i].join("/"))).collect()
+}
+
+// nested clusters, each node goes in the deepest cluster its path names.
+#[derive(Default)]
+struct Cluster {
+    nodes: Vec<String>,
+    children: BTreeMap<String, Cluster>,
+}
+
+impl Cluster {
+    fn insert(&mut self, path: &[String], node: String) {
+        match path.split_first() {
+            Some((first, rest)) => self.children.entry(first.clone()).or_default().insert(rest, node),
+            None => self.nodes.push(node),
+        }
+    }
+
+    fn write(&self, out: &mut String, indent: usize, counter: &mut usize) {
+        let pad = " ".repeat(indent);
+        for node in &self.nodes {
+            out.push_str(&format!("{}{}\n", pad, node));
+        }
+        for (label, child) in &self.children {
+            *counter += 1;
+            out.push_str(&format!("{}subgraph cluster_{} {{\n", pad, counter));
+            out.push_str(&format!("{}  label={};\n", pad, quote(label)));
+            child.write(out, indent + 2, counter);
+            out.push_str(&format!("{}}}\n", pad));
+        }
+    }
+}
+
+// the modules each module instantiates, with counts.
+fn children(project: &Project) -> BTreeMap<String, BTreeMap<String, usize>> {
+    let mut edges = BTreeMap::new();
+    for m in &project.modules {
+        let entry: &mut BTreeMap<String, usize> = edges.entry(m.name.clone()).or_default();
+        for sub in m.submodules() {
+            *entry.entry(sub.name.clone()).or_insert(0) += 1;
+        }
+        if m.schematic.parts.iter().any(|p| matches!(p, Part::Memory(_))) {
+            *entry.entry(MEMORY.to_string()).or_insert(0) +=
+                m.schematic.parts.iter().filter(|p| matches!(p, Part::Memory(_))).count();
+        }
+    }
+    edges
+}
+
+// modules that can reach themselves, found as the strongly connected
+// components with a cycle, Tarjan style.
+fn recursive_edges(edges: &BTreeMap<String, BTreeMap<String, usize>>) -> HashSet<(String, String)> {
+    struct Tarjan<'a> {
+        edges: &'a BTreeMap<String, BTreeMap<String, usize>>,
+        index: HashMap<&'a str, usize>,
+        low: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: HashSet<&'a str>,
+        comp: HashMap<&'a str, usize>,
+        ncomps: usize,
+    }
+
+    impl<'a> Tarjan<'a> {
+        fn visit(&mut self, v: &'a str) {
+            let i = self.index.len();
+            self.index.insert(v, i);
+            self.low.insert(v, i);
+            self.stack.push(v);
+            self.on_stack.insert(v);
+            if let Some(ws) = self.edges.get(v) {
+                for w in ws.keys() {
+                    let w = w.as_str();
+                    if !self.index.contains_key(w) {
+                        self.visit(w);
+                        let low = self.low[v].min(self.low[w]);
+                        self.low.insert(v, low);
+                    } else if self.on_stack.contains(w) {
+                        let low = self.low[v].min(self.index[w]);
+                        self.low.insert(v, low);
+                    }
+                }
+            }
+            if self.low[v] == self.index[v] {
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack.remove(w);
+                    self.comp.insert(w, self.ncomps);
+                    if w == v {
+                        break;
+                    }
+                }
+                self.ncomps += 1;
+            }
+        }
+    }
+
+    let mut t = Tarjan { edges,
+                         index: HashMap::new(),
+                         low: HashMap::new(),
+                         stack: vec![],
+                         on_stack: HashSet::new(),
+                         comp: HashMap::new(),
+                         ncomps: 0 };
+    for v in edges.keys() {
+        if !t.index.contains_key(v.as_str()) {
+            t.visit(v);
+        }
+    }
+
+    let mut out = HashSet::new();
+    for (from, tos) in edges {
+        for to in tos.keys() {
+            if t.comp.get(from.as_str()) == t.comp.get(to.as_str()) {
+                out.insert((from.clone(), to.clone()));
+            }
+        }
+    }
+    out
+}
+
+pub fn hierarchy_dot(project: &Project, top: Option<&str>) -> String {
+    let edges = children(project);
+    let recursive = recursive_edges(&edges);
+
+    // with a top, anything it can't reach is unused.
+    let mut reachable: HashSet<&str> = HashSet::new();
+    let mut todo: Vec<&str> = top.into_iter().collect();
+    while let Some(m) = todo.pop() {
+        if reachable.insert(m) {
+            if let Some(tos) = edges.get(m) {
+                todo.extend(tos.keys().map(|s| s.as_str()));
+            }
+        }
+    }
+
+    let mut nodes: Vec<&str> = edges.keys().map(|s| s.as_str()).collect();
+    for tos in edges.values() {
+        for to in tos.keys() {
+            if !nodes.contains(&to.as_str()) {
+                nodes.push(to);
+            }
+        }
+    }
+
+    let mut root = Cluster::default();
+    for name in &nodes {
+        let mut attrs = vec![format!("label={}", quote(name.rsplit('/').next().unwrap_or(name)))];
+        if is_builtin(name) || *name == MEMORY {
+            attrs.push("shape=box".to_string());
+        } else if project.module(name).is_none() {
+            attrs.push("color=red".to_string());
+            attrs.push("shape=octagon".to_string());
+        }
+        if top == Some(*name) {
+            attrs.push("penwidth=2".to_string());
+        } else if top.is_some() && !reachable.contains(name) {
+            attrs.push("style=dashed".to_string());
+        }
+        root.insert(&path_prefixes(name), format!("{} [{}];", quote(name), attrs.join(", ")));
+    }
+
+    let mut out = String::from("digraph hierarchy {\n  rankdir=TB;\n  node [shape=ellipse];\n");
+    root.write(&mut out, 2, &mut 0);
+    for (from, tos) in &edges {
+        for (to, count) in tos {
+            let mut attrs = vec![];
+            if *count > 1 {
+                attrs.push(format!("label=\"x{}\"", count));
+            }
+            if recursive.contains(&(from.clone(), to.clone())) {
+                attrs.push("color=red".to_string());
+            }
+            let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+            out.push_str(&format!("  {} -> {}{};\n", quote(from), quote(to), attrs));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DotOptions {
+    pub cluster: bool,
+    pub collapse_buses: bool,
+}
+
+impl DotOptions {
+    pub fn new() -> DotOptions {
+        DotOptions { cluster: true, collapse_buses: false }
+    }
+}
+
+fn device_direction(dev: &Device, terminal: &str) -> Direction {
+    let terms = match &dev.memory {
+        Some(mem) => memory_terminals(mem),
+        None => gate_terminals(&dev.module).unwrap_or_default(),
+    };
+    let bit = Bit::Name(terminal.to_string());
+    terms.into_iter().find(|t| t.sig.bits().contains(&bit)).map_or(In, |t| t.direction)
+}
+
+pub fn netlist_dot(netlist: &Netlist, opts: &DotOptions) -> String {
+    // with buses collapsed A[0] .. A[3] share one node, the edge carries the
+    // bit count.
+    let net_node = |net: &str| -> String {
+        if opts.collapse_buses { split_bit(net).0.to_string() } else { net.to_string() }
+    };
+
+    let mut root = Cluster::default();
+    let mut net_nodes: Vec<String> = vec![];
+    let mut edges: BTreeMap<(String, String), usize> = BTreeMap::new();
+    let mut edge = |from: String, to: String| *edges.entry((from, to)).or_insert(0) += 1;
+
+    for dev in &netlist.devices {
+        let mut path: Vec<&str> = dev.name.split('.').collect();
+        let leaf = path.pop().unwrap_or("");
+        let clusters: Vec<String> = if opts.cluster {
+            (1..=path.len()).map(|i| path[..i].join(".")).collect()
+        } else {
+            vec![]
+        };
+        let id = format!("dev:{}", dev.name);
+        let label = format!("{}\\n{}", leaf, dev.module.trim_start_matches("/gates/"));
+        root.insert(&clusters, format!("{} [label=\"{}\", shape=box];", quote(&id), label.replace("\"", "\\\"")));
+        for (term, net) in &dev.connections {
+            let n = format!("net:{}", net_node(net));
+            match device_direction(dev, term) {
+                Out => edge(id.clone(), n),
+                In => edge(n, id.clone()),
+                InOut => {
+                    edge(id.clone(), n.clone());
+                    edge(n, id.clone());
+                }
+            }
+        }
+    }
+
+    let mut port_nodes = vec![];
+    for (k, port) in netlist.ports.iter().enumerate() {
+        let dir = port.direction.unwrap_or(In);
+        let shape = match dir {
+            In => "invhouse",
+            Out => "house",
+            InOut => "diamond",
+        };
+        let id = format!("port:{}", k);
+        let bits: Vec<String> =
+            port.sig.bits().into_iter().filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None }).collect();
+        let label = match (bits.first(), bits.last()) {
+            (Some(first), Some(last)) if first != last => format!("{}..{}", first, last),
+            (Some(first), _) => first.clone(),
+            _ => String::new(),
+        };
+        port_nodes.push(format!("{} [label={}, shape={}];", quote(&id), quote(&label), shape));
+        for net in &port.nets {
+            let n = format!("net:{}", net_node(net));
+            match dir {
+                In => edge(id.clone(), n),
+                _ => edge(n, id.clone()),
+            }
+        }
+    }
+
+    for net in &netlist.nets {
+        let n = net_node(&net.name);
+        let id = format!("net:{}", n);
+        let used = edges.keys().any(|(from, to)| *from == id || *to == id);
+        if used && !net_nodes.contains(&n) {
+            net_nodes.push(n);
+        }
+    }
+
+    let mut out = format!("digraph {} {{\n  rankdir=LR;\n", quote(&netlist.top));
+    for p in &port_nodes {
+        out.push_str(&format!("  {}\n", p));
+    }
+    root.write(&mut out, 2, &mut 0);
+    for n in &net_nodes {
+        out.push_str(&format!("  {} [label={}, shape=point, xlabel={}];\n",
+                              quote(&format!("net:{}", n)),
+                              quote(""),
+                              quote(n)));
+    }
+    for ((from, to), count) in &edges {
+        let attrs = if *count > 1 && opts.collapse_buses {
+            format!(" [label=\"{}\", penwidth=2]", count)
+        } else {
+            String::new()
+        };
+        out.push_str(&format!("  {} -> {}{};\n", quote(from), quote(to), attrs));
+    }
+    out.push_str("}\n");
+    out
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(path_prefixes("/user/fast/and4"), vec!["/user".to_string(), "/user/fast".to_string()]);
+        assert_eq!(path_prefixes("/user/AND2"), vec!["/user".to_string()]);
+    }
+
+    #[test]
+    fn hierarchy1() {
+        let p = project("GarrInc32.json");
+        let got = hierarchy_dot(&p, Some("/user/GarrInc32"));
+        assert!(got.contains("  \"/user/GarrInc32\" -> \"/user/GarrInc4\" [label=\"x8\"];\n"));
+        assert!(got.contains("label=\"/user/fast\";"));
+        assert!(got.contains("\"/user/GarrInc32\" [label=\"GarrInc32\", penwidth=2];"));
+    }
+
+    #[test]
+    fn hierarchy2() {
+        let p = Project::from_str(r#"["Jade", {"/user/A": {"schematic": [["/user/B", [0, 0, 0]]], "icon": []},
+                                               "/user/B": {"schematic": [["/user/A", [0, 0, 0]]], "icon": []},
+                                               "/user/C": {"schematic": [], "icon": []}}]"#).unwrap();
+        let got = hierarchy_dot(&p, Some("/user/A"));
+        assert!(got.contains("\"/user/A\" -> \"/user/B\" [color=red];"));
+        assert!(got.contains("\"/user/C\" [label=\"C\", style=dashed];"));
+    }
+
+    #[test]
+    fn netlist1() {
+        let p = project("use-and2.json");
+        let n = Netlist::flatten(&p, "/user/UseAND2", &NetConfig::new()).unwrap();
+        let got = netlist_dot(&n, &DotOptions::new());
+        assert!(got.contains("subgraph cluster_1 {\n    label=\"AND2_0\";\n    \"dev:AND2_0.and2_0\""));
+        assert!(got.contains("\"dev:AND2_0.and2_0\" -> \"net:AND2_0.out\";"));
+        // nothing uses the tie nets here.
+        assert!(!got.contains("net:vdd"));
+    }
+
+    #[test]
+    fn netlist2() {
+        let p = project("Mux4Rep1.json");
+        let n = Netlist::flatten(&p, "/user/Mux4Rep1", &NetConfig::new()).unwrap();
+        let opts = DotOptions { cluster: false, collapse_buses: true };
+        let got = netlist_dot(&n, &opts);
+        assert!(got.contains("\"net:D\" -> \"dev:mux4_0\" [label=\"4\", penwidth=2];"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::types::*;
+
+// Test times as written, "99n", "2.5ns", "1ms" or "3s", and the same time in
+// the units each backend wants.
+
+impl Duration {
+    pub fn from_str(s: &str) -> E<Duration> {
+        let s = s.trim();
+        let split = s.find(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E').unwrap_or(s.len());
+        let n = match s[..split].trim().parse::<f64>() {
+            Ok(n) => n,
+            Err(_) => return bailfmt!("Bad number in duration: {:?}", s),
+        };
+        Self::from_unit(n, &s[split..])
+    }
+
+    // unit is a scale letter with an optional s, or a bare s for seconds.
+    pub fn from_unit(n: f64, unit: &str) -> E<Duration> {
+        let lower = unit.to_lowercase();
+        let scale = match lower.as_str() {
+            "s" => return Ok(Duration::Second(n)),
+            _ => lower.strip_suffix('s').unwrap_or(&lower),
+        };
+        Ok(match scale {
+            "m" => Duration::MilliSecond(n),
+            "u" => Duration::MicroSecond(n),
+            "n" => Duration::NanoSecond(n),
+            "p" => Duration::PicoSecond(n),
+            "f" => Duration::FemptoSecond(n),
+            "a" => Duration::AttoSecond(n),
+            _ => return bailfmt!("Unknown duration unit: {:?}", unit),
+        })
+    }
+
+    pub fn seconds(&self) -> f64 {
+        match *self {
+            Duration::Second(n) => n,
+            Duration::MilliSecond(n) => n * 1e-3,
+            Duration::MicroSecond(n) => n * 1e-6,
+            Duration::NanoSecond(n) => n * 1e-9,
+            Duration::PicoSecond(n) => n * 1e-12,
+            Duration::FemptoSecond(n) => n * 1e-15,
+            Duration::AttoSecond(n) => n * 1e-18,
+        }
+    }
+
+    // whole femtoseconds, the gate simulator's clock. Negative times are 0.
+    pub fn femtoseconds(&self) -> u64 {
+        let fs = match *self {
+            Duration::Second(n) => n * 1e15,
+            Duration::MilliSecond(n) => n * 1e12,
+            Duration::MicroSecond(n) => n * 1e9,
+            Duration::NanoSecond(n) => n * 1e6,
+            Duration::PicoSecond(n) => n * 1e3,
+            Duration::FemptoSecond(n) => n,
+            Duration::AttoSecond(n) => n * 1e-3,
+        };
+        fs.round().max(0.0) as u64
+    }
+}
+
+// the way a test writes it, 99n or 1ms.
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Duration::Second(n) => write!(f, "{}s", n),
+            Duration::MilliSecond(n) => write!(f, "{}ms", n),
+            Duration::MicroSecond(n) => write!(f, "{}u", n),
+            Duration::NanoSecond(n) => write!(f, "{}n", n),
+            Duration::PicoSecond(n) => write!(f, "{}p", n),
+            Duration::FemptoSecond(n) => write!(f, "{}f", n),
+            Duration::AttoSecond(n) => write!(f, "{}a", n),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        assert_eq!(Duration::from_str("99n"), Ok(Duration::NanoSecond(99.0)));
+        assert_eq!(Duration::from_str(".35ns"), Ok(Duration::NanoSecond(0.35)));
+        assert_eq!(Duration::from_str("1ms"), Ok(Duration::MilliSecond(1.0)));
+        assert_eq!(Duration::from_str("2 s"), Ok(Duration::Second(2.0)));
+        assert_eq!(Duration::from_str("1e3P"), Ok(Duration::PicoSecond(1e3)));
+        assert!(Duration::from_str("10x").is_err());
+        assert!(Duration::from_str("n").is_err());
+    }
+
+    #[test]
+    fn femtoseconds() {
+        assert_eq!(Duration::NanoSecond(49.123).femtoseconds(), 49_123_000);
+        assert_eq!(Duration::MilliSecond(1.0).femtoseconds(), 1_000_000_000_000);
+        assert_eq!(Duration::AttoSecond(400.0).femtoseconds(), 0);
+        assert_eq!(Duration::PicoSecond(-5.0).femtoseconds(), 0);
+        assert_eq!(Duration::MicroSecond(2.0).seconds(), 2e-6);
+        assert_eq!(Duration::NanoSecond(0.35).to_string(), "0.35n");
+        assert_eq!(Duration::MilliSecond(2.0).to_string(), "2ms");
+    }
+}
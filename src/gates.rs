@@ -0,0 +1,167 @@
+use crate::types::*;
+
+// The /gates/* library is built into jade, so the schematic json never
+// carries icons for it. The terminal offsets below are the ones jade draws
+// for the unrotated icon.
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum GateKind {
+    And,
+    Or,
+    Xor,
+    Nand,
+    Nor,
+    Xnor,
+    Buffer,
+    Inverter,
+    Tristate,
+    Mux2,
+    Mux4,
+    Dreg,
+    Memory,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GateTerminal {
+    pub sig: Sig,
+    pub x: i32,
+    pub y: i32,
+    pub direction: Direction,
+}
+
+fn term(name: &str, x: i32, y: i32, direction: Direction) -> GateTerminal {
+    let sig = Sig::from_str(name).unwrap();
+    GateTerminal { sig, x, y, direction }
+}
+
+// inputs stacked 16 apart down the left edge, output on the right.
+fn logic_gate(ninputs: usize) -> Vec<GateTerminal> {
+    let names = ["A", "B", "C", "D"];
+    let mut terms: Vec<GateTerminal> = (0..ninputs).map(|i| term(names[i], 0, 16 * i as i32, In)).collect();
+    terms.push(term("Z", 48, 8 * (ninputs as i32 - 1), Out));
+    terms
+}
+
+// the module a memory instance goes by, schematics place memories as a part
+// of their own rather than as a /gates/ submodule.
+pub const MEMORY: &str = "/gates/memory";
+
+pub fn is_builtin(module_name: &str) -> bool {
+    module_name.starts_with("/gates/")
+}
+
+pub fn gate_kind(module_name: &str) -> Option<GateKind> {
+    if !is_builtin(module_name) {
+        return None;
+    }
+    let gate = &module_name["/gates/".len()..];
+    let base = gate.trim_end_matches(|c: char| c.is_ascii_digit());
+    Some(match base {
+        "and" => GateKind::And,
+        "or" => GateKind::Or,
+        "xor" => GateKind::Xor,
+        "nand" => GateKind::Nand,
+        "nor" => GateKind::Nor,
+        "xnor" => GateKind::Xnor,
+        "buffer" | "buffer_h" => GateKind::Buffer,
+        "inverter" => GateKind::Inverter,
+        "tristate" => GateKind::Tristate,
+        "mux" if gate == "mux2" => GateKind::Mux2,
+        "mux" if gate == "mux4" => GateKind::Mux4,
+        "dreg" => GateKind::Dreg,
+        _ => return None,
+    })
+}
+
+pub fn gate_terminals(module_name: &str) -> Option<Vec<GateTerminal>> {
+    let kind = gate_kind(module_name)?;
+    let gate = &module_name["/gates/".len()..];
+    let ninputs = |gate: &str| gate.chars().last().and_then(|c| c.to_digit(10)).unwrap_or(0) as usize;
+
+    Some(match kind {
+        GateKind::And | GateKind::Or | GateKind::Xor | GateKind::Nand | GateKind::Nor | GateKind::Xnor => {
+            let n = ninputs(gate);
+            if n < 2 || n > 4 {
+                return None;
+            }
+            logic_gate(n)
+        }
+        GateKind::Buffer | GateKind::Inverter => vec![term("A", 0, 0, In), term("Z", 32, 0, Out)],
+        GateKind::Tristate => vec![term("A", 0, 0, In), term("E", 16, 16, In), term("Z", 32, 0, Out)],
+        GateKind::Mux2 => vec![term("D1", 0, 0, In), term("D0", 0, 16, In), term("S", 8, 32, In), term("Y", 16, 8, Out)],
+        GateKind::Mux4 => vec![term("D3", 0, 0, In),
+                               term("D2", 0, 16, In),
+                               term("D1", 0, 32, In),
+                               term("D0", 0, 48, In),
+                               term("S[1:0]", 8, 64, In),
+                               term("Y", 16, 24, Out)],
+        GateKind::Dreg => vec![term("D", 0, 0, In), term("CLK", 0, 16, In), term("Q", 40, 0, Out)],
+        GateKind::Memory => return None,
+    })
+}
+
+// a bus name like A0[1:0], or just A0 for a single bit.
+fn bus(name: &str, width: u32) -> String {
+    if width == 1 { name.to_string() } else { format!("{}[{}:0]", name, width - 1) }
+}
+
+// each memory port is a block of address, output enable, write enable and
+// clock terminals down the left edge with its data bus on the right.
+pub fn memory_terminals(mem: &Memory) -> Vec<GateTerminal> {
+    let mut terms = vec![];
+    for p in 0..mem.nports {
+        let y = 32 * p as i32;
+        terms.push(term(&bus(&format!("A{}", p), mem.naddr), 0, y, In));
+        terms.push(term(&format!("OE{}", p), 0, y + 8, In));
+        terms.push(term(&format!("WE{}", p), 0, y + 16, In));
+        terms.push(term(&format!("CLK{}", p), 0, y + 24, In));
+        terms.push(term(&bus(&format!("D{}", p), mem.ndata), 72, y, InOut));
+    }
+    terms
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gate_kind1() {
+        assert_eq!(gate_kind("/gates/nand4"), Some(GateKind::Nand));
+        assert_eq!(gate_kind("/gates/buffer_h"), Some(GateKind::Buffer));
+        assert_eq!(gate_kind("/gates/mux4"), Some(GateKind::Mux4));
+        assert_eq!(gate_kind("/gates/mux3"), None);
+        assert_eq!(gate_kind("/user/AND2"), None);
+    }
+
+    #[test]
+    fn gate_terminals1() {
+        let got = gate_terminals("/gates/and2").unwrap();
+        let expect = vec![term("A", 0, 0, In), term("B", 0, 16, In), term("Z", 48, 8, Out)];
+        assert_eq!(got, expect);
+    }
+
+    #[test]
+    fn gate_terminals2() {
+        let got = gate_terminals("/gates/nor4").unwrap();
+        assert_eq!(got.len(), 5);
+        assert_eq!(got[4], term("Z", 48, 24, Out));
+        assert!(gate_terminals("/gates/and5").is_none());
+    }
+
+    #[test]
+    fn memory_terminals1() {
+        let mem = Memory { coord3: Coord3 { x: 0, y: 0, r: Rot0 },
+                           name: None,
+                           contents: String::new(),
+                           nports: 2,
+                           naddr: 2,
+                           ndata: 1 };
+        let got = memory_terminals(&mem);
+        assert_eq!(got.len(), 10);
+        assert_eq!(got[0], term("A0[1:0]", 0, 0, In));
+        assert_eq!(got[9], term("D1", 72, 32, InOut));
+    }
+}
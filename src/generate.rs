@@ -0,0 +1,173 @@
+use std::collections::HashSet;
+
+use crate::types::*;
+use crate::vector::Role;
+
+// Test vectors from a reference model. The skeleton's groups and .cycle
+// line say which bits are inputs (asserted groups) and which are outputs
+// (sampled groups). The model gets the input bits and returns the output
+// bits, both most significant first in column order.
+//
+//   let src = ".group inputs A B\n.group outputs Y\n.cycle assert inputs tran 9n sample outputs\n";
+//   let mut test = ModTest::from_str(src)?;
+//   test.generate(Inputs::Exhaustive, |i| vec![i[0] && i[1]])?;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Inputs {
+    // every input combination, counting up from all zeros.
+    Exhaustive,
+    // this many distinct combinations, picked by a seeded generator so the
+    // same seed gives the same test.
+    Random { count: usize, seed: u64 },
+}
+
+// more inputs than this is too many vectors to enumerate.
+const MAX_EXHAUSTIVE_BITS: usize = 20;
+
+// xorshift64*, plenty for picking test cases.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+fn binval(b: bool) -> BinVal {
+    if b {
+        H
+    } else {
+        L
+    }
+}
+
+impl ModTest {
+    // bit counts of the driven and the sampled columns.
+    fn io_widths(&self) -> E<(usize, usize)> {
+        let (mut inputs, mut outputs) = (0, 0);
+        for name in self.column_groups() {
+            let width = self.group_bits(name)?.len();
+            match self.group_role(name) {
+                Some(Role::Driven) => inputs += width,
+                Some(Role::Expected) => outputs += width,
+                _ => return bailfmt!("Group {} is both asserted and sampled, can't generate vectors for it", name),
+            }
+        }
+        Ok((inputs, outputs))
+    }
+
+    // appends a vector per input combination, each with a comment saying
+    // what it tests.
+    pub fn generate<F>(&mut self, inputs: Inputs, mut model: F) -> E<()>
+        where F: FnMut(&[bool]) -> Vec<bool>
+    {
+        let (nin, nout) = self.io_widths()?;
+        let cases: Vec<Vec<bool>> = match inputs {
+            Inputs::Random { count, seed } if nin >= 64 || count < 1 << nin => {
+                let mut rng = Rng(seed.max(1));
+                let mut seen = HashSet::new();
+                while seen.len() < count {
+                    let case: Vec<bool> = (0..nin).map(|_| rng.next() >> 63 == 1).collect();
+                    seen.insert(case);
+                }
+                let mut cases: Vec<Vec<bool>> = seen.into_iter().collect();
+                cases.sort();
+                cases
+            }
+            _ if nin > MAX_EXHAUSTIVE_BITS => {
+                let msg = "input bits are too many to enumerate, the most is";
+                return bail!(format!("{} {} {}, sample them instead", nin, msg, MAX_EXHAUSTIVE_BITS));
+            }
+            // a random sample as big as the input space is all of it.
+            _ => (0..1u64 << nin).map(|n| (0..nin).rev().map(|i| (n >> i) & 1 == 1).collect()).collect(),
+        };
+
+        // nothing is added unless every case works out.
+        let mut lines = vec![];
+        for case in cases {
+            let expected = model(&case);
+            if expected.len() != nout {
+                return bailfmt!("The model returned {} output bits, the sampled groups have {}", expected.len(), nout);
+            }
+            // inputs and outputs back into column order.
+            let (mut ins, mut outs) = (case.iter(), expected.iter());
+            let mut bin_vals = vec![];
+            for name in self.column_groups() {
+                let from = if self.group_role(name) == Some(Role::Driven) { &mut ins } else { &mut outs };
+                for _ in 0..self.group_bits(name)?.len() {
+                    bin_vals.push(binval(*from.next().unwrap()));
+                }
+            }
+            let mut tl = TestLine { bin_vals, comment: None, pos: Pos::default() };
+
+            let values = self.signal_values(&tl)?;
+            let describe = |role: Role| -> Vec<String> {
+                values.iter().filter(|v| v.role == role).map(|v| format!("{}={}", v.sig, v.value())).collect()
+            };
+            tl.comment = Some(format!(" {}: {} -> {}",
+                                      self.test_lines.len() + lines.len() + 1,
+                                      describe(Role::Driven).join(", "),
+                                      describe(Role::Expected).join(", ")));
+            lines.push(tl);
+        }
+        self.test_lines.extend(lines);
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist::NetConfig;
+    use crate::runner::run_test;
+    use std::path::Path;
+
+    #[test]
+    fn and2() {
+        let src = ".group inputs in1 in2\n.group outputs out\n.cycle assert inputs tran 9n sample outputs\n";
+        let mut test = ModTest::from_str(src).unwrap();
+        test.generate(Inputs::Exhaustive, |i| vec![i[0] && i[1]]).unwrap();
+        assert_eq!(test.test_lines.len(), 4);
+        assert_eq!(test.test_lines[3].bin_vals, vec![H, H, H]);
+        assert_eq!(test.test_lines[1].comment, Some(" 2: in1=0, in2=1 -> out=0".to_string()));
+
+        let p = Project::from_file(Path::new("./test-data/use-and2.json")).unwrap();
+        let report = run_test(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(report.passed(), "{}", report);
+
+        assert!(test.generate(Inputs::Exhaustive, |_| vec![]).is_err());
+        // a bad result on the last case leaves the vectors as they were.
+        assert!(test.generate(Inputs::Exhaustive, |i| vec![i[0]; if i[0] && i[1] { 2 } else { 1 }]).is_err());
+        assert_eq!(test.test_lines.len(), 4);
+    }
+
+    #[test]
+    fn random() {
+        let src = ".group inputs A[15:0] B[15:0]\n.group outputs S[16:0]\n.cycle assert inputs sample outputs\n";
+        let add = |i: &[bool]| {
+            let n = |bits: &[bool]| bits.iter().fold(0u32, |n, b| n * 2 + *b as u32);
+            let s = n(&i[..16]) + n(&i[16..]);
+            (0..17).rev().map(|k| (s >> k) & 1 == 1).collect()
+        };
+        let mut test = ModTest::from_str(src).unwrap();
+        assert!(test.generate(Inputs::Exhaustive, add).is_err());
+        test.generate(Inputs::Random { count: 10, seed: 7 }, add).unwrap();
+        assert_eq!(test.test_lines.len(), 10);
+
+        let mut again = ModTest::from_str(src).unwrap();
+        again.generate(Inputs::Random { count: 10, seed: 7 }, add).unwrap();
+        assert_eq!(again.test_lines, test.test_lines);
+
+        // asking for more than there are gives all of them.
+        let src = ".group inputs A B\n.group outputs Y\n.cycle assert inputs sample outputs\n";
+        let mut test = ModTest::from_str(src).unwrap();
+        test.generate(Inputs::Random { count: 100, seed: 1 }, |i| vec![i[0] | i[1]]).unwrap();
+        assert_eq!(test.test_lines.len(), 4);
+    }
+}
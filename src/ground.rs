@@ -0,0 +1,35 @@
+use serde_json::Value;
+
+use crate::common::*;
+use crate::types::*;
+
+impl Ground {
+    pub fn from_value(val: &Value) -> E<Ground> {
+        let mut val_iter = bailif!(tagged_array("ground", &val), "Ground::from_value failes to decode")?;
+
+        let coord3: Coord3 = match val_iter.next() {
+            Some(c) => serde_json::from_value::<Coord3>(c.clone())?,
+            None => {
+                return bailfmt!("Ground expects 1 element, a 3 number array, got: {:?}", val);
+            }
+        };
+        Ok(Ground { coord3 })
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ground1() {
+        let val = json!(["ground", [0, 8, 0]]);
+        let expect = Ground { coord3: Coord3 { x: 0, y: 8, r: Rot0 } };
+        assert_eq!(Ground::from_value(&val), Ok(expect));
+    }
+}
@@ -1,12 +1,77 @@
 use crate::types::*;
-use std::collections::HashMap;
+
+impl Group {
+    // how many test vector columns the group takes.
+    pub fn width(&self) -> usize {
+        self.sigs.iter().map(|s| s.width()).sum()
+    }
+
+    // the bit names, "A[1:0] B" -> A[1] A[0] B
+    pub fn bits(&self) -> E<Vec<String>> {
+        let mut bits = vec![];
+        for sig in &self.sigs {
+            for bit in sig.bits() {
+                match bit {
+                    Bit::Name(name) => bits.push(name),
+                    Bit::Const(_) => return bailfmt!("Group {} contains a constant: {:?}", self.name, sig),
+                }
+            }
+        }
+        Ok(bits)
+    }
+}
 
 impl Groups {
     pub fn new() -> Groups {
-        Groups { sig_set: HashMap::new() }
+        Groups { groups: vec![] }
+    }
+
+    // a group can only be declared once.
+    pub fn insert_signals(&mut self, name: String, sigs: Vec<Sig>, pos: Pos) -> E<()> {
+        if let Some(g) = self.get(&name) {
+            return bailfmt!("Group {} is already declared on line {}", name, g.pos.line);
+        }
+        self.groups.push(Group { name, sigs, pos });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|g| g.name == name)
     }
 
-    pub fn insert_signals(&mut self, name: String, sigs: Vec<Sig>) -> () {
-        self.sig_set.insert(name, sigs);
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Group> {
+        self.groups.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declared_order() {
+        let test = ModTest::from_str(".group outputs Y[1:0]\n.group inputs A[3:0] B\n").unwrap();
+        let names: Vec<&str> = test.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["outputs", "inputs"]);
+        let inputs = test.groups.get("inputs").unwrap();
+        assert_eq!(inputs.width(), 5);
+        assert_eq!(inputs.bits().unwrap(), vec!["A[3]", "A[2]", "A[1]", "A[0]", "B"]);
+        assert!(test.groups.contains("outputs") && !test.groups.contains("Y"));
+
+        match ModTest::from_str(".group inputs A\n.group inputs B\n") {
+            Err(b) => assert_eq!(b.more.unwrap().msg, "Group inputs is already declared on line 1"),
+            _ => panic!("expected a redefinition error"),
+        }
     }
 }
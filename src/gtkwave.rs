@@ -0,0 +1,193 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::sim::*;
+use crate::types::*;
+use crate::vcd::*;
+
+// Waveforms for what a test's .plot lines ask for: a VCD of just those
+// signals, a GTKWave save file that lists them with their radix, and one
+// translate filter per .plotdef so values show as tags, op(id[31:26])
+// reads LD, ST, ... instead of hex.
+//
+//   gtkwave beta.vcd beta.gtkw
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Radix {
+    Bin,
+    Hex,
+    Dec,
+}
+
+struct Plotted<'a> {
+    sig: &'a Sig,
+    radix: Radix,
+    plotdef: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Waveforms {
+    pub vcd: String,
+    pub save: String,
+    // (file name, contents), the save file refers to them by name.
+    pub filters: Vec<(String, String)>,
+}
+
+fn plotted(test: &ModTest) -> E<Vec<Plotted>> {
+    let mut out = vec![];
+    for dir in &test.plot_dirs {
+        out.push(match &dir.plot {
+            PlotDirective::BinStyle(sig) => Plotted { sig, radix: Radix::Bin, plotdef: None },
+            PlotDirective::HexStyle(sig) => Plotted { sig, radix: Radix::Hex, plotdef: None },
+            PlotDirective::DecStyle(sig) => Plotted { sig, radix: Radix::Dec, plotdef: None },
+            // single wires show as levels, buses in hex.
+            PlotDirective::SimplePlot(sig) => {
+                let radix = if sig.width() == 1 { Radix::Bin } else { Radix::Hex };
+                Plotted { sig, radix, plotdef: None }
+            }
+            PlotDirective::PlotDefStyle(name, sig) => match test.plot_defs.iter().position(|d| d.name == *name) {
+                Some(i) => Plotted { sig, radix: Radix::Hex, plotdef: Some(i) },
+                None => return bailfmt!("No .plotdef for .plot {}({})", name, sig),
+            },
+        });
+    }
+    Ok(out)
+}
+
+// the bits the .plot lines name, each once, to select what goes in the VCD.
+pub fn plotted_bits(test: &ModTest) -> E<Vec<String>> {
+    let mut bits = vec![];
+    for p in plotted(test)? {
+        for bit in p.sig.bits() {
+            match bit {
+                Bit::Name(name) if !bits.contains(&name) => bits.push(name),
+                Bit::Name(_) => {}
+                Bit::Const(_) => return bailfmt!("Can't plot a constant: {}", p.sig),
+            }
+        }
+    }
+    Ok(bits)
+}
+
+// gtkwave's trace flags: right justified, plus hex, decimal or binary, plus
+// file translated when a filter applies.
+fn flags(p: &Plotted) -> u32 {
+    let radix = match p.radix {
+        Radix::Hex => 0x2,
+        Radix::Dec => 0x4,
+        Radix::Bin => 0x8,
+    };
+    let translated = if p.plotdef.is_some() { 0x2000 } else { 0 };
+    0x20 | radix | translated
+}
+
+// tag i of a plotdef names the value i, written the way gtkwave shows hex
+// for a bus of that width.
+fn filter(def: &PlotDef, width: usize) -> String {
+    let digits = ((width + 3) / 4).max(1);
+    let mut out = String::new();
+    for (i, tag) in def.tags.iter().enumerate() {
+        writeln!(out, "{:0width$X} {}", i, tag, width = digits).unwrap();
+    }
+    out
+}
+
+pub fn waveforms(sim: &Simulator, top: &str, test: &ModTest, dumpfile: &str) -> E<Waveforms> {
+    let plotted = plotted(test)?;
+    let bits = plotted_bits(test)?;
+    let selection: Vec<&str> = bits.iter().map(|b| b.as_str()).collect();
+    let vcd = vcd(sim, top, if plotted.is_empty() { None } else { Some(&selection) })?;
+
+    let scope = top.rsplit('/').next().unwrap_or(top);
+    let stem = Path::new(dumpfile).file_stem().and_then(|s| s.to_str()).unwrap_or("waves");
+    let mut save = String::new();
+    writeln!(save, "[*] jade-parse\n[dumpfile] \"{}\"\n[timestart] 0", dumpfile).unwrap();
+
+    // a filter file for each plotdef that is used, by the first width it's used at.
+    let mut filters: Vec<(usize, String, String)> = vec![];
+    for p in &plotted {
+        if let Some(d) = p.plotdef {
+            let def = &test.plot_defs[d];
+            if !filters.iter().any(|(i, _, _)| *i == d) {
+                filters.push((d, format!("{}_{}.txt", stem, def.name), filter(def, p.sig.width())));
+            }
+        }
+    }
+
+    for p in &plotted {
+        if let Some(d) = p.plotdef {
+            let n = filters.iter().position(|(i, _, _)| *i == d).unwrap() + 1;
+            writeln!(save, "^{} {}", n, filters[n - 1].1).unwrap();
+        }
+        writeln!(save, "@{:x}", flags(p)).unwrap();
+        let names: Vec<String> = p.sig
+                                  .bits()
+                                  .into_iter()
+                                  .filter_map(|b| if let Bit::Name(n) = b { Some(format!("{}.{}", scope, n)) } else { None })
+                                  .collect();
+        if names.len() == 1 {
+            writeln!(save, "{}", names[0]).unwrap();
+        } else {
+            let label = match p.plotdef {
+                Some(d) => format!("{}({})", test.plot_defs[d].name, p.sig),
+                None => p.sig.to_string(),
+            };
+            writeln!(save, "#{{{}}} {}", label, names.join(" ")).unwrap();
+        }
+    }
+
+    Ok(Waveforms { vcd, save, filters: filters.into_iter().map(|(_, name, body)| (name, body)).collect() })
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist::*;
+    use crate::runner::run_on;
+    use std::path::Path;
+
+    #[test]
+    fn beta_plots() {
+        let src = std::fs::read_to_string("./test-data/modtests/beta.test").unwrap();
+        let test = ModTest::from_str(&src).unwrap();
+        let bits = plotted_bits(&test).unwrap();
+        assert_eq!(&bits[..4], &["clk", "reset", "irq", "ia[31]"]);
+        // the id slices are already in id[31:0].
+        assert!(!bits[3..].contains(&"clk".to_string()));
+        assert_eq!(bits.iter().filter(|b| b.starts_with("id[")).count(), 32);
+
+        let op = &test.plot_defs[1];
+        let got = filter(op, 6);
+        assert!(got.starts_with("00 ???\n01 ???\n"));
+        assert!(got.contains("\n18 LD\n19 ST\n"));
+        assert!(got.ends_with("3F ???\n"));
+    }
+
+    #[test]
+    fn and2() {
+        let p = Project::from_file(Path::new("./test-data/use-and2.json")).unwrap();
+        let src = ".group inputs in1 in2\n.group outputs out\n.cycle assert inputs tran 9n sample outputs tran 1n\n\
+                   .plotdef level LOW HIGH\n.plot in1\n.plot X(in2,in1)\n.plot level(out)\n\n01 L\n11 H\n";
+        let test = ModTest::from_str(src).unwrap();
+        let n = Netlist::flatten(&p, "/user/AND2", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        sim.start_trace();
+        run_on(&mut sim, "/user/AND2", &test).unwrap();
+
+        let w = waveforms(&sim, "/user/AND2", &test, "out/and2.vcd").unwrap();
+        assert!(w.vcd.contains("$var wire 1 ! in1 $end\n$var wire 1 \" in2 $end\n$var wire 1 # out $end\n$upscope"));
+        assert_eq!(w.save,
+                   "[*] jade-parse\n[dumpfile] \"out/and2.vcd\"\n[timestart] 0\n\
+                    @28\nAND2.in1\n\
+                    @22\n#{in2,in1} AND2.in2 AND2.in1\n\
+                    ^1 and2_level.txt\n@2022\nAND2.out\n");
+        assert_eq!(w.filters, vec![("and2_level.txt".to_string(), "0 LOW\n1 HIGH\n".to_string())]);
+
+        let src = ".plot nope(out)\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert!(waveforms(&sim, "/user/AND2", &test, "and2.vcd").is_err());
+    }
+}
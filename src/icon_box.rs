@@ -0,0 +1,30 @@
+use crate::common::*;
+use crate::types::*;
+use serde_json::Value;
+
+impl IconBox {
+    pub fn from_value(val: &Value) -> E<IconBox> {
+        let mut val_iter = bailif!(tagged_array("box", &val), "IconBox::from_value failes to decode")?;
+        match val_iter.next() {
+            Some(c) => Ok(IconBox { coord5: serde_json::from_value::<Coord5>(c.clone())? }),
+            None => bailfmt!("box expects 1 element, a 5 number array, got: {:?}", val),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn box1() {
+        let expect = IconBox { coord5: Coord5 { x: 8, y: -8, r: Rot0, dx: 48, dy: 48 } };
+        let val = json!(["box", [8, -8, 0, 48, 48]]);
+        assert_eq!(IconBox::from_value(&val), Ok(expect));
+    }
+}
@@ -16,6 +16,15 @@ impl IconPart {
         if let Ok(x) = Circle::from_value(val) {
             return Ok(IconPart::Circle(x));
         }
+        if let Ok(x) = Arc::from_value(val) {
+            return Ok(IconPart::Arc(x));
+        }
+        if let Ok(x) = IconBox::from_value(val) {
+            return Ok(IconPart::Box(x));
+        }
+        if let Ok(x) = Property::from_value(val) {
+            return Ok(IconPart::Property(x));
+        }
         bailfmt!("IconPart::from_value finds unknown iconPart: {:?}", val)
     }
 }
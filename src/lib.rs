@@ -8,27 +8,59 @@ pub mod common;
 
 //pub mod project;
 pub mod mod_test;
+pub mod module;
+pub mod rot;
+pub mod union_find;
 
 // schematic
 pub mod dir;
+pub mod ground;
 pub mod jumper;
 pub mod line;
+pub mod memory;
 pub mod part;
 pub mod schematic;
 pub mod sig;
 pub mod signal;
 pub mod submodule;
+pub mod svg;
 pub mod types;
+pub mod vdd;
 pub mod wire;
 
 // icon
+pub mod arc;
 pub mod circle;
 pub mod icon;
+pub mod icon_box;
 pub mod icon_part;
 pub mod port;
+pub mod property;
 pub mod terminal;
 pub mod text;
 
+// netlist
+pub mod dot;
+pub mod gates;
+pub mod netlist;
+pub mod spice;
+pub mod verilog;
+pub mod vhdl;
+pub mod yosys;
+
 // test aspect
+pub mod coverage;
+pub mod cycle;
+pub mod duration;
+pub mod generate;
 pub mod groups;
+pub mod test_format;
 pub mod test_mod;
+pub mod validate;
+pub mod vector;
+
+// simulation
+pub mod gtkwave;
+pub mod runner;
+pub mod sim;
+pub mod vcd;
@@ -0,0 +1,112 @@
+use serde_json::Value;
+
+use crate::common::*;
+use crate::types::*;
+
+impl Memory {
+    pub fn from_value(val: &Value) -> E<Memory> {
+        let mut val_iter = bailif!(tagged_array("memory", &val), "Memory::from_value failes to decode")?;
+
+        let coord3: Coord3 = match val_iter.next() {
+            Some(c) => serde_json::from_value::<Coord3>(c.clone())?,
+            None => {
+                return bailfmt!("Memory expects 2 elements, a location and properties object, got: {:?}", val);
+            }
+        };
+
+        match val_iter.next() {
+            Some(Value::Object(o)) => {
+                let name = if let Some(Value::String(s)) = o.get("name") { Some(s.to_string()) } else { None };
+                let contents =
+                    if let Some(Value::String(s)) = o.get("contents") { s.to_string() } else { String::new() };
+
+                // jade defaults to a single port memory with one address and one data bit.
+                let prop = |key: &str| -> u32 {
+                    match o.get(key).and_then(numeric_property) {
+                        Some(n) => n as u32,
+                        None => 1,
+                    }
+                };
+
+                let (nports, naddr, ndata) = (prop("nports"), prop("naddr"), prop("ndata"));
+                if nports == 0 || naddr == 0 || ndata == 0 {
+                    return bailfmt!("Memory needs at least one port, address and data bit, got: {:?}", o);
+                }
+                Ok(Memory { coord3, name, contents, nports, naddr, ndata })
+            }
+            _ => bailfmt!("Memory expects 2 elements, a location and properties object, got: {:?}", val),
+        }
+    }
+}
+
+impl Memory {
+    // the initial contents, one word per whitespace separated number, in
+    // decimal, 0x hex or 0b binary. Locations past the end of contents
+    // start out zero.
+    pub fn words(&self) -> E<Vec<u64>> {
+        let mut words = vec![];
+        for word in self.contents.split_whitespace() {
+            let parsed = if word.starts_with("0x") || word.starts_with("0X") {
+                u64::from_str_radix(&word[2..], 16)
+            } else if word.starts_with("0b") || word.starts_with("0B") {
+                u64::from_str_radix(&word[2..], 2)
+            } else {
+                word.parse::<u64>()
+            };
+            match parsed {
+                Ok(w) => words.push(w),
+                Err(_) => return bailfmt!("Bad number in memory contents: {:?}", word),
+            }
+        }
+        Ok(words)
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn memory1() {
+        let val = json!(["memory", [0, 0, 0], {"name": "Mem1", "contents": "0\n1"}]);
+        let got = Memory::from_value(&val).unwrap();
+        let expect = Memory { coord3: Coord3 { x: 0, y: 0, r: Rot0 },
+                              name: Some("Mem1".to_string()),
+                              contents: "0\n1".to_string(),
+                              nports: 1,
+                              naddr: 1,
+                              ndata: 1 };
+        assert_eq!(got, expect);
+    }
+
+    #[test]
+    fn memory2() {
+        let val = json!(["memory", [0, 0, 0], {"name": "Mem2", "contents": "0 1 2 3", "nports": "2", "naddr": "2", "ndata": "2"}]);
+        let got = Memory::from_value(&val).unwrap();
+        assert_eq!((got.nports, got.naddr, got.ndata), (2, 2, 2));
+    }
+
+    #[test]
+    fn words1() {
+        let val = json!(["memory", [0, 0, 0], {"contents": "0x0 0x1 0x2 0x3 \n0x4 0x5 0x6 0x7 \n0x8 0x9 0xA 0xB\n", "naddr": "4"}]);
+        let got = Memory::from_value(&val).unwrap().words().unwrap();
+        assert_eq!(got, (0..12).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn words2() {
+        let val = json!(["memory", [0, 0, 0], {"contents": "0b1 12 zz"}]);
+        assert!(Memory::from_value(&val).unwrap().words().is_err());
+    }
+
+    #[test]
+    fn memory3() {
+        let val = json!(["memory", [0, 0, 0]]);
+        assert!(Memory::from_value(&val).is_err());
+    }
+}
@@ -6,7 +6,10 @@ const IDENT: &str = "[a-zA-Z_][a-zA-Z_0-9]*";
 const SPACE: &str = r#"[\s]*"#;
 const ONE_OR_MORE_SPACE: &str = r#"[\s]+"#;
 const NUMBER: &str = r#"[-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?"#;
-const DURATION: &str = r#"([-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?)([unpfaUNF])"#;
+const DURATION: &str = r#"([-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?)[\s]*([a-zA-Z]+)"#;
+// the right hand side of sig=value, a bus literal or a number with an SI suffix.
+const VALUE: &str =
+    r#"(?P<bus>0[xX][0-9a-fA-F]+|0[bB][01]+)|(?P<num>[-+]?[0-9]*\.?[0-9]+([eE][-+]?[0-9]+)?)(?P<si>[a-zA-Z]*)"#;
 
 fn is_ident(s: &str) -> bool {
     let pat_str = format!("^{}$", IDENT);
@@ -16,6 +19,48 @@ fn is_ident(s: &str) -> bool {
     }
 }
 
+// 3.3m, 1.8, 2k and the like, SPICE style: the scale is the first letter
+// (or meg) and anything after it, V say, is ignored.
+fn si_number(num: &str, suffix: &str) -> E<f64> {
+    let n = match num.parse::<f64>() {
+        Ok(n) => n,
+        Err(_) => return bailfmt!("Bad number: {:?}", num),
+    };
+    let suffix = suffix.to_lowercase();
+    let scale = if suffix.starts_with("meg") {
+        1e6
+    } else {
+        match suffix.chars().next() {
+            None | Some('v') => 1.0,
+            Some('t') => 1e12,
+            Some('g') => 1e9,
+            Some('k') => 1e3,
+            Some('m') => 1e-3,
+            Some('u') => 1e-6,
+            Some('n') => 1e-9,
+            Some('p') => 1e-12,
+            Some('f') => 1e-15,
+            Some('a') => 1e-18,
+            _ => return bailfmt!("Unknown scale on {}{}", num, suffix),
+        }
+    };
+    Ok(n * scale)
+}
+
+// 0x2c or 0b101
+fn bus_number(s: &str) -> E<u64> {
+    let (digits, radix) = if s[..2].eq_ignore_ascii_case("0x") { (&s[2..], 16) } else { (&s[2..], 2) };
+    match u64::from_str_radix(digits, radix) {
+        Ok(n) => Ok(n),
+        Err(_) => bailfmt!("Bus value too large: {}", s),
+    }
+}
+
+// the directive a line starts with, ".group inputs A B" -> ".group"
+fn directive_name(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
 impl ModTest {
     pub fn from_str(test_str: &str) -> E<ModTest> {
         let mut power: Vec<Power> = vec![];
@@ -26,38 +71,69 @@ impl ModTest {
         let mut test_lines = vec![];
         let mut plot_dirs = vec![];
         let mut plot_defs = vec![];
+        let mut comments = vec![];
+        let mut directives = vec![];
 
-        for line in test_str.lines() {
-            let line = line.trim();
+        for (line_no, raw) in test_str.lines().enumerate() {
+            let line = raw.trim();
+            let indent = raw.len() - raw.trim_start().len();
+            let pos = Pos { line: line_no + 1, col: raw[..indent].chars().count() + 1 };
 
             if line.is_empty() {
                 continue; // skip whitespace
-            } else if line.starts_with("//") {
-                continue; // TODO keep comments and line numbers 
-            } else if line.starts_with(".power") {
-                let mut xs = Self::parse_power(line)?;
-                power.append(&mut xs);
-            } else if line.starts_with(".group") {
-                let (name, group) = Self::parse_one_group(line)?;
-                groups.insert_signals(name, group);
-            } else if line.starts_with(".thresholds") {
-                thresholds = Some(Self::parse_thresholds(line)?);
-            } else if line.starts_with(".mode") {
-                mode = Some(Self::parse_mode(line)?);
-            } else if line.starts_with(".cycle") {
-                cycle_line = Some(Self::parse_cycle_line(line)?);
-            } else if line.starts_with(".plotdef") {
-                plot_defs.push(Self::parse_plot_def(line)?);
-            } else if line.starts_with(".plot ") {
-                plot_dirs.push(Self::parse_plot_directive(line)?);
-            } else {
+            }
+            // a comment after a directive, kept as a comment of its own.
+            // .verilog text is passed through as is, // and all.
+            let line = match line.find("//") {
+                Some(at) if line.starts_with('.') && directive_name(line) != ".verilog" => {
+                    let col = pos.col + line[..at].chars().count();
+                    comments.push(Comment { text: line[at + 2..].to_string(), pos: Pos { col, ..pos } });
+                    line[..at].trim_end()
+                }
+                _ => line,
+            };
+            let parsed = match directive_name(line) {
+                _ if line.starts_with("//") => {
+                    comments.push(Comment { text: line[2..].to_string(), pos });
+                    Ok(())
+                }
+                ".power" => Self::parse_power(line, pos).map(|mut xs| power.append(&mut xs)),
+                ".group" => {
+                    Self::parse_one_group(line).and_then(|(name, group)| groups.insert_signals(name, group, pos))
+                }
+                ".thresholds" => Self::parse_thresholds(line, pos).map(|t| thresholds = Some(t)),
+                ".mode" => Self::parse_mode(line, pos).map(|m| mode = Some(m)),
+                ".cycle" => Self::parse_cycle_line(line, pos).map(|c| cycle_line = Some(c)),
+                ".plotdef" => Self::parse_plot_def(line, pos).map(|d| plot_defs.push(d)),
+                ".plot" => Self::parse_plot_directive(line, pos).map(|p| plot_dirs.push(p)),
+                _ if line.starts_with('.') => {
+                    Self::parse_directive(line).map(|directive| directives.push(DirectiveLine { directive, pos }))
+                }
                 // this line is either junk or a test vector
-                let tv = Self::parse_test_line(line)?;
-                test_lines.push(tv)
+                _ => match Self::parse_test_line(line, pos) {
+                    Ok(tv) => {
+                        test_lines.push(tv);
+                        Ok(())
+                    }
+                    berr => return bail!(berr, format!("Bad test line {}", pos.line)),
+                },
+            };
+            match parsed {
+                Ok(()) => {}
+                berr => return bail!(berr, format!("Line {}, column {}", pos.line, pos.col)),
             }
         }
 
-        Ok(ModTest { power, thresholds, groups, mode, cycle_line, test_lines, plot_defs, plot_dirs })
+        Ok(ModTest { power,
+                     thresholds,
+                     groups,
+                     mode,
+                     cycle_line,
+                     test_lines,
+                     plot_defs,
+                     plot_dirs,
+                     comments,
+                     directives })
     }
 
     pub fn from_file(p: &Path) -> E<ModTest> {
@@ -67,7 +143,8 @@ impl ModTest {
         }
     }
 
-    fn parse_power(s: &str) -> E<Vec<Power>> {
+    // each supply's column is where its name starts on the line at pos.
+    fn parse_power(s: &str, pos: Pos) -> E<Vec<Power>> {
         if !s.starts_with(".power") {
             return bail!("not power line: todo improve this message");
         }
@@ -83,7 +160,9 @@ impl ModTest {
             match (cap.get(1), cap.get(2)) {
                 (Some(name), Some(volts)) => {
                     powers.push(Power { name: name.as_str().to_string(),
-                                        volts: volts.as_str().parse::<f64>().unwrap() });
+                                        volts: volts.as_str().parse::<f64>().unwrap(),
+                                        pos: Pos { line: pos.line,
+                                                   col: pos.col + s[..name.start()].chars().count() } });
                 }
                 // TODO this should return a syntax error.
                 _ => {}
@@ -92,7 +171,7 @@ impl ModTest {
         Ok(powers)
     }
 
-    fn parse_thresholds(s: &str) -> E<Thresholds> {
+    fn parse_thresholds(s: &str, pos: Pos) -> E<Thresholds> {
         if !s.starts_with(".thresholds") {
             return bail!("not threshhold line: todo improve this message");
         }
@@ -115,7 +194,7 @@ impl ModTest {
         let vih = f("Vih")?;
         let vil = f("Vil")?;
 
-        Ok(Thresholds { voh, vol, vih, vil })
+        Ok(Thresholds { voh, vol, vih, vil, pos })
     }
 
     fn parse_one_group(line: &str) -> E<(String, Vec<Sig>)> {
@@ -147,15 +226,15 @@ impl ModTest {
         Ok((group_name.to_string(), sigs))
     }
 
-    fn parse_mode(line: &str) -> E<Mode> {
+    fn parse_mode(line: &str, pos: Pos) -> E<ModeLine> {
         if !line.starts_with(".mode") {
             bail!("not a mode directive")?;
         }
         let line: &str = &line[".mode".len()..].trim();
 
         match line {
-            "gate" => Ok(Mode::Gate),
-            "device" => Ok(Mode::Device),
+            "gate" => Ok(ModeLine { mode: Mode::Gate, pos }),
+            "device" => Ok(ModeLine { mode: Mode::Device, pos }),
             _ => bailfmt!("Unknown mode: {}", line),
         }
     }
@@ -198,78 +277,112 @@ impl ModTest {
                 match (cap.get(1), cap.get(3)) {
                     (Some(num), Some(unit)) => {
                         let n = num.as_str().parse::<f64>().unwrap();
-                        const MSG: &'static str = "Unknown duration unit in .cycle: {:?}";
-                        let action = Action::Tran(match unit.as_str() {
-                                                      "u" | "U" => Duration::MicroSecond(n),
-                                                      "n" | "N" => Duration::NanoSecond(n),
-                                                      "p" | "P" => Duration::PicoSecond(n),
-                                                      "f" | "F" => Duration::FemptoSecond(n),
-                                                      "a" | "A" => Duration::AttoSecond(n),
-                                                      x => {
-                                                          return (bailfmt!("{} {}", MSG, x), "");
-                                                      }
-                                                  });
-                        return (Ok(action), &line[span.end()..]);
+                        return match Duration::from_unit(n, unit.as_str()) {
+                            Ok(d) => (Ok(Action::Tran(d)), &line[span.end()..]),
+                            berr => (bail!(berr, "Bad tran in .cycle"), ""),
+                        };
                     }
                     _ => return (bailfmt!("Malformed tran in .cycle {:?}", span), ""),
                 }
             }
         }
 
-        // OK, this is involved. Need to split on =, take the left
-        // side, trim and see if it parses as a sig.
-
-        let parts: Vec<&str> = line.split("=").collect();
-        if parts.len() > 1 {
-            match sig::parse_sig(parts[0].trim()) {
-                Some(sig) => {
-                    // grab the right hand side and parse an identifier
-                    let p = format!(r#"[\s]*({})"#, NUMBER);
-                    if let Some(cap) = regex::Regex::new(&p).unwrap().captures(parts[1]) {
-                        let span = cap.get(0).unwrap();
-                        if span.start() == 0 {
-                            // calculate num chars consumed.
-                            // the + 1 in the middle counts the equal sign
-                            let n = parts[0].len() + 1 + span.end();
-
-                            let number = cap.get(1).unwrap().as_str().parse::<f64>().unwrap();
-                            return (Ok(Action::SetSignal(sig, number)), &line[n..]);
-                        }
-                    }
+        // sig=value, with or without spaces around the =. sig can also
+        // name a group, which is sorted out when the cycle is expanded.
+        let set_pattern = format!(r#"^([^\s=]+){}={}(?:{})"#, SPACE, SPACE, VALUE);
+        if let Some(cap) = regex::Regex::new(&set_pattern).unwrap().captures(line) {
+            let rest = &line[cap.get(0).unwrap().end()..];
+            let sig = match sig::parse_sig(&cap[1]) {
+                Some(sig) => sig,
+                None => return (bailfmt!("bad signal found: {:?}", &cap[1]), ""),
+            };
+            let action = match (cap.name("bus"), cap.name("num"), cap.name("si")) {
+                (Some(bus), _, _) => bus_number(bus.as_str()).map(|n| Action::SetBus(sig, n)),
+                (None, Some(num), si) => {
+                    si_number(num.as_str(), si.map_or("", |s| s.as_str())).map(|v| Action::SetSignal(sig, v))
+                }
+                _ => bailfmt!("Bad value in .cycle: {:?}", line),
+            };
+            return (action, rest);
+        }
+        (bailfmt!("unknown action in .cycle: {:?}", line), "")
+    }
+
+    // a run of .cycle style actions, "assert inputs tran 10n CLK=1"
+    fn parse_actions(mut line: &str) -> E<Vec<Action>> {
+        let mut actions: Vec<Action> = vec![];
+        line = line.trim();
+        while !line.is_empty() {
+            match Self::consume_action(line) {
+                (Ok(action), rest) => {
+                    actions.push(action);
+                    line = rest.trim();
                 }
-                None => return (bailfmt!("bad signal found: {:?}", line), ""),
+                (berr, _) => return bail!(berr, format!("Bad action: {}", line)),
             }
-        } else {
-            return (bailfmt!("unknown action in .cycle: {:?}", line), "");
         }
-        return (bailfmt!("What's going on here?: {:?}", line), "");
+        Ok(actions)
     }
 
-    fn parse_cycle_line(line: &str) -> E<CycleLine> {
+    fn parse_cycle_line(line: &str, pos: Pos) -> E<CycleLine> {
         // .cycle assert inputs tran 99n sample outputs tran 1n
         if !line.starts_with(".cycle") {
             bail!("not a cycle directive")
         } else {
-            let mut line = &line[6..];
-            let mut actions: Vec<Action> = vec![];
-            while !line.is_empty() {
-                match Self::consume_action(line) {
-                    (Ok(action), "") => {
-                        actions.push(action);
-                        break;
-                    }
-                    (Ok(action), rest) => {
-                        actions.push(action);
-                        line = rest.trim();
+            let actions = bailif!(Self::parse_actions(&line[6..]), "Couldn't parse .cycle directive")?;
+            Ok(CycleLine(actions, pos))
+        }
+    }
+
+    // .options, .repeat, .pause, .initial, .reset and .verilog
+    fn parse_directive(line: &str) -> E<Directive> {
+        let name = directive_name(line);
+        let rest = line[name.len()..].trim();
+        match name {
+            ".options" => {
+                // name=value pairs, values are kept as written.
+                let pat = regex::Regex::new(&format!(r#"({}){}={}([^\s=]+)"#, IDENT, SPACE, SPACE)).unwrap();
+                if !pat.replace_all(rest, "").trim().is_empty() {
+                    return bailfmt!("Expected name=value pairs in .options, got: {}", rest);
+                }
+                let options = pat.captures_iter(rest).map(|c| (c[1].to_string(), c[2].to_string())).collect();
+                Ok(Directive::Options(options))
+            }
+            ".repeat" => match rest.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Directive::Repeat(n)),
+                _ => bailfmt!(".repeat needs a positive count, got: {:?}", rest),
+            },
+            ".pause" => {
+                let pat = regex::Regex::new(&format!("^{}$", DURATION)).unwrap();
+                match pat.captures(rest) {
+                    Some(cap) => Ok(Directive::Pause(Duration::from_unit(cap[1].parse::<f64>().unwrap(), &cap[3])?)),
+                    None => bailfmt!(".pause needs a duration like 10n, got: {:?}", rest),
+                }
+            }
+            ".initial" => {
+                let mut values = vec![];
+                for action in Self::parse_actions(rest)? {
+                    match action {
+                        Action::SetSignal(sig, v) => values.push((sig, v)),
+                        a => return bailfmt!(".initial only sets signals, got: {:?}", a),
                     }
-                    (berr, _) => return bail!(berr, "Couldn't parse .cycle directive"),
+                }
+                Ok(Directive::Initial(values))
+            }
+            ".reset" => {
+                let actions = Self::parse_actions(rest)?;
+                let waits_and_sets = |a: &&Action| matches!(a, Action::SetSignal(..) | Action::SetBus(..) | Action::Tran(_));
+                match actions.iter().find(|a| !waits_and_sets(a)) {
+                    Some(a) => bailfmt!(".reset only sets signals and waits, got: {:?}", a),
+                    None => Ok(Directive::Reset(actions)),
                 }
             }
-            Ok(CycleLine(actions))
+            ".verilog" => Ok(Directive::Verilog(rest.to_string())),
+            _ => bailfmt!("Unknown directive: {}", name),
         }
     }
 
-    fn parse_test_line(s: &str) -> E<TestLine> {
+    fn parse_test_line(s: &str, pos: Pos) -> E<TestLine> {
         let parse_chars = |cs: &str| -> E<Vec<BinVal>> {
             let mut binvals = vec![];
             for c in cs.chars() {
@@ -297,15 +410,15 @@ impl ModTest {
             let data = &s[0..idx];
             let comment = &s[idx + 2..];
             let bin_vals = parse_chars(data.trim())?;
-            return Ok(TestLine { bin_vals, comment: Some(comment.to_string()) });
+            return Ok(TestLine { bin_vals, comment: Some(comment.to_string()), pos });
         } else {
             // no comment found.
             let bin_vals = parse_chars(s)?;
-            return Ok(TestLine { bin_vals, comment: None });
+            return Ok(TestLine { bin_vals, comment: None, pos });
         }
     }
 
-    fn parse_plot_def(line: &str) -> E<PlotDef> {
+    fn parse_plot_def(line: &str, pos: Pos) -> E<PlotDef> {
         // .plotdef reg R0 R1 ... R31
 
         if !line.starts_with(".plotdef ") {
@@ -328,11 +441,11 @@ impl ModTest {
                     tags.push(tag.to_string());
                 }
             }
-            Ok(PlotDef { name, tags })
+            Ok(PlotDef { name, tags, pos })
         }
     }
 
-    fn parse_plot_directive(line: &str) -> E<PlotDirective> {
+    fn parse_plot_directive(line: &str, pos: Pos) -> E<PlotLine> {
         if !line.starts_with(".plot ") {
             bail!("Not a plot directive")
         } else {
@@ -353,16 +466,17 @@ impl ModTest {
                     let sig = bailif!(sig, "Bad signal name in .plot directive")?;
 
                     let ident = ident.as_str();
-                    Ok(match ident {
+                    let plot = match ident {
                         "B" => PlotDirective::BinStyle(sig),
                         "X" => PlotDirective::HexStyle(sig),
                         "D" => PlotDirective::DecStyle(sig),
                         _ => PlotDirective::PlotDefStyle(ident.to_string(), sig),
-                    })
+                    };
+                    Ok(PlotLine { plot, pos })
                 }
                 (Some(sig_string), None) => {
                     let sig = Sig::from_str(sig_string.as_str())?;
-                    Ok(PlotDirective::SimplePlot(sig))
+                    Ok(PlotLine { plot: PlotDirective::SimplePlot(sig), pos })
                 }
                 (x, y) => bailfmt!("unhandled case in parse_plot_directive ({:?}. {:?}", x, y),
             }
@@ -380,8 +494,8 @@ mod tests {
     #[test]
     fn parse_cycle_line_1() {
         let line = ".cycle assert inputs";
-        match ModTest::parse_cycle_line(line) {
-            Ok(CycleLine(xs)) => assert_eq!(xs, vec!(Action::Assert("inputs".to_string()))),
+        match ModTest::parse_cycle_line(line, Pos::default()) {
+            Ok(CycleLine(xs, _)) => assert_eq!(xs, vec!(Action::Assert("inputs".to_string()))),
             Err(berr) => panic!(berr),
         }
     }
@@ -391,8 +505,8 @@ mod tests {
         //let line = ".cycle assert inputs tran 99n sample outputs tran 1n";
 
         let line = ".cycle tran 99.5n";
-        match ModTest::parse_cycle_line(line) {
-            Ok(CycleLine(xs)) => assert_eq!(xs, vec!(Action::Tran(Duration::NanoSecond(99.5)))),
+        match ModTest::parse_cycle_line(line, Pos::default()) {
+            Ok(CycleLine(xs, _)) => assert_eq!(xs, vec!(Action::Tran(Duration::NanoSecond(99.5)))),
             Err(berr) => panic!("{:?}", berr),
         }
     }
@@ -400,8 +514,8 @@ mod tests {
     #[test]
     fn parse_cycle_line_3() {
         let line = ".cycle assert inputs tran 99n sample outputs tran 1n";
-        match ModTest::parse_cycle_line(line) {
-            Ok(CycleLine(xs)) => assert_eq!(xs,
+        match ModTest::parse_cycle_line(line, Pos::default()) {
+            Ok(CycleLine(xs, _)) => assert_eq!(xs,
                                             vec!(Action::Assert("inputs".to_string()),
                                                  Action::Tran(Duration::NanoSecond(99.0)),
                                                  Action::Sample("outputs".to_string()),
@@ -413,8 +527,8 @@ mod tests {
     #[test]
     fn parse_cycle_line_4() {
         let line = ".cycle tran 1n assert A sample B assert C tran 2n";
-        match ModTest::parse_cycle_line(line) {
-            Ok(CycleLine(xs)) => assert_eq!(xs,
+        match ModTest::parse_cycle_line(line, Pos::default()) {
+            Ok(CycleLine(xs, _)) => assert_eq!(xs,
                                             vec!(Action::Tran(Duration::NanoSecond(1.0)),
                                                  Action::Assert("A".to_string()),
                                                  Action::Sample("B".to_string()),
@@ -428,8 +542,8 @@ mod tests {
     #[test]
     fn parse_cycle_line_5() {
         let line = ".cycle CLK=1";
-        match ModTest::parse_cycle_line(line) {
-            Ok(CycleLine(xs)) => {
+        match ModTest::parse_cycle_line(line, Pos::default()) {
+            Ok(CycleLine(xs, _)) => {
                 let sig = sig::parse_sig("CLK").unwrap();
                 assert_eq!(xs, vec!(Action::SetSignal(sig, 1.0)));
             }
@@ -447,63 +561,65 @@ mod tests {
 
     #[test]
     fn parse_plot_directive1() {
-        let got = ModTest::parse_plot_directive(".plot X(Y[31:0])");
+        let got = ModTest::parse_plot_directive(".plot X(Y[31:0])", Pos::default());
         let sig = sig::parse_sig("Y[31:0]").unwrap();
-        let expect = PlotDirective::HexStyle(sig);
+        let expect = PlotLine { plot: PlotDirective::HexStyle(sig), pos: Pos::default() };
         assert_eq!(got, Ok(expect));
     }
 
     #[test]
     fn parse_plot_directive2() {
-        let got = ModTest::parse_plot_directive(".plot clk");
+        let got = ModTest::parse_plot_directive(".plot clk", Pos::default());
         let sig = sig::parse_sig("clk").unwrap();
-        let expect = PlotDirective::SimplePlot(sig);
+        let expect = PlotLine { plot: PlotDirective::SimplePlot(sig), pos: Pos::default() };
         assert_eq!(got, Ok(expect));
     }
 
     #[test]
     fn parse_plot_def() {
-        let got = ModTest::parse_plot_def(".plotdef op ADD SUB MUL");
+        let got = ModTest::parse_plot_def(".plotdef op ADD SUB MUL", Pos::default());
         let expect =
-            PlotDef { name: "op".to_string(), tags: vec!["ADD".to_string(), "SUB".to_string(), "MUL".to_string()] };
+            PlotDef { name: "op".to_string(),
+                      tags: vec!["ADD".to_string(), "SUB".to_string(), "MUL".to_string()],
+                      pos: Pos::default() };
         assert_eq!(got, Ok(expect));
     }
 
     #[test]
     fn parse_testline1() {
-        let got = ModTest::parse_test_line("HHH LLL 110 --- // last three don't care");
+        let got = ModTest::parse_test_line("HHH LLL 110 --- // last three don't care", Pos::default());
         let bin_vals = vec![H, H, H, L, L, L, H, H, L, DontCare, DontCare, DontCare];
         let comment = Some(" last three don't care".to_string());
-        let expect = Ok(TestLine { bin_vals, comment });
+        let expect = Ok(TestLine { bin_vals, comment, pos: Pos::default() });
         assert_eq!(got, expect);
     }
 
     #[test]
     fn parse_testline2() {
-        let got = ModTest::parse_test_line("HHH LLL 110 ---");
+        let got = ModTest::parse_test_line("HHH LLL 110 ---", Pos::default());
         let bin_vals = vec![H, H, H, L, L, L, H, H, L, DontCare, DontCare, DontCare];
         let comment = None;
-        let expect = Ok(TestLine { bin_vals, comment });
+        let expect = Ok(TestLine { bin_vals, comment, pos: Pos::default() });
         assert_eq!(got, expect);
     }
 
     #[test]
     fn parse_mode1() {
-        let got = ModTest::parse_mode(".mode    gate");
-        let expect = Ok(Mode::Gate);
+        let got = ModTest::parse_mode(".mode    gate", Pos::default());
+        let expect = Ok(ModeLine { mode: Mode::Gate, pos: Pos::default() });
         assert_eq!(got, expect);
     }
 
     #[test]
     fn parse_mode2() {
-        let got = ModTest::parse_mode(".mode device");
-        let expect = Ok(Mode::Device);
+        let got = ModTest::parse_mode(".mode device", Pos::default());
+        let expect = Ok(ModeLine { mode: Mode::Device, pos: Pos::default() });
         assert_eq!(got, expect);
     }
 
     #[test]
     fn parse_mode3() {
-        match ModTest::parse_mode(".mode dvice") {
+        match ModTest::parse_mode(".mode dvice", Pos::default()) {
             Err(b) => {
                 let msg = format!("Unknown mode: {}", "dvice");
                 assert_eq!(b.msg, msg);
@@ -524,15 +640,15 @@ mod tests {
 
     #[test]
     fn parse_threshold1() {
-        let got = ModTest::parse_thresholds(".thresholds Vol=0 Vil=0.1 Vih=0.9 Voh=1");
-        let expect = Ok(Thresholds { vol: 0.0, vil: 0.1, vih: 0.9, voh: 1.0 });
+        let got = ModTest::parse_thresholds(".thresholds Vol=0 Vil=0.1 Vih=0.9 Voh=1", Pos::default());
+        let expect = Ok(Thresholds { vol: 0.0, vil: 0.1, vih: 0.9, voh: 1.0, pos: Pos::default() });
         assert_eq!(got, expect);
     }
 
     #[test]
     fn parse_threshold2() {
         // missing Voh
-        match ModTest::parse_thresholds(".thresholds Vol=0.0 Vil=0.1 Vih=0.9") {
+        match ModTest::parse_thresholds(".thresholds Vol=0.0 Vil=0.1 Vih=0.9", Pos::default()) {
             Err(b) => {
                 assert_eq!(b.msg, "No Voh found in threshold line");
             }
@@ -542,16 +658,91 @@ mod tests {
 
     #[test]
     fn parse_power2() {
-        let got = ModTest::parse_power(".power Vdd=1.0");
-        let expect = vec![Power { name: "Vdd".to_string(), volts: 1.0f64 }];
+        let got = ModTest::parse_power(".power Vdd=1.0", Pos { line: 1, col: 1 });
+        let expect = vec![Power { name: "Vdd".to_string(), volts: 1.0f64, pos: Pos { line: 1, col: 8 } }];
         assert_eq!(got, Ok(expect));
     }
 
     #[test]
     fn parse_power3() {
-        let got = ModTest::parse_power(".power Vdd=1.0 Foo=1.234");
-        let expect =
-            vec![Power { name: "Vdd".to_string(), volts: 1.0f64 }, Power { name: "Foo".to_string(), volts: 1.234f64 }];
+        let got = ModTest::parse_power(".power Vdd=1.0 Foo=1.234", Pos { line: 3, col: 2 });
+        let expect = vec![Power { name: "Vdd".to_string(), volts: 1.0f64, pos: Pos { line: 3, col: 9 } },
+                          Power { name: "Foo".to_string(), volts: 1.234f64, pos: Pos { line: 3, col: 17 } }];
         assert_eq!(got, Ok(expect));
     }
+
+    #[test]
+    fn positions() {
+        let src = "// adder test\n.power Vdd=1\n\n  .group inputs A B\n.cycle assert inputs\n  // vectors\n01\n 10 // x\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert_eq!(test.comments,
+                   vec![Comment { text: " adder test".to_string(), pos: Pos { line: 1, col: 1 } },
+                        Comment { text: " vectors".to_string(), pos: Pos { line: 6, col: 3 } }]);
+        assert_eq!(test.power[0].pos, Pos { line: 2, col: 8 });
+        assert_eq!(test.groups.get("inputs").unwrap().pos, Pos { line: 4, col: 3 });
+        assert_eq!(test.cycle_line.as_ref().unwrap().1, Pos { line: 5, col: 1 });
+        assert_eq!(test.test_lines[0].pos, Pos { line: 7, col: 1 });
+        assert_eq!(test.test_lines[1].pos, Pos { line: 8, col: 2 });
+
+        let src = ".thresholds Vol=0 Vil=0.1 Vih=0.9 Voh=1\n .mode gate\n.plotdef op ADD SUB\n\n  .plot X(Y)\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert_eq!(test.thresholds.unwrap().pos, Pos { line: 1, col: 1 });
+        assert_eq!(test.mode, Some(ModeLine { mode: Mode::Gate, pos: Pos { line: 2, col: 2 } }));
+        assert_eq!(test.plot_defs[0].pos, Pos { line: 3, col: 1 });
+        assert_eq!(test.plot_dirs[0].pos, Pos { line: 5, col: 3 });
+
+        match ModTest::from_str(".power Vdd=1\n.mode gates\n") {
+            Err(b) => assert!(b.msg.contains("Line 2, column 1")),
+            _ => panic!("expected a bad .mode"),
+        }
+    }
+
+    #[test]
+    fn parse_directives() {
+        let got = ModTest::parse_directive(".options tran_step=1p  vdd = 3.3");
+        let options = vec![("tran_step".to_string(), "1p".to_string()), ("vdd".to_string(), "3.3".to_string())];
+        assert_eq!(got, Ok(Directive::Options(options)));
+        assert_eq!(ModTest::parse_directive(".repeat 4"), Ok(Directive::Repeat(4)));
+        assert!(ModTest::parse_directive(".repeat 0").is_err());
+        assert_eq!(ModTest::parse_directive(".pause 2.5n"), Ok(Directive::Pause(Duration::NanoSecond(2.5))));
+        assert_eq!(ModTest::parse_directive(".initial Q=0"),
+                   Ok(Directive::Initial(vec![(sig::parse_sig("Q").unwrap(), 0.0)])));
+        assert!(ModTest::parse_directive(".initial assert inputs").is_err());
+        assert_eq!(ModTest::parse_directive(".reset RESET=1 tran 10n"),
+                   Ok(Directive::Reset(vec![Action::SetSignal(sig::parse_sig("RESET").unwrap(), 1.0),
+                                            Action::Tran(Duration::NanoSecond(10.0))])));
+        assert_eq!(ModTest::parse_directive(".verilog initial $dumpvars;"),
+                   Ok(Directive::Verilog("initial $dumpvars;".to_string())));
+
+        let test = ModTest::from_str(".group inputs A\n.repeat 2\n1\n").unwrap();
+        assert_eq!(test.directives, vec![DirectiveLine { directive: Directive::Repeat(2), pos: Pos { line: 2, col: 1 } }]);
+        match ModTest::from_str(".group inputs A\n.groups outputs B\n") {
+            Err(b) => assert_eq!(b.more.unwrap().msg, "Unknown directive: .groups"),
+            _ => panic!("expected an unknown directive"),
+        }
+
+        let test = ModTest::from_str(".group inputs A B // no carry in\n.verilog // as is\n").unwrap();
+        assert_eq!(test.groups.get("inputs").unwrap().sigs.len(), 2);
+        assert_eq!(test.comments, vec![Comment { text: " no carry in".to_string(), pos: Pos { line: 1, col: 19 } }]);
+        assert_eq!(test.directives[0].directive, Directive::Verilog("// as is".to_string()));
+    }
+
+    #[test]
+    fn parse_cycle_line_6() {
+        let line = ".cycle CLK = 1.8 Vdd=3.3m tran 1ms A[3:0]=0xA B=0b10 tran 2 s";
+        match ModTest::parse_cycle_line(line, Pos::default()) {
+            Ok(CycleLine(xs, _)) => assert_eq!(xs,
+                                               vec![Action::SetSignal(sig::parse_sig("CLK").unwrap(), 1.8),
+                                                    Action::SetSignal(sig::parse_sig("Vdd").unwrap(), 3.3e-3),
+                                                    Action::Tran(Duration::MilliSecond(1.0)),
+                                                    Action::SetBus(sig::parse_sig("A[3:0]").unwrap(), 10),
+                                                    Action::SetBus(sig::parse_sig("B").unwrap(), 2),
+                                                    Action::Tran(Duration::Second(2.0))]),
+            Err(berr) => panic!("{:?}", berr),
+        }
+        assert_eq!(si_number("2", "MEG"), Ok(2e6));
+        assert_eq!(si_number("1.5", "kV"), Ok(1.5e3));
+        assert!(ModTest::parse_cycle_line(".cycle tran 5q", Pos::default()).is_err());
+        assert!(ModTest::parse_cycle_line(".cycle CLK=1z", Pos::default()).is_err());
+    }
 }
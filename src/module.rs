@@ -0,0 +1,214 @@
+use serde_json::Value;
+use std::path::Path;
+
+use crate::types::*;
+
+// A jade file is a two element array, the string "Jade" followed by an
+// object mapping module names to their aspects:
+// ["Jade", {"/user/AND2": {"properties": {..}, "schematic": [..], "icon": [..], "test": [..]}}]
+
+impl Module {
+    pub fn from_value(name: &str, val: &Value) -> E<Module> {
+        let obj = if let Value::Object(obj) = val {
+            obj
+        } else {
+            return bailfmt!("Module::from_value expected an object, got: {:?}", val);
+        };
+
+        let schematic = match obj.get("schematic") {
+            Some(scm) => bailif!(Schematic::from_value(scm), format!("Module {} has a bad schematic", name))?,
+            None => Schematic { parts: vec![] },
+        };
+
+        let icon = match obj.get("icon") {
+            Some(icon) => Some(bailif!(Icon::from_value(icon), format!("Module {} has a bad icon", name))?),
+            None => None,
+        };
+
+        let tests = match obj.get("test") {
+            Some(tests) => {
+                let tests = Self::tests_from_value(tests);
+                bailif!(tests, format!("Module {} has a bad test", name))?
+            }
+            None => vec![],
+        };
+
+        Ok(Module { name: name.to_string(), properties: 0, schematic, icon, tests })
+    }
+
+    // [["test", ".power Vdd=1\n..."], ..]
+    fn tests_from_value(val: &Value) -> E<Vec<NamedTest>> {
+        let entries = match val {
+            Value::Array(entries) => entries,
+            _ => return bailfmt!("Expected an array of tests, got: {:?}", val),
+        };
+        let mut tests = vec![];
+        for entry in entries {
+            match entry.as_array().map(|e| e.as_slice()) {
+                Some([Value::String(name), Value::String(src)]) => {
+                    tests.push(NamedTest { name: name.to_string(), test: ModTest::from_str(src) });
+                }
+                _ => return bailfmt!("Expected [name, test], got: {:?}", entry),
+            }
+        }
+        Ok(tests)
+    }
+
+    // the first of the module's tests with this name.
+    pub fn test(&self, name: &str) -> E<&ModTest> {
+        match self.tests.iter().find(|t| t.name == name).map(|t| &t.test) {
+            Some(Ok(test)) => Ok(test),
+            Some(Err(b)) => {
+                let berr: E<&ModTest> = Err(b.clone());
+                bail!(berr, format!("Module {} has a bad test {}", self.name, name))
+            }
+            None => bailfmt!("Module {} has no test named {}", self.name, name),
+        }
+    }
+
+    // the ports declared in the schematic, in the order they were drawn.
+    pub fn ports(&self) -> Vec<&Port> {
+        let mut ports = vec![];
+        for part in &self.schematic.parts {
+            if let Part::Port(port) = part {
+                ports.push(port);
+            }
+        }
+        ports
+    }
+
+    // the terminals of this module's icon, which is how it connects when
+    // used as a submodule.
+    pub fn terminals(&self) -> Vec<&Terminal> {
+        let mut terms = vec![];
+        if let Some(icon) = &self.icon {
+            for part in &icon.parts {
+                if let IconPart::Terminal(term) = part {
+                    terms.push(term);
+                }
+            }
+        }
+        terms
+    }
+
+    pub fn submodules(&self) -> Vec<&SubModule> {
+        let mut subs = vec![];
+        for part in &self.schematic.parts {
+            if let Part::SubModule(sub) = part {
+                subs.push(sub);
+            }
+        }
+        subs
+    }
+}
+
+impl Project {
+    pub fn from_value(val: &Value) -> E<Project> {
+        let modules_val = match val {
+            Value::Array(arr) if arr.len() == 2 && arr[0] == Value::String("Jade".to_string()) => &arr[1],
+            _ => return bailfmt!("Project::from_value expected [\"Jade\", {{...}}], got: {:?}", val),
+        };
+
+        let mut modules = vec![];
+        if let Value::Object(obj) = modules_val {
+            for (name, module_val) in obj {
+                modules.push(Module::from_value(name, module_val)?);
+            }
+        } else {
+            return bailfmt!("Project::from_value expected an object of modules, got: {:?}", modules_val);
+        }
+        Ok(Project { modules, filename: None })
+    }
+
+    pub fn from_str(s: &str) -> E<Project> {
+        let val: Value = serde_json::from_str(s)?;
+        Self::from_value(&val)
+    }
+
+    pub fn from_file(p: &Path) -> E<Project> {
+        match std::fs::read_to_string(p) {
+            Ok(s) => {
+                let mut project = Self::from_str(&s)?;
+                project.filename = Some(p.to_path_buf());
+                Ok(project)
+            }
+            Err(msg) => bailfmt!("Can't open project: {}", msg),
+        }
+    }
+
+    pub fn module(&self, name: &str) -> Option<&Module> {
+        self.modules.iter().find(|m| m.name == name)
+    }
+
+    // a test saved with a module, for the runner or a testbench exporter.
+    pub fn test(&self, module: &str, name: &str) -> E<&ModTest> {
+        match self.module(module) {
+            Some(m) => m.test(name),
+            None => bailfmt!("No module named {}", module),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::garr_inc_4_json;
+
+    #[test]
+    fn module1() {
+        let project = Project::from_value(&garr_inc_4_json()).unwrap();
+        let module = project.module("/user/GarrInc4").unwrap();
+        assert_eq!(module.schematic.parts.len(), 52);
+        assert_eq!(module.terminals().len(), 4);
+        assert_eq!(module.submodules().len(), 9);
+        // "vout [3:0]" in its .group line.
+        assert!(module.test("test").is_err());
+    }
+
+    #[test]
+    fn project1() {
+        let project = Project::from_file(Path::new("./test-data/use-and2.json")).unwrap();
+        assert_eq!(project.modules.len(), 2);
+        assert!(project.module("/user/AND2").is_some());
+        assert!(project.module("/user/UseAND2").is_some());
+    }
+
+    #[test]
+    fn project2() {
+        // memory, vdd, arcs and properties all have to parse.
+        for f in &["./test-data/MemUnit4x2.json", "./test-data/Vdd.json", "./test-data/Mux4Rep1.json"] {
+            if let Err(e) = Project::from_file(Path::new(f)) {
+                panic!("{:?}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn project3() {
+        let got = Project::from_str(r#"["Jade"]"#);
+        assert!(got.is_err());
+    }
+
+    #[test]
+    fn tests() {
+        let project = Project::from_file(Path::new("./test-data/use-and2.json")).unwrap();
+        let and2 = project.module("/user/AND2").unwrap();
+        assert_eq!(and2.tests.len(), 1);
+        assert_eq!(and2.test("test").unwrap().test_lines.len(), 4);
+        assert!(project.test("/user/AND2", "slow").is_err());
+        assert!(project.test("/user/Nope", "test").is_err());
+
+        let src = r#"["Jade", {"/user/M": {"test": [["test", ".group inputs A\n0\n"], ["slow", "1\n1\n"]]}}]"#;
+        let project = Project::from_str(src).unwrap();
+        let names: Vec<&str> = project.modules[0].tests.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["test", "slow"]);
+        assert_eq!(project.test("/user/M", "slow").unwrap().test_lines.len(), 2);
+
+        let src = r#"["Jade", {"/user/M": {"test": [["test", ".group inputs A B\n.nope\n"]]}}]"#;
+        assert!(Project::from_str(src).unwrap().test("/user/M", "test").is_err());
+        assert!(Project::from_str(r#"["Jade", {"/user/M": {"test": [["test"]]}}]"#).is_err());
+    }
+}
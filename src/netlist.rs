@@ -0,0 +1,1091 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::gates::*;
+use crate::types::*;
+use crate::union_find::UnionFind;
+
+// -----------------------------------------------------------------------------
+// The net builder turns a schematic's geometry into nets. Wire end points,
+// ports, jumpers and submodule terminals that land on the same coordinate
+// form a node, a node's signal labels name its bits, and bits with the same
+// name are the same net. Global names (vdd, gnd, ...) are the same net at
+// every level of the hierarchy, and quoted constants like 0'1 tie to the
+// tie-low/tie-high nets instead of becoming ordinary names.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetConfig {
+    pub globals: Vec<String>,
+    pub tie_high: String,
+    pub tie_low: String,
+}
+
+impl Default for NetConfig {
+    fn default() -> NetConfig {
+        NetConfig { globals: vec!["vdd".to_string(), "gnd".to_string()],
+                    tie_high: "vdd".to_string(),
+                    tie_low: "gnd".to_string() }
+    }
+}
+
+impl NetConfig {
+    pub fn new() -> NetConfig {
+        NetConfig::default()
+    }
+
+    pub fn with_global(mut self, name: &str) -> NetConfig {
+        if !self.is_global(name) {
+            self.globals.push(name.to_string());
+        }
+        self
+    }
+
+    // the configured spelling of a global, jade ignores case in names.
+    fn global(&self, name: &str) -> Option<&str> {
+        let mut globals = vec![&self.tie_high, &self.tie_low];
+        globals.extend(self.globals.iter());
+        globals.into_iter().map(|g| g.as_str()).find(|g| g.eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_global(&self, name: &str) -> bool {
+        self.global(name).is_some()
+    }
+
+    fn kind(&self, name: &str) -> NetKind {
+        if name.eq_ignore_ascii_case(&self.tie_high) {
+            NetKind::TieHigh
+        } else if name.eq_ignore_ascii_case(&self.tie_low) {
+            NetKind::TieLow
+        } else if self.is_global(name) {
+            NetKind::Global
+        } else {
+            NetKind::Local
+        }
+    }
+}
+
+// the key a bit name is matched by, jade ignores case in names so cout
+// and COUT are the same wire.
+pub fn name_key(name: &str) -> String {
+    name.to_ascii_lowercase()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NetKind {
+    Local,
+    Global,
+    TieHigh,
+    TieLow,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Net {
+    pub name: String,
+    pub kind: NetKind,
+    // no label names it, it goes by a generated _N name.
+    pub anon: bool,
+}
+
+// one copy of a submodule, replicated submodules get one instance per copy.
+// connections map each terminal bit to a net.
+#[derive(Debug, PartialEq)]
+pub struct Instance {
+    pub name: String,
+    pub module: String,
+    pub connections: Vec<(String, String)>,
+    pub memory: Option<Memory>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ModulePort {
+    pub sig: Sig,
+    pub direction: Option<Direction>,
+    pub nets: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ModuleNetlist {
+    pub name: String,
+    pub nets: Vec<Net>,
+    pub ports: Vec<ModulePort>,
+    pub instances: Vec<Instance>,
+    // every bit name used in the schematic, lower cased, and the net it
+    // ended up on.
+    pub aliases: HashMap<String, String>,
+}
+
+// a submodule terminal, placed in schematic coordinates.
+#[derive(Debug)]
+struct PlacedTerminal {
+    sig: Sig,
+    point: (i32, i32),
+}
+
+#[derive(Debug)]
+struct PlacedInstance {
+    module: String,
+    name: Option<String>,
+    terminals: Vec<PlacedTerminal>,
+    memory: Option<Memory>,
+}
+
+// everything that landed on one node.
+#[derive(Default)]
+struct Node {
+    labels: Vec<Sig>,
+    width: Option<u64>,
+    tie: Option<bool>,
+    terminal_widths: Vec<usize>,
+}
+
+// bit names, merged by union find. Each name remembers the order it was
+// first seen and how strongly it should name the net it belongs to. Jade
+// ignores case in names, so cout and COUT are one name, spelled the way
+// the highest rank use, then the first, spells it.
+struct BitNames {
+    ids: HashMap<String, usize>,
+    names: Vec<String>,
+    rank: Vec<u8>,
+    uf: UnionFind,
+}
+
+const RANK_ANON: u8 = 0;
+const RANK_LABEL: u8 = 1;
+const RANK_PORT: u8 = 2;
+const RANK_GLOBAL: u8 = 3;
+
+impl BitNames {
+    fn new() -> BitNames {
+        BitNames { ids: HashMap::new(), names: vec![], rank: vec![], uf: UnionFind::new(0) }
+    }
+
+    fn intern(&mut self, name: &str, rank: u8) -> usize {
+        if let Some(id) = self.id(name) {
+            if rank > self.rank[id] {
+                self.rank[id] = rank;
+                self.names[id] = name.to_string();
+            }
+            return id;
+        }
+        let id = self.uf.add();
+        self.ids.insert(name_key(name), id);
+        self.names.push(name.to_string());
+        self.rank.push(rank);
+        id
+    }
+
+    fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(&name_key(name)).cloned()
+    }
+
+    // the name a net is known by, highest rank wins, then first seen.
+    fn canonical(&mut self) -> Vec<usize> {
+        let n = self.names.len();
+        let mut best: HashMap<usize, usize> = HashMap::new();
+        for id in 0..n {
+            let root = self.uf.find(id);
+            match best.get(&root) {
+                Some(&b) if self.rank[b] >= self.rank[id] => {}
+                _ => {
+                    best.insert(root, id);
+                }
+            }
+        }
+        (0..n).map(|id| best[&self.uf.find(id)]).collect()
+    }
+}
+
+fn point_id(points: &mut HashMap<(i32, i32), usize>, uf: &mut UnionFind, p: (i32, i32)) -> usize {
+    if let Some(&id) = points.get(&p) {
+        return id;
+    }
+    let id = uf.add();
+    points.insert(p, id);
+    id
+}
+
+fn place(coord3: &Coord3, x: i32, y: i32) -> (i32, i32) {
+    let (dx, dy) = coord3.r.transform(x, y);
+    (coord3.x + dx, coord3.y + dy)
+}
+
+fn instance_base_name(module: &str) -> &str {
+    module.rsplit('/').next().unwrap_or(module)
+}
+
+fn placed_instance(project: &Project, sub: &SubModule) -> E<PlacedInstance> {
+    let terminals: Vec<(Sig, i32, i32)> = if is_builtin(&sub.name) {
+        match gate_terminals(&sub.name) {
+            Some(terms) => terms.into_iter().map(|t| (t.sig, t.x, t.y)).collect(),
+            None => return bailfmt!("Unknown builtin gate: {}", sub.name),
+        }
+    } else {
+        match project.module(&sub.name) {
+            Some(module) if module.icon.is_some() => {
+                module.terminals().iter().map(|t| (t.sig.clone(), t.coord3.x, t.coord3.y)).collect()
+            }
+            Some(_) => return bailfmt!("Module {} has no icon, so it can't be used as a submodule", sub.name),
+            None => return bailfmt!("Can't find module: {}", sub.name),
+        }
+    };
+
+    let terminals =
+        terminals.into_iter().map(|(sig, x, y)| PlacedTerminal { sig, point: place(&sub.coord3, x, y) }).collect();
+    Ok(PlacedInstance { module: sub.name.clone(), name: sub.instance_name.clone(), terminals, memory: None })
+}
+
+// the direction of one terminal bit of an instance, as far as it's known
+// without building the instance's own netlist.
+fn terminal_direction(project: &Project, inst: &Instance, bit: &str) -> Option<Direction> {
+    let has_bit = |sig: &Sig| sig.bits().iter().any(|b| matches!(b, Bit::Name(n) if n.eq_ignore_ascii_case(bit)));
+    if let Some(mem) = &inst.memory {
+        return memory_terminals(mem).into_iter().find(|t| has_bit(&t.sig)).map(|t| t.direction);
+    }
+    if let Some(terms) = gate_terminals(&inst.module) {
+        return terms.into_iter().find(|t| has_bit(&t.sig)).map(|t| t.direction);
+    }
+    let module = project.module(&inst.module)?;
+    module.ports()
+          .into_iter()
+          .filter_map(|p| p.signal.as_ref())
+          .find(|s| s.sig.as_ref().map_or(false, |sig| has_bit(sig)))
+          .and_then(|s| s.direction)
+}
+
+impl ModuleNetlist {
+    pub fn build(project: &Project, module_name: &str, config: &NetConfig) -> E<ModuleNetlist> {
+        let module = match project.module(module_name) {
+            Some(m) => m,
+            None => return bailfmt!("Can't find module: {}", module_name),
+        };
+
+        // -- connection points -------------------------------------------------
+        let mut points: HashMap<(i32, i32), usize> = HashMap::new();
+        let mut puf = UnionFind::new(0);
+        let mut wire_labels: Vec<(usize, Sig)> = vec![];
+        let mut port_labels: Vec<(usize, Sig)> = vec![];
+        let mut widths: Vec<(usize, u64)> = vec![];
+        let mut ties: Vec<(usize, bool)> = vec![];
+        let mut placed: Vec<PlacedInstance> = vec![];
+        let mut ports: Vec<(&Port, usize)> = vec![];
+
+        for part in &module.schematic.parts {
+            match part {
+                Part::Wire(wire) => {
+                    let c = &wire.coord5;
+                    let (dx, dy) = c.r.transform(c.dx, c.dy);
+                    let p0 = point_id(&mut points, &mut puf, (c.x, c.y));
+                    let p1 = point_id(&mut points, &mut puf, (c.x + dx, c.y + dy));
+                    puf.union(p0, p1);
+                    if let Some(signal) = &wire.signal {
+                        if let Some(sig) = &signal.sig {
+                            wire_labels.push((p0, sig.clone()));
+                        }
+                        if let Some(width) = signal.width {
+                            widths.push((p0, width));
+                        }
+                    }
+                }
+                Part::Port(port) => {
+                    let p = point_id(&mut points, &mut puf, (port.coord3.x, port.coord3.y));
+                    if let Some(Signal { sig: Some(sig), .. }) = &port.signal {
+                        port_labels.push((p, sig.clone()));
+                    }
+                    ports.push((port, p));
+                }
+                Part::Jumper(jumper) => {
+                    let p0 = point_id(&mut points, &mut puf, (jumper.coord3.x, jumper.coord3.y));
+                    let p1 = point_id(&mut points, &mut puf, place(&jumper.coord3, 8, 0));
+                    puf.union(p0, p1);
+                }
+                Part::Vdd(vdd) => {
+                    let p = point_id(&mut points, &mut puf, (vdd.coord3.x, vdd.coord3.y));
+                    ties.push((p, true));
+                }
+                Part::Ground(gnd) => {
+                    let p = point_id(&mut points, &mut puf, (gnd.coord3.x, gnd.coord3.y));
+                    ties.push((p, false));
+                }
+                Part::Memory(mem) => {
+                    let terminals = memory_terminals(mem)
+                        .into_iter()
+                        .map(|t| PlacedTerminal { sig: t.sig, point: place(&mem.coord3, t.x, t.y) })
+                        .collect();
+                    placed.push(PlacedInstance { module: MEMORY.to_string(),
+                                                 name: mem.name.clone(),
+                                                 terminals,
+                                                 memory: Some(mem.clone()) });
+                }
+                Part::SubModule(sub) => {
+                    placed.push(bailif!(placed_instance(project, sub),
+                                        format!("Module {} has a bad submodule", module_name))?);
+                }
+                Part::Terminal(_) | Part::Text(_) => {}
+            }
+        }
+
+        for inst in &placed {
+            for term in &inst.terminals {
+                point_id(&mut points, &mut puf, term.point);
+            }
+        }
+
+        // -- nodes -------------------------------------------------------------
+        let mut nodes: HashMap<usize, Node> = HashMap::new();
+        for (p, sig) in wire_labels.iter().chain(port_labels.iter()) {
+            nodes.entry(puf.find(*p)).or_default().labels.push(sig.clone());
+        }
+        for (p, width) in &widths {
+            nodes.entry(puf.find(*p)).or_default().width = Some(*width);
+        }
+        for (p, tie) in &ties {
+            let node = nodes.entry(puf.find(*p)).or_default();
+            if node.tie == Some(!tie) {
+                return bailfmt!("vdd and ground are connected at {:?} in {}", p, module_name);
+            }
+            node.tie = Some(*tie);
+        }
+        for inst in &placed {
+            for term in &inst.terminals {
+                let root = puf.find(points[&term.point]);
+                nodes.entry(root).or_default().terminal_widths.push(term.sig.width());
+            }
+        }
+
+        // -- bits --------------------------------------------------------------
+        let mut names = BitNames::new();
+        let port_names: Vec<String> = port_labels
+            .iter()
+            .flat_map(|(_, sig)| sig.bits())
+            .filter_map(|bit| if let Bit::Name(n) = bit { Some(name_key(&n)) } else { None })
+            .collect();
+
+        let intern_bit = |names: &mut BitNames, bit: &Bit| -> usize {
+            match bit {
+                Bit::Const(true) => names.intern(&config.tie_high, RANK_GLOBAL),
+                Bit::Const(false) => names.intern(&config.tie_low, RANK_GLOBAL),
+                Bit::Name(n) => match config.global(n) {
+                    Some(g) => names.intern(g, RANK_GLOBAL),
+                    None if port_names.contains(&name_key(n)) => names.intern(n, RANK_PORT),
+                    None => names.intern(n, RANK_LABEL),
+                },
+            }
+        };
+
+        // node bits, in point order so anonymous names are stable.
+        let mut roots: Vec<usize> = nodes.keys().cloned().collect();
+        roots.sort();
+        let mut node_bits: HashMap<usize, Vec<usize>> = HashMap::new();
+        // _N1 is a legal label, anonymous names skip the ones the schematic uses.
+        let labels: HashSet<String> = nodes.values()
+                                           .flat_map(|n| n.labels.iter())
+                                           .flat_map(|sig| sig.bits())
+                                           .filter_map(|b| if let Bit::Name(n) = b { Some(name_key(&n)) } else { None })
+                                           .collect();
+        let taken = |n: usize| {
+            let name = format!("_n{}", n);
+            labels.iter().any(|l| *l == name || l.starts_with(&format!("{}[", name)))
+        };
+        let mut anon = 0;
+        for root in roots {
+            let node = &nodes[&root];
+            let bits: Vec<usize> = if let Some(first) = node.labels.first() {
+                let first_bits: Vec<usize> = first.bits().iter().map(|b| intern_bit(&mut names, b)).collect();
+                for other in &node.labels[1..] {
+                    if other.width() != first_bits.len() {
+                        let msg = format!("Signal width mismatch in {}: {:?} is connected to {:?}",
+                                          module_name, first, other);
+                        return bail!(msg);
+                    }
+                    for (i, b) in other.bits().iter().enumerate() {
+                        let id = intern_bit(&mut names, b);
+                        names.uf.union(first_bits[i], id);
+                    }
+                }
+                first_bits
+            } else {
+                let width = match (node.width, node.terminal_widths.iter().max()) {
+                    (Some(w), _) if w > 0 => w as usize,
+                    (_, Some(&w)) => w,
+                    _ => 1,
+                };
+                if let Some(tie) = node.tie {
+                    let bit = Bit::Const(tie);
+                    (0..width).map(|_| intern_bit(&mut names, &bit)).collect()
+                } else {
+                    anon += 1;
+                    while taken(anon) {
+                        anon += 1;
+                    }
+                    (0..width)
+                        .map(|i| {
+                            let name = if width == 1 { format!("_N{}", anon) } else { format!("_N{}[{}]", anon, i) };
+                            names.intern(&name, RANK_ANON)
+                        })
+                        .collect()
+                }
+            };
+
+            if let (Some(tie), true) = (node.tie, !node.labels.is_empty()) {
+                let tie_id = intern_bit(&mut names, &Bit::Const(tie));
+                for b in &bits {
+                    names.uf.union(*b, tie_id);
+                }
+            }
+            node_bits.insert(root, bits);
+        }
+
+        let high = names.intern(&config.tie_high, RANK_GLOBAL);
+        let low = names.intern(&config.tie_low, RANK_GLOBAL);
+        if names.uf.connected(high, low) {
+            return bail!(format!("{} and {} are shorted together in {}", config.tie_high, config.tie_low, module_name));
+        }
+
+        // -- nets --------------------------------------------------------------
+        let canon = names.canonical();
+        let net_of = |id: usize| names.names[canon[id]].clone();
+        let mut nets: Vec<Net> = vec![];
+        let mut aliases: HashMap<String, String> = HashMap::new();
+        for id in 0..names.names.len() {
+            let net = net_of(id);
+            if canon[id] == id {
+                nets.push(Net { kind: config.kind(&net), name: net.clone(), anon: names.rank[id] == RANK_ANON });
+            }
+            aliases.insert(name_key(&names.names[id]), net);
+        }
+        let node_nets = |p: usize, puf: &mut UnionFind| -> Vec<String> {
+            node_bits[&puf.find(p)].iter().map(|&id| net_of(id)).collect()
+        };
+
+        // -- instances ---------------------------------------------------------
+        let mut instances: Vec<Instance> = vec![];
+        for inst in placed {
+            let mut terms: Vec<(Vec<String>, Vec<String>)> = vec![];
+            let mut copies = 1;
+            for term in &inst.terminals {
+                let term_bits: Vec<String> = term.sig
+                                                 .bits()
+                                                 .into_iter()
+                                                 .filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None })
+                                                 .collect();
+                if term_bits.len() != term.sig.width() {
+                    return bailfmt!("Terminal {:?} of {} can't be a constant", term.sig, inst.module);
+                }
+                let nets = node_nets(points[&term.point], &mut puf);
+                if nets.len() % term_bits.len() != 0 {
+                    let msg = format!("{} terminal {:?} is {} bits wide, but is connected to {} bits",
+                                      inst.module,
+                                      term.sig,
+                                      term_bits.len(),
+                                      nets.len());
+                    return bail!(msg);
+                }
+                copies = copies.max(nets.len() / term_bits.len());
+                terms.push((term_bits, nets));
+            }
+
+            // a bus that is narrower than the replication is reused in turn,
+            // so it has to divide it evenly.
+            for (term_bits, nets) in &terms {
+                let n = nets.len() / term_bits.len();
+                if copies % n != 0 {
+                    let msg = format!("{} is replicated {} times, which {} bits can't be spread over",
+                                      inst.module, copies, nets.len());
+                    return bail!(msg);
+                }
+            }
+
+            // a replicated bus is split into one slice per terminal bit, copy
+            // c takes bit c of each slice, so S[1:0] on "B[31:0],A[31:0]"
+            // gives copy 0 S[1]=B[31] and S[0]=A[31]. Terminals that match
+            // the width exactly fan out to every copy.
+            for copy in 0..copies {
+                let mut connections = vec![];
+                for (term_bits, nets) in &terms {
+                    let n = nets.len() / term_bits.len();
+                    for (i, tb) in term_bits.iter().enumerate() {
+                        connections.push((tb.clone(), nets[i * n + copy % n].clone()));
+                    }
+                }
+                let name = match &inst.name {
+                    Some(name) if copies == 1 => name.clone(),
+                    Some(name) => format!("{}_{}", name, copy),
+                    None => format!("{}_{}", instance_base_name(&inst.module), instances.len()),
+                };
+                instances.push(Instance { name,
+                                          module: inst.module.clone(),
+                                          connections,
+                                          memory: inst.memory.clone() });
+            }
+        }
+
+        // -- ports -------------------------------------------------------------
+        let mut module_ports = vec![];
+        for (port, p) in ports {
+            if let Some(signal) = &port.signal {
+                if let Some(sig) = &signal.sig {
+                    module_ports.push(ModulePort { sig: sig.clone(),
+                                                   direction: signal.direction,
+                                                   nets: node_nets(p, &mut puf) });
+                }
+            }
+        }
+
+        // icon terminals are ports too, a terminal named A[3:0] connects to the
+        // schematic's A[3:0] label whether or not a port part was drawn. With
+        // no port part the direction comes from what drives the net inside.
+        let named = |sig: &Sig| -> Vec<String> {
+            sig.bits().into_iter().filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None }).collect()
+        };
+        let mut port_bits: Vec<String> =
+            module_ports.iter().flat_map(|p| named(&p.sig)).map(|b| name_key(&b)).collect();
+        for term in module.terminals() {
+            let bits = named(&term.sig);
+            if bits.iter().all(|b| port_bits.contains(&name_key(b))) {
+                continue;
+            }
+            let mut term_nets = vec![];
+            let mut direction = In;
+            for bit in &bits {
+                let net = match aliases.get(&name_key(bit)) {
+                    Some(net) => net.clone(),
+                    None => {
+                        // a terminal with nothing behind it still gets a net.
+                        nets.push(Net { kind: config.kind(bit), name: bit.clone(), anon: false });
+                        aliases.insert(name_key(bit), bit.clone());
+                        bit.clone()
+                    }
+                };
+                let kind = config.kind(&net);
+                if kind == NetKind::TieHigh || kind == NetKind::TieLow {
+                    direction = Out;
+                }
+                for inst in &instances {
+                    for (t, n) in &inst.connections {
+                        if *n == net {
+                            match terminal_direction(project, inst, t) {
+                                Some(Out) => direction = Out,
+                                Some(InOut) if direction == In => direction = InOut,
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                term_nets.push(net);
+            }
+            port_bits.extend(bits.iter().map(|b| name_key(b)));
+            module_ports.push(ModulePort { sig: term.sig.clone(), direction: Some(direction), nets: term_nets });
+        }
+
+        Ok(ModuleNetlist { name: module_name.to_string(), nets, ports: module_ports, instances, aliases })
+    }
+
+    // the net a bit name belongs to.
+    pub fn net(&self, bit: &str) -> Option<&str> {
+        self.aliases.get(&name_key(bit)).map(|s| s.as_str())
+    }
+
+    pub fn net_kind(&self, net: &str) -> Option<NetKind> {
+        self.nets.iter().find(|n| n.name == net).map(|n| n.kind)
+    }
+}
+
+// "A[3]" -> ("A", Some(3)), "A" -> ("A", None)
+pub fn split_bit(bit: &str) -> (&str, Option<i32>) {
+    // called for every bit of every signal, so the pattern is built once.
+    static PAT: OnceLock<regex::Regex> = OnceLock::new();
+    let pat = PAT.get_or_init(|| regex::Regex::new(r#"^(.+)\[(-?[0-9]+)\]$"#).unwrap());
+    match pat.captures(bit) {
+        Some(caps) => (caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str().parse::<i32>().ok()),
+        None => (bit, None),
+    }
+}
+
+// bits grouped back into buses for the HDL backends. A[3] .. A[0] become one
+// group with range (3, 0) when every bit of the base name is indexed,
+// otherwise each bit stands alone.
+#[derive(Debug, PartialEq)]
+pub struct BitGroup {
+    pub base: String,
+    pub range: Option<(i32, i32)>,
+    pub bits: Vec<String>,
+}
+
+pub fn group_bits(bits: &[String]) -> Vec<BitGroup> {
+    let mut order: Vec<&str> = vec![];
+    let mut groups: HashMap<&str, Vec<(Option<i32>, &String)>> = HashMap::new();
+    for bit in bits {
+        let (base, idx) = split_bit(bit);
+        if !groups.contains_key(base) {
+            order.push(base);
+        }
+        let group = groups.entry(base).or_default();
+        if !group.iter().any(|(_, b)| *b == bit) {
+            group.push((idx, bit));
+        }
+    }
+
+    let mut out = vec![];
+    for base in order {
+        let group = &groups[base];
+        let indices: Vec<i32> = group.iter().filter_map(|(i, _)| *i).collect();
+        let distinct = indices.iter().collect::<std::collections::HashSet<_>>().len();
+        if indices.len() == group.len() && distinct == group.len() {
+            let hi = *indices.iter().max().unwrap();
+            let lo = *indices.iter().min().unwrap();
+            out.push(BitGroup { base: base.to_string(),
+                                range: Some((hi, lo)),
+                                bits: group.iter().map(|(_, b)| b.to_string()).collect() });
+        } else {
+            for (_, bit) in group {
+                out.push(BitGroup { base: bit.to_string(), range: None, bits: vec![bit.to_string()] });
+            }
+        }
+    }
+    out
+}
+
+// the user modules reachable from top with their netlists, children before
+// their parents so HDL output reads bottom up.
+pub fn hierarchy(project: &Project, top: &str, config: &NetConfig) -> E<(Vec<String>, HashMap<String, ModuleNetlist>)> {
+    fn visit(project: &Project, name: &str, config: &NetConfig, stack: &mut Vec<String>, order: &mut Vec<String>,
+             netlists: &mut HashMap<String, ModuleNetlist>)
+             -> E<()> {
+        if netlists.contains_key(name) {
+            return Ok(());
+        }
+        if stack.iter().any(|m| m == name) {
+            return bailfmt!("Recursive module: {}", name);
+        }
+        let m = ModuleNetlist::build(project, name, config)?;
+        stack.push(name.to_string());
+        for inst in &m.instances {
+            if inst.memory.is_none() && gate_kind(&inst.module).is_none() {
+                visit(project, &inst.module, config, stack, order, netlists)?;
+            }
+        }
+        stack.pop();
+        order.push(name.to_string());
+        netlists.insert(name.to_string(), m);
+        Ok(())
+    }
+
+    let mut order = vec![];
+    let mut netlists = HashMap::new();
+    visit(project, top, config, &mut vec![], &mut order, &mut netlists)?;
+    Ok((order, netlists))
+}
+
+// -----------------------------------------------------------------------------
+// A flattened netlist holds only primitive devices, /gates/* and memories,
+// with nets named by their hierarchical path, AND2_0.in1 and so on.
+
+#[derive(Debug, PartialEq)]
+pub struct Device {
+    pub name: String,
+    pub module: String,
+    pub kind: GateKind,
+    pub connections: Vec<(String, String)>,
+    pub memory: Option<Memory>,
+}
+
+impl Device {
+    // the net on a terminal bit, like "A" or "S[1]".
+    pub fn net(&self, terminal: &str) -> Option<&str> {
+        self.connections.iter().find(|(t, _)| t == terminal).map(|(_, n)| n.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DriverConflict {
+    pub net: String,
+    pub drivers: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Netlist {
+    pub top: String,
+    pub nets: Vec<Net>,
+    pub devices: Vec<Device>,
+    pub ports: Vec<ModulePort>,
+    // the top module's bit names, lower cased, and the flat net each is on.
+    pub aliases: HashMap<String, String>,
+}
+
+struct Flattener<'a> {
+    project: &'a Project,
+    config: &'a NetConfig,
+    cache: HashMap<String, ModuleNetlist>,
+    names: BitNames,
+    kinds: HashMap<String, NetKind>,
+    devices: Vec<Device>,
+    stack: Vec<String>,
+}
+
+impl<'a> Flattener<'a> {
+    fn module_netlist(&mut self, name: &str) -> E<()> {
+        if !self.cache.contains_key(name) {
+            let netlist = ModuleNetlist::build(self.project, name, self.config)?;
+            self.cache.insert(name.to_string(), netlist);
+        }
+        Ok(())
+    }
+
+    fn net(&mut self, name: &str, kind: NetKind, rank: u8) -> String {
+        self.names.intern(name, rank);
+        self.kinds.entry(name_key(name)).or_insert(kind);
+        name.to_string()
+    }
+
+    fn join(&mut self, a: &str, b: &str) {
+        let (a, b) = (self.names.id(a).unwrap(), self.names.id(b).unwrap());
+        self.names.uf.union(a, b);
+    }
+
+    // binding maps the module's own net names to flat net names for the
+    // nets that leave it through its terminals.
+    fn expand(&mut self, module: &str, prefix: &str, binding: &HashMap<String, String>) -> E<()> {
+        if self.stack.iter().any(|m| m == module) {
+            let msg = format!("Recursive module: {} -> {}", self.stack.join(" -> "), module);
+            return bail!(msg);
+        }
+        self.module_netlist(module)?;
+        self.stack.push(module.to_string());
+
+        let (nets, instances): (Vec<Net>, Vec<(String, String, Vec<(String, String)>, Option<Memory>)>) = {
+            let m = &self.cache[module];
+            (m.nets.clone(),
+             m.instances
+              .iter()
+              .map(|i| (i.name.clone(), i.module.clone(), i.connections.clone(), i.memory.clone()))
+              .collect())
+        };
+
+        // every inner net gets its own path name, nets that leave through a
+        // terminal are joined to the outer net and the better name wins.
+        let mut flat: HashMap<String, String> = HashMap::new();
+        for net in &nets {
+            let name = if net.kind == NetKind::Local {
+                let rank = if net.anon { RANK_ANON } else { RANK_LABEL };
+                self.net(&format!("{}{}", prefix, net.name), net.kind, rank)
+            } else {
+                self.net(&net.name, net.kind, RANK_GLOBAL)
+            };
+            if let Some(bound) = binding.get(&net.name) {
+                self.join(&name, bound);
+            }
+            flat.insert(net.name.clone(), name);
+        }
+
+        for (name, sub, connections, memory) in instances {
+            let connections: Vec<(String, String)> =
+                connections.into_iter().map(|(t, n)| (t, flat[&n].clone())).collect();
+            let path = format!("{}{}", prefix, name);
+
+            if let Some(kind) = gate_kind(&sub) {
+                self.devices.push(Device { name: path, module: sub, kind, connections, memory });
+                continue;
+            }
+            if memory.is_some() {
+                self.devices.push(Device { name: path, module: sub, kind: GateKind::Memory, connections, memory });
+                continue;
+            }
+
+            self.module_netlist(&sub)?;
+            let mut child_binding: HashMap<String, String> = HashMap::new();
+            let mut joins = vec![];
+            {
+                let child = &self.cache[&sub];
+                for (term, net) in &connections {
+                    if let Some(child_net) = child.net(term) {
+                        match child_binding.get(child_net) {
+                            // two terminals on the same inner net short their outer nets.
+                            Some(other) if other != net => joins.push((other.clone(), net.clone())),
+                            Some(_) => {}
+                            None => {
+                                child_binding.insert(child_net.to_string(), net.clone());
+                            }
+                        }
+                    }
+                }
+            }
+            for (a, b) in joins {
+                self.join(&a, &b);
+            }
+            self.expand(&sub, &format!("{}.", path), &child_binding)?;
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+impl Netlist {
+    pub fn flatten(project: &Project, top: &str, config: &NetConfig) -> E<Netlist> {
+        let mut f = Flattener { project,
+                                config,
+                                cache: HashMap::new(),
+                                names: BitNames::new(),
+                                kinds: HashMap::new(),
+                                devices: vec![],
+                                stack: vec![] };
+        f.expand(top, "", &HashMap::new())?;
+
+        // nets shorted together through a submodule get one name.
+        let canon = f.names.canonical();
+        let mut nets = vec![];
+        for id in 0..f.names.names.len() {
+            if canon[id] == id {
+                let name = f.names.names[id].clone();
+                nets.push(Net { kind: f.kinds[&name_key(&name)], name, anon: f.names.rank[id] == RANK_ANON });
+            }
+        }
+        let names = &f.names;
+        let rename = |net: &str| names.names[canon[names.id(net).unwrap()]].clone();
+
+        let mut devices = f.devices;
+        for dev in devices.iter_mut() {
+            for (_, net) in dev.connections.iter_mut() {
+                *net = rename(net);
+            }
+        }
+
+        let top_netlist = &f.cache[top];
+        let ports = top_netlist.ports
+                               .iter()
+                               .map(|p| ModulePort { sig: p.sig.clone(),
+                                                     direction: p.direction,
+                                                     nets: p.nets.iter().map(|n| rename(n)).collect() })
+                               .collect();
+
+        let aliases = top_netlist.aliases.iter().map(|(bit, net)| (bit.clone(), rename(net))).collect();
+
+        Ok(Netlist { top: top.to_string(), nets, devices, ports, aliases })
+    }
+
+    // the net a top level bit name is on, test groups name bits this way.
+    // Inner nets go by their path, AND2_0.in1 say.
+    pub fn net(&self, bit: &str) -> Option<&str> {
+        match self.aliases.get(&name_key(bit)) {
+            Some(net) => Some(net),
+            None => self.nets.iter().find(|n| n.name.eq_ignore_ascii_case(bit)).map(|n| n.name.as_str()),
+        }
+    }
+
+    // nets that more than one thing drives without being able to let go,
+    // gate outputs and input ports. Tristates and memory data pins can share
+    // a net, they float when not enabled.
+    pub fn driver_conflicts(&self) -> Vec<DriverConflict> {
+        let mut found: Vec<(&str, String)> = vec![];
+        for port in &self.ports {
+            if port.direction == Some(In) {
+                for (bit, net) in port.sig.bits().into_iter().zip(port.nets.iter()) {
+                    if let Bit::Name(bit) = bit {
+                        found.push((net, format!("input {}", bit)));
+                    }
+                }
+            }
+        }
+        for dev in &self.devices {
+            if dev.kind == GateKind::Tristate || dev.kind == GateKind::Memory {
+                continue;
+            }
+            for term in gate_terminals(&dev.module).unwrap_or_default() {
+                if term.direction != Out {
+                    continue;
+                }
+                for bit in term.sig.bits() {
+                    if let Bit::Name(bit) = bit {
+                        if let Some(net) = dev.net(&bit) {
+                            found.push((net, format!("{}.{}", dev.name, bit)));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut conflicts: Vec<DriverConflict> = vec![];
+        for (net, driver) in found {
+            match conflicts.iter_mut().find(|c| c.net == net) {
+                Some(c) => c.drivers.push(driver),
+                None => conflicts.push(DriverConflict { net: net.to_string(), drivers: vec![driver] }),
+            }
+        }
+        conflicts.retain(|c| c.drivers.len() > 1);
+        conflicts
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    #[test]
+    fn jumper_aliases() {
+        let p = project("Jumper5.json");
+        let m = ModuleNetlist::build(&p, "/user/Jumper5", &NetConfig::new()).unwrap();
+        assert_eq!(m.net("A"), m.net("vout"));
+        assert_eq!(m.net("A"), m.net("B"));
+
+        // the flat netlist still knows the labels that lost their name.
+        let n = Netlist::flatten(&p, "/user/Jumper5", &NetConfig::new()).unwrap();
+        assert!(n.net("vout").is_some());
+        assert_eq!(n.net("VOUT"), n.net("A"));
+    }
+
+    #[test]
+    fn anonymous_names() {
+        // the wire between the inverters has no label, B's wire is labelled _N1.
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Anon": {"schematic": [["/gates/inverter", [0, 0, 0]],
+                                         ["/gates/inverter", [48, 0, 0]],
+                                         ["wire", [0, 0, 0, -8, 0], {"signal": "A"}],
+                                         ["wire", [32, 0, 0, 16, 0]],
+                                         ["wire", [80, 0, 0, 8, 0], {"signal": "Y"}],
+                                         ["wire", [0, 32, 0, 8, 0], {"signal": "_N1"}],
+                                         ["port", [-8, 0, 0], {"signal": "A", "direction": "in"}],
+                                         ["port", [0, 32, 0], {"signal": "B", "direction": "in"}],
+                                         ["port", [88, 0, 4], {"signal": "Y", "direction": "out"}]]}}]"#)
+                    .unwrap();
+        let m = ModuleNetlist::build(&p, "/user/Anon", &NetConfig::new()).unwrap();
+        let z = m.instances[0].connections.iter().find(|(t, _)| t == "Z").map(|(_, n)| n.as_str());
+        assert_eq!(z, Some("_N2"));
+        assert_eq!(m.net("_N1"), Some("B"));
+        let anon: Vec<&str> = m.nets.iter().filter(|n| n.anon).map(|n| n.name.as_str()).collect();
+        assert_eq!(anon, vec!["_N2"]);
+
+        let n = Netlist::flatten(&p, "/user/Anon", &NetConfig::new()).unwrap();
+        assert_eq!(n.driver_conflicts(), vec![]);
+        assert!(n.nets.iter().any(|n| n.name == "_N2" && n.anon));
+    }
+
+    #[test]
+    fn names_ignore_case() {
+        // the icon says CIN and COUT, the schematic's wires cin and cout.
+        let p = project("NoRepFA2.json");
+        let m = ModuleNetlist::build(&p, "/user/NoRepFA2", &NetConfig::new()).unwrap();
+        assert_eq!(m.net("COUT"), Some("cout"));
+        let cin = m.ports.iter().find(|p| p.sig.to_string() == "CIN").unwrap();
+        assert_eq!(cin.nets, vec!["cin".to_string()]);
+        assert_eq!(cin.direction, Some(In));
+        assert!(!m.nets.iter().any(|n| n.name == "CIN" || n.name == "COUT"));
+    }
+
+    #[test]
+    fn gate_connections() {
+        let p = project("Vdd.json");
+        let m = ModuleNetlist::build(&p, "/user/Vdd", &NetConfig::new()).unwrap();
+        assert_eq!(m.instances.len(), 1);
+        let and2 = &m.instances[0];
+        assert_eq!(and2.connections,
+                   vec![("A".to_string(), "vdd".to_string()),
+                        ("B".to_string(), "X".to_string()),
+                        ("Z".to_string(), "Y".to_string())]);
+        assert_eq!(m.net_kind("vdd"), Some(NetKind::TieHigh));
+    }
+
+    #[test]
+    fn constant_ties() {
+        let p = project("Constant1.json");
+        let m = ModuleNetlist::build(&p, "/user/Constant1", &NetConfig::new()).unwrap();
+        let and2 = &m.instances[0];
+        assert_eq!(and2.connections[0], ("A".to_string(), "vdd".to_string()));
+        assert_eq!(m.net("1'1"), None);
+    }
+
+    #[test]
+    fn replication() {
+        let p = project("Mux4Rep1.json");
+        let m = ModuleNetlist::build(&p, "/user/Mux4Rep1", &NetConfig::new()).unwrap();
+        assert_eq!(m.instances.len(), 2);
+        let find = |i: usize, t: &str| {
+            m.instances[i].connections.iter().find(|(term, _)| term == t).map(|(_, n)| n.clone()).unwrap()
+        };
+        assert_eq!(find(0, "S[1]"), "A[1]");
+        assert_eq!(find(1, "S[1]"), "A[0]");
+        assert_eq!(find(0, "S[0]"), "B[1]");
+        assert_eq!(find(0, "Y"), "Y[1]");
+        assert_eq!(find(1, "Y"), "Y[0]");
+        assert_eq!(find(0, "D0"), "D[0]");
+        assert_eq!(find(1, "D0"), "D[0]");
+    }
+
+    #[test]
+    fn width_mismatch() {
+        let p = Project::from_str(r#"["Jade", {"/user/W": {"schematic": [
+            ["wire", [0, 0, 0, 8, 0], {"signal": "A[1:0]"}],
+            ["wire", [8, 0, 0, 8, 0], {"signal": "B[2:0]"}]]}}]"#).unwrap();
+        assert!(ModuleNetlist::build(&p, "/user/W", &NetConfig::new()).is_err());
+    }
+
+    #[test]
+    fn flatten1() {
+        let p = project("use-and2.json");
+        let n = Netlist::flatten(&p, "/user/UseAND2", &NetConfig::new()).unwrap();
+        assert_eq!(n.devices.len(), 1);
+        assert_eq!(n.devices[0].name, "AND2_0.and2_0");
+        assert_eq!(n.devices[0].net("A"), Some("AND2_0.in1"));
+    }
+
+    #[test]
+    fn flatten_globals() {
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Inner": {"schematic": [["/gates/buffer", [0, 0, 0]],
+                                          ["wire", [0, 0, 0, -8, 0], {"signal": "clk"}],
+                                          ["wire", [32, 0, 0, 8, 0], {"signal": "Q"}]],
+                            "icon": [["terminal", [8, 0, 4], {"name": "Q"}]]},
+            "/user/Outer": {"schematic": [["/user/Inner", [0, 0, 0]],
+                                          ["/user/Inner", [0, 16, 0]],
+                                          ["wire", [8, 0, 0, 8, 0], {"signal": "Q1"}],
+                                          ["wire", [8, 16, 0, 8, 0], {"signal": "Q2"}]]}}]"#)
+                    .unwrap();
+
+        let local = Netlist::flatten(&p, "/user/Outer", &NetConfig::new()).unwrap();
+        assert_eq!(local.devices[0].net("A"), Some("Inner_0.clk"));
+        assert_eq!(local.devices[1].net("A"), Some("Inner_1.clk"));
+
+        let config = NetConfig::new().with_global("clk");
+        let global = Netlist::flatten(&p, "/user/Outer", &config).unwrap();
+        assert_eq!(global.devices[0].net("A"), Some("clk"));
+        assert_eq!(global.devices[1].net("A"), Some("clk"));
+        assert_eq!(global.devices[1].net("Z"), Some("Q2"));
+    }
+
+    #[test]
+    fn recursion() {
+        let p = Project::from_str(r#"["Jade", {
+            "/user/R": {"schematic": [["/user/R", [0, 0, 0]]],
+                        "icon": [["terminal", [8, 0, 4], {"name": "Q"}]]}}]"#)
+                    .unwrap();
+        assert!(Netlist::flatten(&p, "/user/R", &NetConfig::new()).is_err());
+    }
+
+    #[test]
+    fn driver_conflicts() {
+        let p = project("Tristate1.json");
+        let n = Netlist::flatten(&p, "/user/Tristate1", &NetConfig::new()).unwrap();
+        assert_eq!(n.driver_conflicts(), vec![]);
+
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Short": {"schematic": [["/gates/buffer", [0, 0, 0]],
+                                          ["/gates/inverter", [0, 16, 0]],
+                                          ["/gates/tristate", [0, 48, 0]],
+                                          ["wire", [0, 0, 0, -8, 0], {"signal": "A"}],
+                                          ["wire", [0, 16, 0, -8, 0], {"signal": "A"}],
+                                          ["wire", [32, 0, 0, 8, 0], {"signal": "Y"}],
+                                          ["wire", [32, 16, 0, 8, 0], {"signal": "Y"}],
+                                          ["wire", [32, 48, 0, 8, 0], {"signal": "Y"}],
+                                          ["port", [-8, 0, 0], {"signal": "A", "direction": "in"}],
+                                          ["port", [40, 0, 4], {"signal": "Y", "direction": "out"}]]}}]"#)
+                    .unwrap();
+        let n = Netlist::flatten(&p, "/user/Short", &NetConfig::new()).unwrap();
+        assert_eq!(n.driver_conflicts(),
+                   vec![DriverConflict { net: "Y".to_string(),
+                                         drivers: vec!["buffer_0.Z".to_string(), "inverter_1.Z".to_string()] }]);
+    }
+}
@@ -19,6 +19,15 @@ impl Part {
         if let Ok(x) = Text::from_value(val) {
             return Ok(Part::Text(x));
         }
+        if let Ok(x) = Vdd::from_value(val) {
+            return Ok(Part::Vdd(x));
+        }
+        if let Ok(x) = Ground::from_value(val) {
+            return Ok(Part::Ground(x));
+        }
+        if let Ok(x) = Memory::from_value(val) {
+            return Ok(Part::Memory(x));
+        }
         if let Ok(x) = SubModule::from_value(val) {
             return Ok(Part::SubModule(x));
         }
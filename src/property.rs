@@ -0,0 +1,49 @@
+use serde_json::Value;
+
+use crate::common::*;
+use crate::types::*;
+
+impl Property {
+    pub fn from_value(val: &Value) -> E<Property> {
+        let mut val_iter = bailif!(tagged_array("property", &val), "Property::from_value failes to decode")?;
+
+        let coord3: Coord3 = match val_iter.next() {
+            Some(c) => serde_json::from_value::<Coord3>(c.clone())?,
+            None => {
+                return bailfmt!("Property expects 2 elements, a location and properties object, got: {:?}", val);
+            }
+        };
+
+        match val_iter.next() {
+            Some(Value::Object(o)) => {
+                let format = if let Some(Value::String(s)) = o.get("format") {
+                    s.to_string()
+                } else {
+                    return bail!("property expected a format string but didn't find anything");
+                };
+                let align = if let Some(Value::String(s)) = o.get("align") { Some(s.to_string()) } else { None };
+                Ok(Property { coord3, format, align })
+            }
+            _ => bailfmt!("Property expects 2 elements, a location and properties object, got: {:?}", val),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn property1() {
+        let val = json!(["property", [0, -9, 0], {"format": "{name}", "align": "bottom-center"}]);
+        let expect = Property { coord3: Coord3 { x: 0, y: -9, r: Rot0 },
+                                format: "{name}".to_string(),
+                                align: Some("bottom-center".to_string()) };
+        assert_eq!(Property::from_value(&val), Ok(expect));
+    }
+}
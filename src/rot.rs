@@ -0,0 +1,42 @@
+use crate::types::*;
+
+impl Rot {
+    // transform an offset relative to a part's origin into schematic
+    // coordinates, the same eight orientations jade uses.
+    pub fn transform(&self, x: i32, y: i32) -> (i32, i32) {
+        match self {
+            Rot0 => (x, y),
+            Rot270 => (-y, x),
+            Rot180 => (-x, -y),
+            Rot90 => (y, -x),
+            FlipX => (-x, y),
+            TransposeNeg => (-y, -x),
+            FlipY => (x, -y),
+            TransposePos => (y, x),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform1() {
+        assert_eq!(Rot0.transform(48, 8), (48, 8));
+        assert_eq!(Rot270.transform(48, 8), (-8, 48));
+        assert_eq!(Rot180.transform(48, 8), (-48, -8));
+        assert_eq!(Rot90.transform(48, 8), (8, -48));
+    }
+
+    #[test]
+    fn transform2() {
+        assert_eq!(FlipX.transform(48, 8), (-48, 8));
+        assert_eq!(FlipY.transform(48, 8), (48, -8));
+        assert_eq!(TransposeNeg.transform(48, 8), (-8, -48));
+        assert_eq!(TransposePos.transform(48, 8), (8, 48));
+    }
+}
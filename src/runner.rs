@@ -0,0 +1,241 @@
+use std::fmt;
+
+use crate::cycle::*;
+use crate::netlist::*;
+use crate::sim::*;
+use crate::types::*;
+
+// Runs a module's test the way jade's check button does: every test line
+// walks the .cycle actions against the gate simulator and each sampled bit
+// is compared with the expected value.
+
+#[derive(Debug, PartialEq)]
+pub struct BitFailure {
+    pub group: String,
+    pub bit: String,
+    pub expected: BinVal,
+    pub got: Logic,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VectorResult {
+    pub index: usize,
+    pub line: usize,
+    pub comment: Option<String>,
+    // simulated time in femtoseconds when the vector finished.
+    pub time: u64,
+    pub failures: Vec<BitFailure>,
+}
+
+impl VectorResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TestReport {
+    pub module: String,
+    pub vectors: Vec<VectorResult>,
+    pub time: u64,
+    pub violations: Vec<Violation>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> bool {
+        self.vectors.iter().all(|v| v.passed())
+    }
+
+    pub fn failed(&self) -> Vec<&VectorResult> {
+        self.vectors.iter().filter(|v| !v.passed()).collect()
+    }
+}
+
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for v in self.failed() {
+            for fail in &v.failures {
+                write!(f,
+                       "vector {} (line {}) at {} fs: {} expected {} got {}",
+                       v.index,
+                       v.line,
+                       v.time,
+                       fail.bit,
+                       expected_char(fail.expected),
+                       fail.got.to_char())?;
+                if let Some(c) = &v.comment {
+                    write!(f, " //{}", c)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        for v in &self.violations {
+            let kind = match v.kind {
+                ViolationKind::Setup => "setup",
+                ViolationKind::Hold => "hold",
+            };
+            writeln!(f, "{} violation at {} fs: {}", kind, v.time, v.device)?;
+        }
+        let failed = self.failed().len();
+        if failed == 0 {
+            writeln!(f, "{}: {} vectors passed", self.module, self.vectors.len())
+        } else {
+            writeln!(f, "{}: {} of {} vectors failed", self.module, failed, self.vectors.len())
+        }
+    }
+}
+
+fn expected_char(v: BinVal) -> char {
+    match v {
+        L => '0',
+        H => '1',
+        X => 'X',
+        Z => 'Z',
+        DontCare => '-',
+    }
+}
+
+pub fn matches(expected: BinVal, got: Logic) -> bool {
+    match expected {
+        DontCare => true,
+        _ => Logic::from(expected) == got,
+    }
+}
+
+fn step(sim: &mut Simulator, step: &Step, failures: &mut Vec<BitFailure>) -> E<()> {
+    match step {
+        Step::Drive(_, values) => {
+            for (bit, v) in values {
+                sim.drive(bit, Logic::from(*v))?;
+            }
+        }
+        Step::Release(_, bits) => {
+            for bit in bits {
+                sim.release(bit)?;
+            }
+        }
+        Step::Set(bit, high) => sim.drive(bit, Logic::from_bool(*high))?,
+        Step::Wait(d) => sim.run_for(*d)?,
+        Step::Check(group, values) => {
+            // driven values take effect before anything is looked at.
+            let now = sim.now();
+            sim.run_until(now)?;
+            for (bit, expected) in values {
+                let got = sim.sample(bit)?;
+                if !matches(*expected, got) {
+                    failures.push(BitFailure { group: group.clone(), bit: bit.clone(), expected: *expected, got });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn run_test(project: &Project, module: &str, config: &NetConfig, test: &ModTest) -> E<TestReport> {
+    if matches!(test.mode, Some(ModeLine { mode: Mode::Device, .. })) {
+        return bailfmt!("Test of {} needs a device level simulator", module);
+    }
+    let netlist = Netlist::flatten(project, module, config)?;
+    let mut sim = Simulator::new(&netlist)?;
+    run_on(&mut sim, module, test)
+}
+
+// run one of the tests saved with the module, by name.
+pub fn run_saved(project: &Project, module: &str, config: &NetConfig, name: &str) -> E<TestReport> {
+    run_test(project, module, config, project.test(module, name)?)
+}
+
+// run a test on a simulator set up by the caller, one that traces its nets
+// for a waveform say.
+pub fn run_on(sim: &mut Simulator, module: &str, test: &ModTest) -> E<TestReport> {
+    let mut vectors = vec![];
+    for (index, cycle) in test.cycles()?.into_iter().enumerate() {
+        let mut failures = vec![];
+        for s in &cycle.steps {
+            match step(sim, s, &mut failures) {
+                Ok(()) => {}
+                berr => return bail!(berr, format!("Test line {}", cycle.line)),
+            }
+        }
+        vectors.push(VectorResult { index, line: cycle.line, comment: cycle.comment, time: sim.now(), failures });
+    }
+    Ok(TestReport { module: module.to_string(), vectors, time: sim.now(), violations: sim.violations().to_vec() })
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::{each_saved_test, project, BROKEN};
+    use std::path::Path;
+
+    #[test]
+    fn and2() {
+        let p = project("use-and2.json");
+        let src = ".group inputs in1 in2\n.group outputs out\n.mode gate\n\
+                   .cycle assert inputs tran 9n sample outputs tran 1n\n\n\
+                   00 L\n01 L\n10 L // ten\n11 H\n1- -\n";
+        let test = ModTest::from_str(src).unwrap();
+        let report = run_test(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(report.passed());
+        assert_eq!(report.vectors.len(), 5);
+        assert_eq!(report.vectors[2].line, 8);
+        assert_eq!(report.time, 50_000_000);
+        assert_eq!(report.to_string(), "/user/AND2: 5 vectors passed\n");
+
+        let src = ".group inputs in1 in2\n.group outputs out\n.cycle assert inputs tran 9n sample outputs\n\
+                   11 L // wrong\n0Z L\n";
+        let test = ModTest::from_str(src).unwrap();
+        let report = run_test(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(!report.passed());
+        assert_eq!(report.vectors[0].failures,
+                   vec![BitFailure { group: "outputs".to_string(), bit: "out".to_string(), expected: L, got: Logic::One }]);
+        assert!(report.vectors[1].passed());
+        assert_eq!(report.to_string(),
+                   "vector 0 (line 4) at 9000000 fs: out expected 0 got 1 // wrong\n/user/AND2: 1 of 2 vectors failed\n");
+    }
+
+    #[test]
+    fn bool_unit() {
+        // the 6.004 boolean unit, a replicated mux4 selected by A and B.
+        let p = project("Bool1.json");
+        let test = ModTest::from_str(&std::fs::read_to_string("./test-data/modtests/bool.test").unwrap()).unwrap();
+        let report = run_test(&p, "/user/Bool1", &NetConfig::new(), &test).unwrap();
+        assert_eq!(report.vectors.len(), 16);
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[test]
+    fn sequential() {
+        for (file, module, n) in &[("LeReg1.json", "/user/LeReg1", 6),
+                                   ("zreg.json", "/user/zreg", 9),
+                                   ("MemUnit1.json", "/user/MemUnit1", 6),
+                                   ("MemUnit2.json", "/user/MemUnit2", 8)]
+        {
+            let report = run_saved(&project(file), module, &NetConfig::new(), "test").unwrap();
+            assert_eq!(report.vectors.len(), *n);
+            assert!(report.passed(), "{}", report);
+            assert!(report.violations.is_empty());
+        }
+    }
+
+    #[test]
+    fn saved_tests() {
+        // every gate level test saved in test-data has to pass.
+        let ran = each_saved_test(|file, p, m, _| {
+            let report = run_saved(p, &m.name, &NetConfig::new(), "test")
+                .unwrap_or_else(|b| panic!("{} {}: {:?}", file, m.name, b));
+            assert!(report.passed(), "{} {}\n{}", file, m.name, report);
+        });
+        assert_eq!(ran, 163);
+
+        // the broken ones still fail, or the list is stale.
+        for (file, module) in BROKEN {
+            if let Ok(report) = run_saved(&project(file), module, &NetConfig::new(), "test") {
+                assert!(!report.passed(), "{} {} passes now", file, module);
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::types::*;
 use regex::Regex;
 use serde_json::Value;
+use std::fmt;
 
 // these should all be Result instead of Option for error messages.
 
@@ -55,19 +56,19 @@ pub fn general_sig_quote(input: &str, pattern_string: &str, prefix: &str, radix:
 }
 
 pub fn bin_sig_quote(input: &str) -> Option<Sig> {
-    general_sig_quote(input, "^(0b[01]+)'([0-9])$", "0b", 2)
+    general_sig_quote(input, "^(0b[01]+)'([0-9]+)$", "0b", 2)
 }
 
 pub fn dec_sig_quote(input: &str) -> Option<Sig> {
-    general_sig_quote(input, "^(0d[0-9]+)'([0-9])$", "0d", 10)
+    general_sig_quote(input, "^(0d[0-9]+)'([0-9]+)$", "0d", 10)
 }
 
 pub fn hex_sig_quote(input: &str) -> Option<Sig> {
-    general_sig_quote(input, "^(0x[0-9A-Fa-f]+)'([0-9])$", "0x", 16)
+    general_sig_quote(input, "^(0x[0-9A-Fa-f]+)'([0-9]+)$", "0x", 16)
 }
 
 pub fn implicit_dec_sig_quote(input: &str) -> Option<Sig> {
-    general_sig_quote(input, "^([0-9]+)'([0-9])$", "", 10)
+    general_sig_quote(input, "^([0-9]+)'([0-9]+)$", "", 10)
 }
 
 pub fn sig_quote(input: &str) -> Option<Sig> {
@@ -127,6 +128,47 @@ impl Sig {
             bailfmt!("error parsing sig string: {:?}", val)
         }
     }
+
+    // expand a signal into its individual bits, most significant first.
+    pub fn bits(&self) -> Vec<Bit> {
+        let indexed = |name: &str, idx: i32| Bit::Name(format!("{}[{}]", name, idx));
+        match self {
+            SigSimple(name) => vec![Bit::Name(name.clone())],
+            SigIndex(name, idx) => vec![indexed(name, *idx)],
+            SigHash(name, count) => (0..*count).map(|_| Bit::Name(name.clone())).collect(),
+            SigRange(name, from, to) => range_step(*from, *to, 1).map(|i| indexed(name, i)).collect(),
+            SigRangeStep(name, from, to, step) => range_step(*from, *to, *step).map(|i| indexed(name, i)).collect(),
+            SigQuote(val, width) => (0..*width).rev().map(|i| Bit::Const((*val as i64 >> i.min(63)) & 1 == 1)).collect(),
+            SigConcat(sigs) => sigs.iter().flat_map(|sig| sig.bits()).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.bits().len()
+    }
+}
+
+// back to jade's own spelling, A[3:0] or A[7:0:2].
+impl fmt::Display for Sig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SigSimple(name) => write!(f, "{}", name),
+            SigIndex(name, i) => write!(f, "{}[{}]", name, i),
+            SigHash(name, n) => write!(f, "{}#{}", name, n),
+            SigRange(name, from, to) => write!(f, "{}[{}:{}]", name, from, to),
+            SigRangeStep(name, from, to, step) => write!(f, "{}[{}:{}:{}]", name, from, to, step),
+            SigQuote(val, width) => write!(f, "{}'{}", val, width),
+            SigConcat(sigs) => write!(f, "{}", sigs.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(",")),
+        }
+    }
+}
+
+// indices from `from` to `to` inclusive, counting down when from > to.
+fn range_step(from: i32, to: i32, step: i32) -> impl Iterator<Item = i32> {
+    let step = step.max(1);
+    let count = (from - to).abs() / step + 1;
+    let dir = if from > to { -step } else { step };
+    (0..count).map(move |i| from + i * dir)
 }
 
 #[cfg(test)]
@@ -313,6 +355,13 @@ mod tests {
         assert_eq!(got, expect);
     }
 
+    #[test]
+    fn sig_quote6() {
+        let got = sig_quote("1'32");
+        let expect = Some(SigQuote(1, 32));
+        assert_eq!(got, expect);
+    }
+
     #[test]
     fn sig_concat1() {
         let got = sig_concat("0x12'4, 0x12'4");
@@ -327,6 +376,33 @@ mod tests {
         assert_eq!(got, expect);
     }
 
+    #[test]
+    fn sig_bits1() {
+        let got = parse_sig("A[2:0]").unwrap().bits();
+        let expect = vec![Bit::Name("A[2]".to_string()), Bit::Name("A[1]".to_string()), Bit::Name("A[0]".to_string())];
+        assert_eq!(got, expect);
+    }
+
+    #[test]
+    fn sig_bits2() {
+        let got = parse_sig("A[0:4:2]").unwrap().bits();
+        let expect = vec![Bit::Name("A[0]".to_string()), Bit::Name("A[2]".to_string()), Bit::Name("A[4]".to_string())];
+        assert_eq!(got, expect);
+    }
+
+    #[test]
+    fn sig_bits3() {
+        let got = parse_sig("0b101'3,B").unwrap().bits();
+        let expect = vec![Bit::Const(true), Bit::Const(false), Bit::Const(true), Bit::Name("B".to_string())];
+        assert_eq!(got, expect);
+    }
+
+    #[test]
+    fn sig_width1() {
+        assert_eq!(parse_sig("A#4").unwrap().width(), 4);
+        assert_eq!(parse_sig("A[31:0],B[3]").unwrap().width(), 33);
+    }
+
     #[test]
     fn sig_concat3() {
         let got = one_of_sig("0x12'4, _asdf456[123:0:5]");
@@ -1,3 +1,4 @@
+use crate::common::*;
 use crate::sig;
 
 use crate::types::*;
@@ -15,8 +16,8 @@ impl Signal {
                     return bailfmt!("Can't parse signal string: {:?}", sig_string);
                 }
             }
-            if let Some(Value::Number(width)) = o.get("width") {
-                signal.width = width.as_u64()
+            if let Some(width) = o.get("width") {
+                signal.width = numeric_property(width)
             }
             if let Some(dir) = o.get("direction") {
                 signal.direction = Some(Direction::from_value(dir.clone())?);
@@ -57,4 +58,11 @@ mod tests {
         let expected = Signal { sig: Some(Sig::SigRange("out".to_string(), 2, 0)), width: None, direction: None };
         assert_eq!(got, expected);
     }
+
+    #[test]
+    fn signal4() {
+        let val = json!({"signal":"1'1","width":"3"});
+        let got = Signal::from_value(&val).unwrap();
+        assert_eq!(got.width, Some(3));
+    }
 }
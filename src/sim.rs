@@ -0,0 +1,872 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::gates::*;
+use crate::netlist::*;
+use crate::types::*;
+
+// An event driven simulator over a flattened netlist. Nets carry one of
+// four values, a gate output changes its net one propagation delay after
+// an input changed. Time is kept in femtoseconds.
+//
+//   let mut sim = Simulator::new(&netlist)?;
+//   sim.drive("A", Logic::One)?;
+//   sim.run_for(Duration::NanoSecond(1.0))?;
+//   sim.sample("Z")?
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Logic {
+    Zero,
+    One,
+    X,
+    Z,
+}
+
+impl Logic {
+    pub fn from_bool(b: bool) -> Logic {
+        if b { Logic::One } else { Logic::Zero }
+    }
+
+    // a floating input reads as unknown.
+    fn to_bool(self) -> Option<bool> {
+        match self {
+            Logic::Zero => Some(false),
+            Logic::One => Some(true),
+            _ => None,
+        }
+    }
+
+    // what a gate passes on, a floating input comes out unknown.
+    fn strong(self) -> Logic {
+        if self == Logic::Z { Logic::X } else { self }
+    }
+
+    fn not(self) -> Logic {
+        match self.to_bool() {
+            Some(b) => Logic::from_bool(!b),
+            None => Logic::X,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Logic::Zero => '0',
+            Logic::One => '1',
+            Logic::X => 'X',
+            Logic::Z => 'Z',
+        }
+    }
+}
+
+impl From<BinVal> for Logic {
+    fn from(v: BinVal) -> Logic {
+        match v {
+            L => Logic::Zero,
+            H => Logic::One,
+            Z => Logic::Z,
+            X | DontCare => Logic::X,
+        }
+    }
+}
+
+// propagation delays, roughly those of jade's gate library.
+pub fn default_delay(kind: GateKind) -> Duration {
+    match kind {
+        GateKind::Buffer | GateKind::Inverter => Duration::PicoSecond(20.0),
+        GateKind::Nand | GateKind::Nor => Duration::PicoSecond(30.0),
+        GateKind::And | GateKind::Or | GateKind::Tristate => Duration::PicoSecond(50.0),
+        GateKind::Xor | GateKind::Xnor | GateKind::Mux2 => Duration::PicoSecond(70.0),
+        GateKind::Mux4 | GateKind::Dreg => Duration::PicoSecond(100.0),
+        GateKind::Memory => Duration::PicoSecond(200.0),
+    }
+}
+
+// too many events at one instant means a zero delay loop that never settles.
+const MAX_DELTAS: usize = 100_000;
+
+// register timing, changes of D closer to the clock edge than this make Q
+// unknown.
+const DEFAULT_SETUP: Duration = Duration::PicoSecond(50.0);
+const DEFAULT_HOLD: Duration = Duration::PicoSecond(20.0);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ViolationKind {
+    Setup,
+    Hold,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Violation {
+    pub time: u64,
+    pub device: String,
+    pub kind: ViolationKind,
+}
+
+#[derive(Debug)]
+struct MemState {
+    naddr: usize,
+    ndata: usize,
+    // each word least significant bit first.
+    words: Vec<Vec<Logic>>,
+}
+
+#[derive(Debug)]
+enum State {
+    Gate,
+    // when D last changed and when the clock last rose.
+    Dreg { d_changed: Option<u64>, edge: Option<u64> },
+    Memory(MemState),
+}
+
+#[derive(Debug)]
+struct SimDevice {
+    name: String,
+    kind: GateKind,
+    inputs: Vec<Option<usize>>,
+    // the driver slot of each output, None when it's not connected.
+    outputs: Vec<Option<usize>>,
+    // the value last scheduled on each output.
+    driving: Vec<Logic>,
+    state: State,
+}
+
+// what a net with several drivers carries: Z gives way to any driven value,
+// drivers that disagree make it unknown.
+pub fn resolve(drivers: impl Iterator<Item = Logic>) -> Logic {
+    let mut value = Logic::Z;
+    for v in drivers {
+        value = match (value, v) {
+            (a, Logic::Z) => a,
+            (Logic::Z, b) => b,
+            (a, b) if a == b => a,
+            _ => Logic::X,
+        };
+    }
+    value
+}
+
+fn and(inputs: &[Logic]) -> Logic {
+    if inputs.iter().any(|v| *v == Logic::Zero) {
+        Logic::Zero
+    } else if inputs.iter().all(|v| *v == Logic::One) {
+        Logic::One
+    } else {
+        Logic::X
+    }
+}
+
+fn or(inputs: &[Logic]) -> Logic {
+    and(&inputs.iter().map(|v| v.not()).collect::<Vec<Logic>>()).not()
+}
+
+fn xor(inputs: &[Logic]) -> Logic {
+    let mut acc = false;
+    for v in inputs {
+        match v.to_bool() {
+            Some(b) => acc ^= b,
+            None => return Logic::X,
+        }
+    }
+    Logic::from_bool(acc)
+}
+
+// an unknown select still gives a value when both data inputs agree.
+fn mux(select: Logic, d0: Logic, d1: Logic) -> Logic {
+    match select.to_bool() {
+        Some(false) => d0.strong(),
+        Some(true) => d1.strong(),
+        None if d0 == d1 && d0.to_bool().is_some() => d0,
+        None => Logic::X,
+    }
+}
+
+fn evaluate(kind: GateKind, inputs: &[Logic]) -> Logic {
+    match kind {
+        GateKind::And => and(inputs),
+        GateKind::Or => or(inputs),
+        GateKind::Xor => xor(inputs),
+        GateKind::Nand => and(inputs).not(),
+        GateKind::Nor => or(inputs).not(),
+        GateKind::Xnor => xor(inputs).not(),
+        GateKind::Buffer => inputs[0].strong(),
+        GateKind::Inverter => inputs[0].not(),
+        // A, E
+        GateKind::Tristate => match inputs[1].to_bool() {
+            Some(true) => inputs[0].strong(),
+            Some(false) => Logic::Z,
+            None => Logic::X,
+        },
+        // D0, D1, S
+        GateKind::Mux2 => mux(inputs[2], inputs[0], inputs[1]),
+        // D0, D1, D2, D3, S[0], S[1]
+        GateKind::Mux4 => {
+            let lo = mux(inputs[4], inputs[0], inputs[1]);
+            let hi = mux(inputs[4], inputs[2], inputs[3]);
+            mux(inputs[5], lo, hi)
+        }
+        GateKind::Dreg | GateKind::Memory => Logic::X,
+    }
+}
+
+// the input terminals in the order evaluate() takes them.
+fn input_terminals(dev: &Device) -> Vec<String> {
+    let names: Vec<&str> = match dev.kind {
+        GateKind::Tristate => vec!["A", "E"],
+        GateKind::Buffer | GateKind::Inverter => vec!["A"],
+        GateKind::Mux2 => vec!["D0", "D1", "S"],
+        GateKind::Mux4 => vec!["D0", "D1", "D2", "D3", "S[0]", "S[1]"],
+        _ => {
+            let n = dev.module.chars().last().and_then(|c| c.to_digit(10)).unwrap_or(2) as usize;
+            ["A", "B", "C", "D"][..n.min(4)].to_vec()
+        }
+    };
+    names.into_iter().map(|n| n.to_string()).collect()
+}
+
+fn output_terminal(kind: GateKind) -> &'static str {
+    match kind {
+        GateKind::Mux2 | GateKind::Mux4 => "Y",
+        GateKind::Dreg => "Q",
+        _ => "Z",
+    }
+}
+
+// a memory port's terminal bits as memory_terminals names them, most
+// significant first.
+fn port_bits(name: &str, width: usize) -> Vec<String> {
+    if width == 1 { vec![name.to_string()] } else { (0..width).rev().map(|i| format!("{}[{}]", name, i)).collect() }
+}
+
+// per port: address bits, OE, WE, CLK, then the data bits, which are read
+// for writes and driven by reads.
+fn memory_inputs(mem: &Memory) -> Vec<String> {
+    let mut names = vec![];
+    for p in 0..mem.nports {
+        names.extend(port_bits(&format!("A{}", p), mem.naddr as usize));
+        names.push(format!("OE{}", p));
+        names.push(format!("WE{}", p));
+        names.push(format!("CLK{}", p));
+        names.extend(port_bits(&format!("D{}", p), mem.ndata as usize));
+    }
+    names
+}
+
+fn memory_outputs(mem: &Memory) -> Vec<String> {
+    (0..mem.nports).flat_map(|p| port_bits(&format!("D{}", p), mem.ndata as usize)).collect()
+}
+
+fn memory_state(mem: &Memory) -> E<MemState> {
+    let (naddr, ndata) = (mem.naddr as usize, mem.ndata as usize);
+    if naddr > 20 {
+        return bailfmt!("Memory with {} address bits is too big to simulate", naddr);
+    }
+    let contents = mem.words()?;
+    let words = (0..1usize << naddr).map(|a| {
+                                        let w = contents.get(a).cloned().unwrap_or(0);
+                                        (0..ndata).map(|i| Logic::from_bool(i < 64 && (w >> i) & 1 == 1)).collect()
+                                    })
+                                    .collect();
+    Ok(MemState { naddr, ndata, words })
+}
+
+// an address read off the inputs, None when any bit isn't a 0 or 1.
+fn address(bits: &[Logic]) -> Option<usize> {
+    let mut a = 0;
+    for b in bits {
+        a = (a << 1) | b.to_bool()? as usize;
+    }
+    Some(a)
+}
+
+fn rising(old: Logic, new: Logic) -> bool {
+    old == Logic::Zero && new == Logic::One
+}
+
+// an edge that may or may not have been a rising one.
+fn maybe_rising(old: Logic, new: Logic) -> bool {
+    old != new && old != Logic::One && new != Logic::Zero && !rising(old, new)
+}
+
+// every net change after tracing started, for waveform output.
+#[derive(Debug, PartialEq)]
+pub struct Trace {
+    pub start: u64,
+    pub initial: Vec<Logic>,
+    pub changes: Vec<(u64, usize, Logic)>,
+}
+
+pub struct Simulator {
+    nets: Vec<String>,
+    index: HashMap<String, usize>,
+    // the top module's bit names, lower cased, and the net each is on.
+    aliases: HashMap<String, usize>,
+    values: Vec<Logic>,
+    // everything that drives a net has a slot, (net, value), and the net
+    // takes the resolution of its slots.
+    slots: Vec<(usize, Logic)>,
+    net_slots: Vec<Vec<usize>>,
+    // the slots the test bench drives through, made on first use.
+    external: HashMap<usize, usize>,
+    devices: Vec<SimDevice>,
+    fanout: Vec<Vec<usize>>,
+    delays: HashMap<GateKind, u64>,
+    // (time, order scheduled, slot, value)
+    queue: BinaryHeap<Reverse<(u64, u64, usize, Logic)>>,
+    scheduled: u64,
+    now: u64,
+    trace: Option<Trace>,
+    setup: u64,
+    hold: u64,
+    violations: Vec<Violation>,
+}
+
+impl Simulator {
+    pub fn new(netlist: &Netlist) -> E<Simulator> {
+        let mut nets = vec![];
+        let mut index = HashMap::new();
+        let mut values = vec![];
+        for net in &netlist.nets {
+            index.insert(net.name.clone(), nets.len());
+            nets.push(net.name.clone());
+            values.push(match net.kind {
+                NetKind::TieHigh => Logic::One,
+                NetKind::TieLow => Logic::Zero,
+                _ => Logic::X,
+            });
+        }
+        let aliases = netlist.aliases.iter().map(|(bit, net)| (bit.clone(), index[net])).collect();
+
+        // tie nets are driven by a constant.
+        let mut slots = vec![];
+        let mut net_slots = vec![vec![]; nets.len()];
+        for (i, v) in values.iter().enumerate() {
+            if *v != Logic::X {
+                net_slots[i].push(slots.len());
+                slots.push((i, *v));
+            }
+        }
+
+        let mut devices = vec![];
+        let mut fanout = vec![vec![]; nets.len()];
+        for dev in &netlist.devices {
+            let net = |t: &String| dev.net(t).and_then(|n| index.get(n).cloned());
+            let (inputs, outputs, state) = match (dev.kind, &dev.memory) {
+                (GateKind::Memory, Some(mem)) => (memory_inputs(mem), memory_outputs(mem), State::Memory(memory_state(mem)?)),
+                (GateKind::Memory, None) => return bailfmt!("Memory {} has no contents", dev.name),
+                (GateKind::Dreg, _) => (vec!["D".to_string(), "CLK".to_string()],
+                                        vec!["Q".to_string()],
+                                        State::Dreg { d_changed: None, edge: None }),
+                (kind, _) => (input_terminals(dev), vec![output_terminal(kind).to_string()], State::Gate),
+            };
+            let inputs: Vec<Option<usize>> = inputs.iter().map(net).collect();
+            for i in inputs.iter().flatten() {
+                if !fanout[*i].contains(&devices.len()) {
+                    fanout[*i].push(devices.len());
+                }
+            }
+            let mut output_slots = vec![];
+            for o in outputs.iter().map(net) {
+                output_slots.push(o.map(|o| {
+                                       net_slots[o].push(slots.len());
+                                       slots.push((o, Logic::Z));
+                                       slots.len() - 1
+                                   }));
+            }
+            devices.push(SimDevice { name: dev.name.clone(),
+                                     kind: dev.kind,
+                                     inputs,
+                                     driving: vec![Logic::Z; outputs.len()],
+                                     outputs: output_slots,
+                                     state });
+        }
+
+        let delays = devices.iter().map(|d| (d.kind, default_delay(d.kind).femtoseconds())).collect();
+        let mut sim = Simulator { nets,
+                                  index,
+                                  aliases,
+                                  values,
+                                  slots,
+                                  net_slots,
+                                  external: HashMap::new(),
+                                  devices,
+                                  fanout,
+                                  delays,
+                                  queue: BinaryHeap::new(),
+                                  scheduled: 0,
+                                  now: 0,
+                                  trace: None,
+                                  setup: DEFAULT_SETUP.femtoseconds(),
+                                  hold: DEFAULT_HOLD.femtoseconds(),
+                                  violations: vec![] };
+        // every device looks at its inputs once so constants propagate.
+        for d in 0..sim.devices.len() {
+            sim.evaluate_device(d, None);
+        }
+        Ok(sim)
+    }
+
+    pub fn set_delay(&mut self, kind: GateKind, delay: Duration) {
+        self.delays.insert(kind, delay.femtoseconds());
+    }
+
+    pub fn set_register_timing(&mut self, setup: Duration, hold: Duration) {
+        self.setup = setup.femtoseconds();
+        self.hold = hold.femtoseconds();
+    }
+
+    // setup and hold violations seen so far.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    // the current time in femtoseconds.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn nets(&self) -> &[String] {
+        &self.nets
+    }
+
+    // start recording net changes from now on.
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Trace { start: self.now, initial: self.values.clone(), changes: vec![] });
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    // the net a bit name is on, a top level bit name or an inner net's path.
+    pub fn net_index(&self, bit: &str) -> E<usize> {
+        match self.aliases.get(&name_key(bit)).or_else(|| self.index.get(bit)) {
+            Some(i) => Ok(*i),
+            None => bailfmt!("No net for signal: {}", bit),
+        }
+    }
+
+    fn schedule(&mut self, time: u64, slot: usize, value: Logic) {
+        self.queue.push(Reverse((time, self.scheduled, slot, value)));
+        self.scheduled += 1;
+    }
+
+    fn input(&self, i: Option<usize>) -> Logic {
+        i.map(|i| self.values[i]).unwrap_or(Logic::Z)
+    }
+
+    // put values on a device's outputs after its delay, outputs that already
+    // have that value coming are left alone.
+    fn drive_outputs(&mut self, d: usize, values: Vec<Logic>) {
+        let delay = self.delays.get(&self.devices[d].kind).cloned().unwrap_or(0);
+        for (o, value) in values.into_iter().enumerate() {
+            if self.devices[d].driving[o] == value {
+                continue;
+            }
+            self.devices[d].driving[o] = value;
+            if let Some(slot) = self.devices[d].outputs[o] {
+                self.schedule(self.now + delay, slot, value);
+            }
+        }
+    }
+
+    fn violation(&mut self, d: usize, kind: ViolationKind) {
+        self.violations.push(Violation { time: self.now, device: self.devices[d].name.clone(), kind });
+    }
+
+    // look at a device's inputs again, `changed` is the net that moved and
+    // its old value, None when the device is first set up.
+    fn evaluate_device(&mut self, d: usize, changed: Option<(usize, Logic)>) {
+        let inputs: Vec<Logic> = self.devices[d].inputs.iter().map(|i| self.input(*i)).collect();
+        match self.devices[d].kind {
+            GateKind::Dreg => self.evaluate_dreg(d, &inputs, changed),
+            GateKind::Memory => self.evaluate_memory(d, &inputs, changed),
+            kind => self.drive_outputs(d, vec![evaluate(kind, &inputs)]),
+        }
+    }
+
+    fn evaluate_dreg(&mut self, d: usize, inputs: &[Logic], changed: Option<(usize, Logic)>) {
+        let (d_net, clk_net) = (self.devices[d].inputs[0], self.devices[d].inputs[1]);
+        let (d_changed, edge) = match self.devices[d].state {
+            State::Dreg { d_changed, edge } => (d_changed, edge),
+            _ => return,
+        };
+        let (value, clk) = (inputs[0].strong(), inputs[1]);
+        let (net, old) = match changed {
+            Some(c) => c,
+            None => return,
+        };
+
+        if Some(net) == clk_net && rising(old, clk) {
+            self.devices[d].state = State::Dreg { d_changed, edge: Some(self.now) };
+            if d_changed.map(|t| self.now - t < self.setup).unwrap_or(false) {
+                self.violation(d, ViolationKind::Setup);
+                self.drive_outputs(d, vec![Logic::X]);
+            } else {
+                self.drive_outputs(d, vec![value]);
+            }
+        } else if Some(net) == clk_net && maybe_rising(old, clk) {
+            // an unknown clock keeps Q only when D already matches it.
+            if self.devices[d].driving[0] != value {
+                self.drive_outputs(d, vec![Logic::X]);
+            }
+        } else if Some(net) == d_net {
+            self.devices[d].state = State::Dreg { d_changed: Some(self.now), edge };
+            if edge.map(|t| self.now - t < self.hold).unwrap_or(false) {
+                self.violation(d, ViolationKind::Hold);
+                self.drive_outputs(d, vec![Logic::X]);
+            }
+        }
+    }
+
+    fn evaluate_memory(&mut self, d: usize, inputs: &[Logic], changed: Option<(usize, Logic)>) {
+        let mut mem = match std::mem::replace(&mut self.devices[d].state, State::Gate) {
+            State::Memory(mem) => mem,
+            other => {
+                self.devices[d].state = other;
+                return;
+            }
+        };
+        let width = mem.naddr + 3 + mem.ndata;
+        let nports = inputs.len() / width;
+
+        for p in 0..nports {
+            let port = &inputs[p * width..(p + 1) * width];
+            let clk_net = self.devices[d].inputs[p * width + mem.naddr + 2];
+            let old = match changed {
+                Some((net, old)) if Some(net) == clk_net => old,
+                _ => continue,
+            };
+            // only a clean edge with WE high writes, jade leaves the contents
+            // alone otherwise. Data bits nothing drives keep what was there.
+            let (we, clk) = (port[mem.naddr + 1], port[mem.naddr + 2]);
+            if !rising(old, clk) || we != Logic::One {
+                continue;
+            }
+            let data: Vec<Logic> = port[mem.naddr + 3..].iter().rev().cloned().collect();
+            let write = |word: &mut Vec<Logic>, unknown: bool| {
+                for (bit, v) in word.iter_mut().zip(data.iter()) {
+                    if *v != Logic::Z {
+                        *bit = if unknown { Logic::X } else { *v };
+                    }
+                }
+            };
+            match address(&port[..mem.naddr]) {
+                Some(a) => write(&mut mem.words[a], false),
+                None => {
+                    for w in mem.words.iter_mut() {
+                        write(w, true);
+                    }
+                }
+            }
+        }
+
+        // reads put the addressed word on D while OE is high.
+        let mut outputs = vec![];
+        for p in 0..nports {
+            let port = &inputs[p * width..(p + 1) * width];
+            let word: Vec<Logic> = match (port[mem.naddr], address(&port[..mem.naddr])) {
+                (Logic::Zero, _) => vec![Logic::Z; mem.ndata],
+                (Logic::One, Some(a)) => mem.words[a].iter().rev().cloned().collect(),
+                _ => vec![Logic::X; mem.ndata],
+            };
+            outputs.extend(word);
+        }
+        self.devices[d].state = State::Memory(mem);
+        self.drive_outputs(d, outputs);
+    }
+
+    // drive a bit from outside the circuit, effective now. The drive stays
+    // until it's changed or released.
+    pub fn drive(&mut self, bit: &str, value: Logic) -> E<()> {
+        let net = self.net_index(bit)?;
+        let slot = match self.external.get(&net) {
+            Some(slot) => *slot,
+            None => {
+                self.slots.push((net, Logic::Z));
+                self.net_slots[net].push(self.slots.len() - 1);
+                self.external.insert(net, self.slots.len() - 1);
+                self.slots.len() - 1
+            }
+        };
+        self.schedule(self.now, slot, value);
+        Ok(())
+    }
+
+    pub fn release(&mut self, bit: &str) -> E<()> {
+        self.drive(bit, Logic::Z)
+    }
+
+    pub fn sample(&self, bit: &str) -> E<Logic> {
+        Ok(self.values[self.net_index(bit)?])
+    }
+
+    pub fn run_for(&mut self, d: Duration) -> E<()> {
+        let until = self.now + d.femtoseconds();
+        self.run_until(until)
+    }
+
+    // process every event up to and including `until`, then stop there.
+    pub fn run_until(&mut self, until: u64) -> E<()> {
+        let mut deltas = 0;
+        while let Some(Reverse((time, _, slot, value))) = self.queue.peek().cloned() {
+            if time > until {
+                break;
+            }
+            self.queue.pop();
+            if time == self.now {
+                deltas += 1;
+                if deltas > MAX_DELTAS {
+                    return bailfmt!("Circuit doesn't settle at {} fs", self.now);
+                }
+            } else {
+                deltas = 0;
+            }
+            self.now = time;
+            let net = self.slots[slot].0;
+            self.slots[slot].1 = value;
+            let value = resolve(self.net_slots[net].iter().map(|s| self.slots[*s].1));
+            if self.values[net] == value {
+                continue;
+            }
+            let old = self.values[net];
+            self.values[net] = value;
+            if let Some(trace) = &mut self.trace {
+                trace.changes.push((time, net, value));
+            }
+            for d in self.fanout[net].clone() {
+                self.evaluate_device(d, Some((net, old)));
+            }
+        }
+        self.now = self.now.max(until);
+        Ok(())
+    }
+
+    // the devices driving a net other than Z right now, for messages.
+    pub fn drivers(&self, bit: &str) -> E<Vec<&str>> {
+        let net = self.net_index(bit)?;
+        let active = |s: &Option<usize>| s.map(|s| self.slots[s].0 == net && self.slots[s].1 != Logic::Z).unwrap_or(false);
+        Ok(self.devices.iter().filter(|d| d.outputs.iter().any(active)).map(|d| d.name.as_str()).collect())
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    #[test]
+    fn gates() {
+        use Logic::*;
+        assert_eq!(evaluate(GateKind::And, &[One, One, One]), One);
+        assert_eq!(evaluate(GateKind::And, &[Zero, X]), Zero);
+        assert_eq!(evaluate(GateKind::Or, &[Z, One]), One);
+        assert_eq!(evaluate(GateKind::Nor, &[Z, Zero]), X);
+        assert_eq!(evaluate(GateKind::Xnor, &[One, One]), One);
+        assert_eq!(evaluate(GateKind::Tristate, &[One, Zero]), Z);
+        assert_eq!(evaluate(GateKind::Mux2, &[One, One, X]), One);
+        assert_eq!(evaluate(GateKind::Mux4, &[Zero, Zero, One, Zero, Zero, One]), One);
+    }
+
+    #[test]
+    fn and2() {
+        let p = project("use-and2.json");
+        let n = Netlist::flatten(&p, "/user/AND2", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        assert_eq!(sim.sample("out").unwrap(), Logic::X);
+        sim.drive("in1", Logic::One).unwrap();
+        sim.drive("in2", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("out").unwrap(), Logic::Zero);
+        sim.drive("in2", Logic::One).unwrap();
+        sim.run_for(Duration::PicoSecond(49.0)).unwrap();
+        assert_eq!(sim.sample("out").unwrap(), Logic::Zero);
+        sim.run_for(Duration::PicoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("out").unwrap(), Logic::One);
+        assert_eq!(sim.now(), 1_050_000);
+        assert!(sim.drive("nope", Logic::One).is_err());
+    }
+
+    #[test]
+    fn ring() {
+        // a nand fed back on itself oscillates with twice its delay once
+        // it's enabled.
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Ring": {"schematic": [["/gates/nand2", [0, 0, 0]],
+                                         ["wire", [0, 0, 0, -8, 0], {"signal": "EN"}],
+                                         ["wire", [0, 16, 0, -8, 0], {"signal": "Q"}],
+                                         ["wire", [48, 8, 0, 8, 0], {"signal": "Q"}]]}}]"#)
+                    .unwrap();
+        let n = Netlist::flatten(&p, "/user/Ring", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        sim.drive("EN", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::One);
+        sim.drive("EN", Logic::One).unwrap();
+        sim.run_for(Duration::PicoSecond(30.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::Zero);
+        sim.run_for(Duration::PicoSecond(30.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::One);
+        assert_eq!(sim.drivers("Q").unwrap(), vec!["nand2_0"]);
+
+        sim.set_delay(GateKind::Nand, Duration::PicoSecond(0.0));
+        assert!(sim.run_for(Duration::PicoSecond(100.0)).is_err());
+    }
+
+    #[test]
+    fn resolution() {
+        use Logic::*;
+        assert_eq!(resolve(vec![].into_iter()), Z);
+        assert_eq!(resolve(vec![Z, One, Z].into_iter()), One);
+        assert_eq!(resolve(vec![One, One].into_iter()), One);
+        assert_eq!(resolve(vec![Zero, One].into_iter()), X);
+        assert_eq!(resolve(vec![Z, X].into_iter()), X);
+    }
+
+    #[test]
+    fn tristate_bus() {
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Bus": {"schematic": [["/gates/tristate", [0, 0, 0]],
+                                        ["/gates/tristate", [0, 32, 0]],
+                                        ["wire", [0, 0, 0, -8, 0], {"signal": "A"}],
+                                        ["wire", [16, 16, 0, 0, 8], {"signal": "EA"}],
+                                        ["wire", [0, 32, 0, -8, 0], {"signal": "B"}],
+                                        ["wire", [16, 48, 0, 0, 8], {"signal": "EB"}],
+                                        ["wire", [32, 0, 0, 8, 0], {"signal": "BUS"}],
+                                        ["wire", [32, 32, 0, 8, 0], {"signal": "BUS"}]]}}]"#)
+                    .unwrap();
+        let n = Netlist::flatten(&p, "/user/Bus", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        let ns = Duration::NanoSecond(1.0);
+        use Logic::*;
+        for (bit, v) in &[("A", One), ("B", Zero), ("EA", Zero), ("EB", Zero)] {
+            sim.drive(bit, *v).unwrap();
+        }
+        sim.run_for(ns).unwrap();
+        assert_eq!(sim.sample("BUS").unwrap(), Z);
+        sim.drive("EA", One).unwrap();
+        sim.run_for(ns).unwrap();
+        assert_eq!(sim.sample("BUS").unwrap(), One);
+        sim.drive("EB", One).unwrap();
+        sim.run_for(ns).unwrap();
+        assert_eq!(sim.sample("BUS").unwrap(), X);
+        assert_eq!(sim.drivers("BUS").unwrap().len(), 2);
+        sim.drive("EA", Zero).unwrap();
+        sim.run_for(ns).unwrap();
+        assert_eq!(sim.sample("BUS").unwrap(), Zero);
+
+        // the test bench is one more driver, released it gives way again.
+        sim.drive("BUS", One).unwrap();
+        sim.run_for(ns).unwrap();
+        assert_eq!(sim.sample("BUS").unwrap(), X);
+        sim.release("BUS").unwrap();
+        sim.run_for(ns).unwrap();
+        assert_eq!(sim.sample("BUS").unwrap(), Zero);
+    }
+
+    fn dreg() -> Simulator {
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Reg": {"schematic": [["/gates/dreg", [0, 0, 0]],
+                                        ["wire", [0, 0, 0, -8, 0], {"signal": "D"}],
+                                        ["wire", [0, 16, 0, -8, 0], {"signal": "clk"}],
+                                        ["wire", [40, 0, 0, 8, 0], {"signal": "Q"}]]}}]"#)
+                    .unwrap();
+        let n = Netlist::flatten(&p, "/user/Reg", &NetConfig::new()).unwrap();
+        Simulator::new(&n).unwrap()
+    }
+
+    #[test]
+    fn registers() {
+        let mut sim = dreg();
+        sim.drive("CLK", Logic::Zero).unwrap();
+        sim.drive("D", Logic::One).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::X);
+        sim.drive("CLK", Logic::One).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::One);
+        // D changing while the clock is high only matters at the next edge.
+        sim.drive("D", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::One);
+        assert!(sim.violations().is_empty());
+
+        // D moves 10ps before the edge.
+        sim.drive("CLK", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        sim.drive("D", Logic::One).unwrap();
+        sim.run_for(Duration::PicoSecond(10.0)).unwrap();
+        sim.drive("CLK", Logic::One).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::X);
+        assert_eq!(sim.violations()[0].kind, ViolationKind::Setup);
+        assert_eq!(sim.violations()[0].device, "dreg_0");
+
+        // and 10ps after one.
+        let mut sim = dreg();
+        sim.drive("CLK", Logic::Zero).unwrap();
+        sim.drive("D", Logic::One).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        sim.drive("CLK", Logic::One).unwrap();
+        sim.run_for(Duration::PicoSecond(10.0)).unwrap();
+        sim.drive("D", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::X);
+        assert_eq!(sim.violations()[0].kind, ViolationKind::Hold);
+    }
+
+    #[test]
+    fn latch() {
+        // a mux feeding itself back holds its value while G is low.
+        let p = Project::from_str(r#"["Jade", {
+            "/user/Latch": {"schematic": [["/gates/mux2", [0, 0, 0]],
+                                          ["wire", [0, 0, 0, -8, 0], {"signal": "D"}],
+                                          ["wire", [0, 16, 0, -8, 0], {"signal": "Q"}],
+                                          ["wire", [8, 32, 0, 0, 8], {"signal": "G"}],
+                                          ["wire", [16, 8, 0, 8, 0], {"signal": "Q"}]]}}]"#)
+                    .unwrap();
+        let n = Netlist::flatten(&p, "/user/Latch", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        sim.drive("G", Logic::One).unwrap();
+        sim.drive("D", Logic::One).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::One);
+        sim.drive("G", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        sim.drive("D", Logic::Zero).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+        assert_eq!(sim.sample("Q").unwrap(), Logic::One);
+    }
+
+    #[test]
+    fn memory() {
+        let p = project("MemUnit2.json");
+        let n = Netlist::flatten(&p, "/user/MemUnit2", &NetConfig::new()).unwrap();
+        assert_eq!(n.devices.iter().filter(|d| d.module == MEMORY).count(), 1);
+        let mut sim = Simulator::new(&n).unwrap();
+        let ns = Duration::NanoSecond(1.0);
+        let set = |sim: &mut Simulator, bits: &[(&str, Logic)]| {
+            for (bit, v) in bits {
+                sim.drive(bit, *v).unwrap();
+            }
+            sim.run_for(ns).unwrap();
+        };
+        use Logic::*;
+        set(&mut sim, &[("clk", Zero), ("we", Zero), ("oe", One), ("addr[1]", One), ("addr[0]", Zero)]);
+        assert_eq!((sim.sample("d[1]").unwrap(), sim.sample("d[0]").unwrap()), (One, Zero));
+
+        // write 01 to address 2, then read it back.
+        set(&mut sim, &[("oe", Zero)]);
+        set(&mut sim, &[("we", One), ("d[1]", Zero), ("d[0]", One)]);
+        set(&mut sim, &[("clk", One)]);
+        set(&mut sim, &[("clk", Zero), ("we", Zero), ("d[1]", Z), ("d[0]", Z)]);
+        assert_eq!(sim.sample("d[1]").unwrap(), Z);
+        set(&mut sim, &[("oe", One)]);
+        assert_eq!((sim.sample("d[1]").unwrap(), sim.sample("d[0]").unwrap()), (Zero, One));
+        set(&mut sim, &[("addr[0]", X)]);
+        assert_eq!(sim.sample("d[0]").unwrap(), X);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cycle::*;
+use crate::gates::*;
+use crate::netlist::*;
+use crate::types::*;
+use crate::vhdl::entity_name;
+
+// SPICE decks for .mode device tests, runnable with ngspice:
+//
+//   ngspice -b deck.cir
+//
+// Each jade module becomes a .subckt. The /gates/* library has no
+// transistor level schematics here, so the combinational gates are
+// behavioural B sources switching at Vdd/2, tie high is the global vdd node
+// and tie low is ground. Test vectors become PWL sources and every sampled
+// bit a .measure whose value is checked against the thresholds.
+//
+// Only combinational gates have models. A dreg, a tristate or a memory
+// anywhere in the design is an error, "No SPICE model for ..", rather than a
+// deck that silently simulates something else.
+
+// minimum edge time for the PWL sources, shortened to fit short trans.
+const EDGE: f64 = 10e-12;
+
+// node names keep letters, digits and underscores, A[3] -> A_3
+pub fn spice_node(name: &str) -> String {
+    let node: String = name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' }).collect();
+    node.trim_end_matches('_').to_string()
+}
+
+pub fn subckt_name(module: &str) -> String {
+    entity_name(module).trim_matches('\\').to_string()
+}
+
+// the port bits of a module in order, each bit once.
+fn port_bits(m: &ModuleNetlist) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+    let mut bits = vec![];
+    for port in &m.ports {
+        let names = port.sig.bits().into_iter().filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None });
+        for (bit, net) in names.zip(port.nets.iter()) {
+            if seen.insert(bit.clone()) {
+                bits.push((bit, net.clone()));
+            }
+        }
+    }
+    bits
+}
+
+fn gate_source(label: &str, kind: GateKind, inst: &Instance, node: &dyn Fn(&str) -> String) -> E<String> {
+    let hi = |t: &str| format!("u(V({})-V(vdd)/2)", node(t));
+    let ins: Vec<String> = ["A", "B", "C", "D"].iter()
+                                               .filter(|t| inst.connections.iter().any(|(c, _)| c == *t))
+                                               .map(|t| hi(t))
+                                               .collect();
+    let and = || ins.join("*");
+    let or = || format!("(1-{})", ins.iter().map(|i| format!("(1-{})", i)).collect::<Vec<String>>().join("*"));
+    let xor = || {
+        ins[1..].iter().fold(ins[0].clone(), |acc, i| format!("(({})+{}-2*({})*{})", acc, i, acc, i))
+    };
+    let (out, expr) = match kind {
+        GateKind::And => ("Z", and()),
+        GateKind::Or => ("Z", or()),
+        GateKind::Xor => ("Z", xor()),
+        GateKind::Nand => ("Z", format!("(1-{})", and())),
+        GateKind::Nor => ("Z", format!("(1-{})", or())),
+        GateKind::Xnor => ("Z", format!("(1-{})", xor())),
+        GateKind::Buffer => ("Z", hi("A")),
+        GateKind::Inverter => ("Z", format!("(1-{})", hi("A"))),
+        GateKind::Mux2 => ("Y", format!("({}*{}+(1-{})*{})", hi("S"), hi("D1"), hi("S"), hi("D0"))),
+        GateKind::Mux4 => {
+            let (s1, s0) = (hi("S[1]"), hi("S[0]"));
+            ("Y",
+             format!("({s1}*({s0}*{d3}+(1-{s0})*{d2})+(1-{s1})*({s0}*{d1}+(1-{s0})*{d0}))",
+                     s1 = s1,
+                     s0 = s0,
+                     d3 = hi("D3"),
+                     d2 = hi("D2"),
+                     d1 = hi("D1"),
+                     d0 = hi("D0")))
+        }
+        _ => return bailfmt!("No SPICE model for {} ({})", inst.module, label),
+    };
+    Ok(format!("B{} {} 0 V=V(vdd)*{}\n", label, node(out), expr))
+}
+
+pub fn module_to_spice(m: &ModuleNetlist, netlists: &HashMap<String, ModuleNetlist>) -> E<String> {
+    let node = |net: &str| -> String {
+        match m.net_kind(net) {
+            Some(NetKind::TieHigh) => "vdd".to_string(),
+            Some(NetKind::TieLow) => "0".to_string(),
+            _ => spice_node(net),
+        }
+    };
+
+    let mut out = String::new();
+    let ports = port_bits(m);
+    let mut header: Vec<String> = vec![];
+    let mut shorts = vec![];
+    for (bit, net) in &ports {
+        // a port bit on another port's net, or on a constant, is joined to
+        // it by a zero volt source.
+        let n = spice_node(bit);
+        let home = node(net);
+        if home != n {
+            shorts.push((n.clone(), home));
+        }
+        header.push(n);
+    }
+    out.push_str(&format!("* {}\n.subckt {} {}\n", m.name, subckt_name(&m.name), header.join(" ")));
+    for (k, (from, to)) in shorts.iter().enumerate() {
+        out.push_str(&format!("Vshort{} {} {} DC 0\n", k, from, to));
+    }
+
+    let mut floating = 0;
+    for inst in &m.instances {
+        let label = spice_node(&inst.name);
+        let conn = |t: &str| -> String {
+            match inst.connections.iter().find(|(c, _)| c == t) {
+                Some((_, net)) => node(net),
+                None => format!("nc_{}_{}", label, spice_node(t)),
+            }
+        };
+
+        if inst.memory.is_some() {
+            return bailfmt!("No SPICE model for memory {} in {}", inst.name, m.name);
+        }
+        if let Some(kind) = gate_kind(&inst.module) {
+            out.push_str(&gate_source(&label, kind, inst, &conn)?);
+            continue;
+        }
+
+        let child = match netlists.get(&inst.module) {
+            Some(child) => child,
+            None => return bailfmt!("No netlist for submodule {}", inst.module),
+        };
+        let mut nodes = vec![];
+        for (bit, _) in port_bits(child) {
+            let child_net = child.net(&bit);
+            match inst.connections.iter().find(|(t, _)| child_net.is_some() && child.net(t) == child_net) {
+                Some((_, net)) => nodes.push(node(net)),
+                None => {
+                    floating += 1;
+                    nodes.push(format!("nc_{}_{}", label, floating));
+                }
+            }
+        }
+        out.push_str(&format!("X{} {} {}\n", label, nodes.join(" "), subckt_name(&inst.module)));
+    }
+    out.push_str(&format!(".ends {}\n", subckt_name(&m.name)));
+    Ok(out)
+}
+
+// the subcircuits for top and everything below it, children first.
+pub fn project_to_spice(project: &Project, top: &str, config: &NetConfig) -> E<String> {
+    let (order, netlists) = hierarchy(project, top, config)?;
+    let mut globals = vec!["vdd".to_string()];
+    for name in &order {
+        for net in &netlists[name].nets {
+            let node = spice_node(&net.name);
+            if net.kind == NetKind::Global && !globals.contains(&node) {
+                globals.push(node);
+            }
+        }
+    }
+
+    let mut out = format!(".global {}\n\n", globals.join(" "));
+    for name in &order {
+        out.push_str(&module_to_spice(&netlists[name], &netlists)?);
+        out.push_str("\n");
+    }
+    Ok(out)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpiceCheck {
+    pub measure: String,
+    pub line: usize,
+    pub bit: String,
+    pub expect: BinVal,
+}
+
+impl SpiceCheck {
+    // jade reads a sampled voltage as low at or below Vil and high at or
+    // above Vih, anything between is an X.
+    pub fn passes(&self, volts: f64, th: &Thresholds) -> bool {
+        match self.expect {
+            L => volts <= th.vil,
+            H => volts >= th.vih,
+            X => volts > th.vil && volts < th.vih,
+            Z | DontCare => true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpiceDeck {
+    pub deck: String,
+    pub checks: Vec<SpiceCheck>,
+}
+
+pub fn spice_deck(project: &Project, top: &str, config: &NetConfig, test: &ModTest) -> E<SpiceDeck> {
+    let m = ModuleNetlist::build(project, top, config)?;
+    let ports = port_bits(&m);
+    let cycles = test.cycles()?;
+
+    let target = |bit: &str| -> E<String> {
+        if ports.iter().any(|(b, _)| b == bit) {
+            return Ok(spice_node(bit));
+        }
+        match m.net(bit).and_then(|net| ports.iter().find(|(_, n)| n == net)) {
+            Some((b, _)) => Ok(spice_node(b)),
+            None => bailfmt!("Test signal {} is not a port of {}", bit, top),
+        }
+    };
+
+    let vdd = test.power.first().map(|p| p.volts).unwrap_or(1.0);
+    let level = |v: bool| if v { vdd } else { 0.0 };
+
+    // walk the schedule, collecting a PWL point list per driven node.
+    let shortest = cycles.iter()
+                         .flat_map(|c| c.steps.iter())
+                         .filter_map(|s| if let Step::Wait(d) = s { Some(d.seconds()) } else { None })
+                         .filter(|t| *t > 0.0)
+                         .fold(f64::MAX, f64::min);
+    let edge = EDGE.min(shortest / 10.0);
+    let mut sources: Vec<(String, Vec<(f64, f64)>)> = vec![];
+    let mut drive = |node: String, t: f64, volts: f64| {
+        let points = match sources.iter_mut().find(|(n, _)| *n == node) {
+            Some((_, points)) => points,
+            None => {
+                sources.push((node, vec![]));
+                &mut sources.last_mut().unwrap().1
+            }
+        };
+        match points.last() {
+            Some((_, last)) if *last == volts => {}
+            // driven again at the same time, before the last edge is done:
+            // the later value wins and the PWL times never go backwards.
+            Some(&(at, _)) if at >= t => points.last_mut().unwrap().1 = volts,
+            Some(&(_, last)) => {
+                points.push((t, last));
+                points.push((t + edge, volts));
+            }
+            None => points.push((0.0, volts)),
+        }
+    };
+
+    let mut checks = vec![];
+    let mut measures = vec![];
+    let mut t = 0.0;
+    for cycle in &cycles {
+        for step in &cycle.steps {
+            match step {
+                Step::Drive(_, values) => {
+                    for (bit, v) in values {
+                        match v {
+                            L => drive(target(bit)?, t, 0.0),
+                            H => drive(target(bit)?, t, vdd),
+                            // a voltage source can't float or go unknown,
+                            // the node keeps its last value.
+                            _ => {}
+                        }
+                    }
+                }
+                Step::Release(_, _) => {}
+                Step::Set(bit, v) => drive(target(bit)?, t, level(*v)),
+                Step::Wait(d) => t += d.seconds(),
+                Step::Check(_, values) => {
+                    for (bit, v) in values {
+                        if *v == DontCare {
+                            continue;
+                        }
+                        let measure = format!("l{}_{}", cycle.line, spice_node(bit)).to_lowercase();
+                        measures.push(format!(".measure tran {} FIND V({}) AT={:e}", measure, target(bit)?, t));
+                        checks.push(SpiceCheck { measure, line: cycle.line, bit: bit.clone(), expect: *v });
+                    }
+                }
+            }
+        }
+    }
+
+    let mut deck = format!("* device test deck for {}, generated by jade-parse\n", top);
+    deck.push_str(&project_to_spice(project, top, config)?);
+    deck.push_str(&format!("Vvdd vdd 0 DC {}\n", vdd));
+    for p in test.power.iter().skip(1) {
+        deck.push_str(&format!("V{} {} 0 DC {}\n", spice_node(&p.name), spice_node(&p.name).to_lowercase(), p.volts));
+    }
+    let nodes: Vec<String> = ports.iter().map(|(b, _)| spice_node(b)).collect();
+    deck.push_str(&format!("Xdut {} {}\n", nodes.join(" "), subckt_name(top)));
+    for (node, points) in &sources {
+        let pwl: Vec<String> = points.iter().map(|(t, v)| format!("{:e} {}", t, v)).collect();
+        deck.push_str(&format!("Vin_{} {} 0 PWL({})\n", node, node, pwl.join(" ")));
+    }
+    deck.push_str(&format!(".tran {:e} {:e}\n", edge, t));
+    for measure in &measures {
+        deck.push_str(measure);
+        deck.push_str("\n");
+    }
+    deck.push_str(".end\n");
+    Ok(SpiceDeck { deck, checks })
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    #[test]
+    fn nodes() {
+        assert_eq!(spice_node("A[3]"), "A_3");
+        assert_eq!(spice_node("Inst_0.out"), "Inst_0_out");
+        assert_eq!(subckt_name("/user/AND2"), "mod_user_AND2");
+    }
+
+    #[test]
+    fn subckts() {
+        let p = project("use-and2.json");
+        let got = project_to_spice(&p, "/user/UseAND2", &NetConfig::new()).unwrap();
+        assert!(got.contains(".subckt mod_user_AND2 in1 in2 out\nBand2_0 out 0 V=V(vdd)*u(V(in1)-V(vdd)/2)*u(V(in2)-V(vdd)/2)\n"));
+        assert!(got.contains("XAND2_0 _N1 _N2 _N3 mod_user_AND2\n"));
+        assert!(got.find(".subckt mod_user_AND2").unwrap() < got.find(".subckt mod_user_UseAND2").unwrap());
+    }
+
+    #[test]
+    fn deck1() {
+        let p = project("use-and2.json");
+        let src = ".power Vdd=1.8\n.group inputs in1 in2\n.group outputs out\n.mode device\n\
+                   .cycle assert inputs tran 99n sample outputs tran 1n\n01 L\n11 H\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = spice_deck(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(got.deck.contains("Vvdd vdd 0 DC 1.8\n"));
+        assert!(got.deck.contains("Xdut in1 in2 out mod_user_AND2\n"));
+        assert!(got.deck.contains("Vin_in1 in1 0 PWL(0e0 0 1e-7 0 "));
+        assert!(got.deck.contains(".measure tran l7_out FIND V(out) AT=1.99e-7\n"));
+        assert_eq!(got.checks.len(), 2);
+        let th = Thresholds { vol: 0.0, vil: 0.1, vih: 0.9, voh: 1.0, pos: Pos::default() };
+        assert!(got.checks[1].passes(1.8, &th));
+        assert!(!got.checks[0].passes(0.5, &th));
+
+        // in2 is asserted and then set at the same time, the set wins.
+        let src = ".group inputs in1 in2\n.group outputs out\n.mode device\n\
+                   .cycle assert inputs in2=0 tran 99n sample outputs tran 1n\n01 L\n11 L\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = spice_deck(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(got.deck.contains("Vin_in2 in2 0 PWL(0e0 0 1e-7 0 1.0001e-7 0)\n"));
+    }
+
+    #[test]
+    fn no_model() {
+        for (file, module, msg) in &[("MemUnit1.json", "/user/MemUnit1", "No SPICE model for memory Mem1 in /user/MemUnit1"),
+                                     ("LeReg1.json", "/user/LeReg1", "No SPICE model for /gates/dreg (dreg_0)"),
+                                     ("Tristate1.json", "/user/Tristate1", "No SPICE model for /gates/tristate (tristate_0)")]
+        {
+            match project_to_spice(&project(file), module, &NetConfig::new()) {
+                Err(b) => assert_eq!(b.msg, *msg),
+                Ok(_) => panic!("{} has no SPICE model", module),
+            }
+        }
+    }
+}
@@ -10,8 +10,8 @@ impl SubModule {
             return bailfmt!("SubModule::from_value got bad json value: {:?}", val);
         };
 
-        if arr.len() != 2 {
-            return bailfmt!("submodule expected an array of size 2, got: {:?}", arr);
+        if arr.len() != 2 && arr.len() != 3 {
+            return bailfmt!("submodule expected an array of size 2 or 3, got: {:?}", arr);
         }
 
         let name: String = match &arr[0] {
@@ -24,8 +24,17 @@ impl SubModule {
             _ => return bailfmt!("submodule expected module name as first item, got: {:?}", arr[0]),
         };
 
+        let instance_name = match arr.get(2) {
+            Some(Value::Object(o)) => match o.get("name") {
+                Some(Value::String(s)) if !s.is_empty() => Some(s.clone()),
+                _ => None,
+            },
+            Some(x) => return bailfmt!("submodule expected a properties object, got: {:?}", x),
+            None => None,
+        };
+
         match serde_json::from_value::<Coord3>(arr[1].clone()) {
-            Ok(coord3) => return Ok(SubModule { name, coord3 }),
+            Ok(coord3) => return Ok(SubModule { name, coord3, instance_name }),
             Err(msg) => return bailfmt!("submodule fails to decode location, with error: {:?}", msg),
         }
     }
@@ -56,6 +65,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn subModule4() {
+        let val = json!(["/user/AND2", [48, 0, 0], {"name": "OUT"}]);
+        let got = SubModule::from_value(&val).unwrap();
+        assert_eq!(got.instance_name, Some("OUT".to_string()));
+    }
+
     #[test]
     fn subModule3() {
         let val = json!(["/gates/xor2", [-64, 128, 1]]);
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use crate::gates::*;
+use crate::types::*;
+
+// SVG drawings of a module's schematic and icon, in jade's own coordinates
+// (y grows downwards, as in SVG), framed by a viewBox around what's drawn.
+// Submodules are drawn as their icons placed with the instance rotation,
+// /gates/* and memories, which have no icon in the json, as labelled boxes
+// around their terminals.
+
+const STYLE: &str = "line, path, polyline, polygon, rect, circle { stroke: black; stroke-width: 1; fill: none; }
+  text { font-family: sans-serif; font-size: 6px; }
+  .wire { stroke: #006400; }
+  .label { fill: #006400; }
+  .dot { fill: #006400; stroke: none; }
+  .port { fill: #e0e0ff; }
+  .terminal { stroke: #a00000; }
+  .builtin { fill: #f4f4f4; }";
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// where a part or icon is put: a rotation about its origin, then a move.
+#[derive(Debug, Clone, Copy)]
+struct Place {
+    x: i32,
+    y: i32,
+    r: Rot,
+}
+
+impl Place {
+    fn identity() -> Place {
+        Place { x: 0, y: 0, r: Rot0 }
+    }
+
+    fn of(c: &Coord3) -> Place {
+        Place { x: c.x, y: c.y, r: c.r }
+    }
+
+    fn apply(&self, x: i32, y: i32) -> (i32, i32) {
+        let (dx, dy) = self.r.transform(x, y);
+        (self.x + dx, self.y + dy)
+    }
+
+    // a part inside an icon is placed by the part's own coordinate first.
+    fn then(&self, inner: &Coord3) -> Place {
+        let (x, y) = self.apply(inner.x, inner.y);
+        Place { x, y, r: compose(inner.r, self.r) }
+    }
+
+    // text stays upright, only its anchor follows the rotation.
+    fn anchor(&self) -> &'static str {
+        match self.r.transform(1, 0) {
+            (x, _) if x < 0 => "end",
+            (x, _) if x > 0 => "start",
+            _ => "middle",
+        }
+    }
+}
+
+// the rotation doing `first` then `second`, found by where it sends two
+// unit vectors.
+fn compose(first: Rot, second: Rot) -> Rot {
+    let all = [Rot0, Rot270, Rot180, Rot90, FlipX, TransposeNeg, FlipY, TransposePos];
+    let (ax, ay) = first.transform(1, 0);
+    let (bx, by) = first.transform(0, 1);
+    let want = (second.transform(ax, ay), second.transform(bx, by));
+    *all.iter().find(|r| (r.transform(1, 0), r.transform(0, 1)) == want).unwrap()
+}
+
+struct Canvas {
+    elems: Vec<String>,
+    min: (i32, i32),
+    max: (i32, i32),
+}
+
+impl Canvas {
+    fn new() -> Canvas {
+        Canvas { elems: vec![], min: (i32::MAX, i32::MAX), max: (i32::MIN, i32::MIN) }
+    }
+
+    fn grow(&mut self, (x, y): (i32, i32)) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn line(&mut self, a: (i32, i32), b: (i32, i32), class: Option<&str>) {
+        self.grow(a);
+        self.grow(b);
+        let class = class.map(|c| format!(" class=\"{}\"", c)).unwrap_or_default();
+        self.elems.push(format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"{}/>", a.0, a.1, b.0, b.1, class));
+    }
+
+    fn polygon(&mut self, points: &[(i32, i32)], class: &str) {
+        for p in points {
+            self.grow(*p);
+        }
+        let pts: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+        self.elems.push(format!("<polygon points=\"{}\" class=\"{}\"/>", pts.join(" "), class));
+    }
+
+    fn circle(&mut self, c: (i32, i32), r: f64, class: Option<&str>) {
+        let ri = r.ceil() as i32;
+        self.grow((c.0 - ri, c.1 - ri));
+        self.grow((c.0 + ri, c.1 + ri));
+        let class = class.map(|c| format!(" class=\"{}\"", c)).unwrap_or_default();
+        self.elems.push(format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\"{}/>", c.0, c.1, r, class));
+    }
+
+    fn path(&mut self, d: String, points: &[(i32, i32)]) {
+        for p in points {
+            self.grow(*p);
+        }
+        self.elems.push(format!("<path d=\"{}\"/>", d));
+    }
+
+    fn text(&mut self, at: (i32, i32), text: &str, anchor: &str, size: Option<f64>, class: Option<&str>) {
+        self.grow(at);
+        let size = size.map(|s| format!(" font-size=\"{}\"", s)).unwrap_or_default();
+        let class = class.map(|c| format!(" class=\"{}\"", c)).unwrap_or_default();
+        self.elems.push(format!("<text x=\"{}\" y=\"{}\" text-anchor=\"{}\" dominant-baseline=\"middle\"{}{}>{}</text>",
+                                at.0,
+                                at.1,
+                                anchor,
+                                size,
+                                class,
+                                escape(text)));
+    }
+
+    fn finish(self) -> String {
+        let (min, max) = if self.elems.is_empty() { ((0, 0), (0, 0)) } else { (self.min, self.max) };
+        let margin = 8;
+        let (x, y) = (min.0 - margin, min.1 - margin);
+        let (w, h) = (max.0 - min.0 + 2 * margin, max.1 - min.1 + 2 * margin);
+        let mut out = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n",
+                              x,
+                              y,
+                              w,
+                              h,
+                              w * 2,
+                              h * 2);
+        out.push_str(&format!("<style>\n  {}\n</style>\n", STYLE));
+        for e in self.elems {
+            out.push_str(&e);
+            out.push_str("\n");
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+// "12pt sans-serif" -> 12
+fn font_size(font: &Option<String>) -> Option<f64> {
+    let font = font.as_ref()?;
+    let num: String = font.chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+    num.parse::<f64>().ok()
+}
+
+fn draw_terminal(canvas: &mut Canvas, at: Place, name: Option<&str>) {
+    canvas.line(at.apply(0, 0), at.apply(8, 0), Some("terminal"));
+    canvas.circle(at.apply(0, 0), 1.5, Some("terminal"));
+    if let Some(name) = name {
+        canvas.text(at.apply(10, 0), name, at.anchor(), Some(4.0), None);
+    }
+}
+
+fn draw_icon(canvas: &mut Canvas, icon: &Icon, at: Place, instance: Option<&str>, terminal_names: bool) {
+    for part in &icon.parts {
+        match part {
+            IconPart::Line(line) => {
+                let c = &line.coord;
+                let p = at.then(&Coord3 { x: c.x, y: c.y, r: c.r });
+                canvas.line(p.apply(0, 0), p.apply(c.dx, c.dy), None);
+            }
+            IconPart::Terminal(term) => {
+                let name = term.sig.to_string();
+                draw_terminal(canvas, at.then(&term.coord3), if terminal_names { Some(&name) } else { None });
+            }
+            IconPart::Text(text) => {
+                let p = at.then(&text.coord3);
+                canvas.text(p.apply(0, 0), &text.text, "start", font_size(&text.font), None);
+            }
+            IconPart::Circle(circle) => {
+                let c = Coord3 { x: circle.x as i32, y: circle.y as i32, r: circle.rot };
+                canvas.circle(at.then(&c).apply(0, 0), circle.radius, None);
+            }
+            IconPart::Arc(arc) => {
+                // a quadratic through the arc's middle point is close enough
+                // for icons.
+                let c = &arc.coord5;
+                let p = at.then(&Coord3 { x: c.x, y: c.y, r: c.r });
+                let (a, b, m) = (p.apply(0, 0), p.apply(c.dx, c.dy), p.apply(arc.cx, arc.cy));
+                let ctrl = (2 * m.0 - (a.0 + b.0) / 2, 2 * m.1 - (a.1 + b.1) / 2);
+                canvas.path(format!("M {} {} Q {} {} {} {}", a.0, a.1, ctrl.0, ctrl.1, b.0, b.1), &[a, b, m]);
+            }
+            IconPart::Box(bx) => {
+                let c = &bx.coord5;
+                let p = at.then(&Coord3 { x: c.x, y: c.y, r: c.r });
+                let corners = [p.apply(0, 0), p.apply(c.dx, 0), p.apply(c.dx, c.dy), p.apply(0, c.dy)];
+                canvas.polygon(&corners, "box");
+            }
+            IconPart::Property(prop) => {
+                let text = match instance {
+                    Some(name) => prop.format.replace("{name}", name),
+                    None => prop.format.clone(),
+                };
+                let p = at.then(&prop.coord3);
+                canvas.text(p.apply(0, 0), &text, "middle", Some(4.0), None);
+            }
+        }
+    }
+}
+
+// gates and memories are boxes around their terminals with the gate name.
+fn draw_builtin(canvas: &mut Canvas, label: &str, terms: &[GateTerminal], at: Place) {
+    let xs = || terms.iter().map(|t| t.x);
+    let ys = || terms.iter().map(|t| t.y);
+    let (x0, x1) = (xs().min().unwrap_or(0) + 8, xs().max().unwrap_or(0) - 8);
+    let (y0, y1) = (ys().min().unwrap_or(0) - 4, ys().max().unwrap_or(0) + 4);
+    let (x1, y1) = (x1.max(x0 + 8), y1.max(y0 + 8));
+    canvas.polygon(&[at.apply(x0, y0), at.apply(x1, y0), at.apply(x1, y1), at.apply(x0, y1)], "builtin");
+    for t in terms {
+        // stubs run from the terminal to the box edge.
+        let edge = if t.x <= x0 { (x0, t.y) } else if t.x >= x1 { (x1, t.y) } else { (t.x, y1) };
+        canvas.line(at.apply(t.x, t.y), at.apply(edge.0, edge.1), None);
+    }
+    canvas.text(at.apply((x0 + x1) / 2, (y0 + y1) / 2), label, "middle", Some(5.0), None);
+}
+
+pub fn icon_svg(module: &Module) -> E<String> {
+    let icon = match &module.icon {
+        Some(icon) => icon,
+        None => return bailfmt!("Module {} has no icon", module.name),
+    };
+    let mut canvas = Canvas::new();
+    draw_icon(&mut canvas, icon, Place::identity(), None, true);
+    Ok(canvas.finish())
+}
+
+pub fn schematic_svg(project: &Project, module_name: &str) -> E<String> {
+    let module = match project.module(module_name) {
+        Some(m) => m,
+        None => return bailfmt!("Can't find module: {}", module_name),
+    };
+
+    let mut canvas = Canvas::new();
+    // connection points, three or more meeting get a junction dot.
+    let mut ends: HashMap<(i32, i32), usize> = HashMap::new();
+    let mut end = |p: (i32, i32)| *ends.entry(p).or_insert(0) += 1;
+
+    for part in &module.schematic.parts {
+        match part {
+            Part::Wire(wire) => {
+                let c = &wire.coord5;
+                let p = Place { x: c.x, y: c.y, r: c.r };
+                let (a, b) = (p.apply(0, 0), p.apply(c.dx, c.dy));
+                canvas.line(a, b, Some("wire"));
+                end(a);
+                end(b);
+                if let Some(Signal { sig: Some(sig), .. }) = &wire.signal {
+                    let mid = ((a.0 + b.0) / 2, (a.1 + b.1) / 2 - 3);
+                    canvas.text(mid, &sig.to_string(), "middle", Some(5.0), Some("label"));
+                }
+            }
+            Part::Port(port) => {
+                let p = Place::of(&port.coord3);
+                end(p.apply(0, 0));
+                let body = [p.apply(0, 0), p.apply(-4, -4), p.apply(-20, -4), p.apply(-20, 4), p.apply(-4, 4)];
+                canvas.polygon(&body, "port");
+                if let Some(Signal { sig: Some(sig), .. }) = &port.signal {
+                    let anchor = Place { x: 0, y: 0, r: compose(Rot180, p.r) }.anchor();
+                    canvas.text(p.apply(-23, 0), &sig.to_string(), anchor, Some(5.0), Some("label"));
+                }
+            }
+            Part::Jumper(jumper) => {
+                let p = Place::of(&jumper.coord3);
+                let (a, b, m) = (p.apply(0, 0), p.apply(8, 0), p.apply(4, -4));
+                end(a);
+                end(b);
+                canvas.path(format!("M {} {} Q {} {} {} {}", a.0, a.1, m.0, m.1, b.0, b.1), &[a, b, m]);
+            }
+            Part::Terminal(term) => {
+                let p = Place::of(&term.coord3);
+                end(p.apply(0, 0));
+                draw_terminal(&mut canvas, p, Some(&term.sig.to_string()));
+            }
+            Part::Text(text) => {
+                let p = Place::of(&text.coord3);
+                canvas.text(p.apply(0, 0), &text.text, "start", font_size(&text.font), None);
+            }
+            Part::Vdd(vdd) => {
+                let p = Place::of(&vdd.coord3);
+                end(p.apply(0, 0));
+                canvas.line(p.apply(0, 0), p.apply(0, -8), None);
+                canvas.line(p.apply(-6, -8), p.apply(6, -8), None);
+            }
+            Part::Ground(gnd) => {
+                let p = Place::of(&gnd.coord3);
+                end(p.apply(0, 0));
+                canvas.line(p.apply(0, 0), p.apply(0, 8), None);
+                canvas.polygon(&[p.apply(-6, 8), p.apply(6, 8), p.apply(0, 14)], "ground");
+            }
+            Part::Memory(mem) => {
+                let p = Place::of(&mem.coord3);
+                let terms = memory_terminals(mem);
+                for t in &terms {
+                    end(p.apply(t.x, t.y));
+                }
+                let label = format!("{}x{}", 1u64 << mem.naddr.min(63), mem.ndata);
+                draw_builtin(&mut canvas, &label, &terms, p);
+            }
+            Part::SubModule(sub) => {
+                let p = Place::of(&sub.coord3);
+                if let Some(terms) = gate_terminals(&sub.name) {
+                    for t in &terms {
+                        end(p.apply(t.x, t.y));
+                    }
+                    draw_builtin(&mut canvas, sub.name.trim_start_matches("/gates/"), &terms, p);
+                    continue;
+                }
+                match project.module(&sub.name).and_then(|m| m.icon.as_ref()) {
+                    Some(icon) => {
+                        for part in &icon.parts {
+                            if let IconPart::Terminal(t) = part {
+                                end(p.then(&t.coord3).apply(0, 0));
+                            }
+                        }
+                        draw_icon(&mut canvas, icon, p, sub.instance_name.as_deref(), false);
+                    }
+                    None => {
+                        // a missing module is still shown where it was placed.
+                        canvas.circle(p.apply(0, 0), 4.0, None);
+                        canvas.text(p.apply(6, 0), &sub.name, "start", Some(5.0), None);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut dots: Vec<(i32, i32)> = ends.into_iter().filter(|(_, n)| *n >= 3).map(|(p, _)| p).collect();
+    dots.sort();
+    for p in dots {
+        canvas.circle(p, 2.0, Some("dot"));
+    }
+    Ok(canvas.finish())
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    #[test]
+    fn compose1() {
+        assert_eq!(compose(Rot0, Rot90), Rot90);
+        assert_eq!(compose(Rot90, Rot90), Rot180);
+        assert_eq!(compose(FlipX, FlipX), Rot0);
+        for r in &[Rot270, FlipY, TransposePos] {
+            let (x, y) = r.transform(3, 5);
+            assert_eq!(compose(*r, Rot180).transform(3, 5), Rot180.transform(x, y));
+        }
+    }
+
+    #[test]
+    fn icon1() {
+        let p = project("use-and2.json");
+        let got = icon_svg(p.module("/user/AND2").unwrap()).unwrap();
+        assert!(got.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-26 -18 52 36\""));
+        assert!(got.contains("<line x1=\"-8\" y1=\"-8\" x2=\"-8\" y2=\"8\"/>"));
+        assert!(got.contains(">&amp;2</text>"));
+        assert!(got.contains(">in1</text>"));
+    }
+
+    #[test]
+    fn schematic1() {
+        let p = project("use-and2.json");
+        let got = schematic_svg(&p, "/user/AND2").unwrap();
+        assert!(got.contains("<line x1=\"8\" y1=\"-168\" x2=\"16\" y2=\"-168\" class=\"wire\"/>"));
+        assert!(got.contains("class=\"builtin\""));
+        assert!(got.contains("text-anchor=\"end\" dominant-baseline=\"middle\" font-size=\"5\" class=\"label\">in1</text>"));
+        assert!(got.contains("text-anchor=\"start\" dominant-baseline=\"middle\" font-size=\"5\" class=\"label\">out</text>"));
+
+        // the used module is drawn as its icon, moved to where it was placed.
+        let got = schematic_svg(&p, "/user/UseAND2").unwrap();
+        assert!(got.contains("<line x1=\"8\" y1=\"0\" x2=\"8\" y2=\"16\"/>"));
+    }
+
+    #[test]
+    fn junctions() {
+        let p = project("WireConnectMid1.json");
+        let name = p.modules[0].name.clone();
+        let got = schematic_svg(&p, &name).unwrap();
+        // three wires meet in a T at the origin.
+        assert_eq!(got.matches("class=\"wire\"").count(), 3);
+        assert_eq!(got.matches("class=\"dot\"").count(), 1);
+        assert!(got.contains("<circle cx=\"0\" cy=\"0\" r=\"2\" class=\"dot\"/>"));
+        assert!(schematic_svg(&p, "/user/Nope").is_err());
+
+        // a plain bend is two wire ends, no dot.
+        let src = r#"["Jade", {"/user/Bend": {"schematic": [["wire", [0, 0, 0, 8, 0]], ["wire", [8, 0, 0, 0, 8]]]}}]"#;
+        let p = Project::from_str(src).unwrap();
+        let got = schematic_svg(&p, "/user/Bend").unwrap();
+        assert_eq!(got.matches("class=\"wire\"").count(), 2);
+        assert_eq!(got.matches("class=\"dot\"").count(), 0);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+
+use crate::types::*;
+use crate::vector::Role;
+
+// Jade test text back out of a ModTest, in the layout jade's own tests use:
+// supplies, groups, mode and .cycle first, then the vectors with a column
+// per bus, then the plots. Standalone comments keep their place among the
+// vectors, those ahead of the first vector go at the top.
+//
+//   std::fs::write("bool.test", test.to_string())?;
+
+fn value_char(v: BinVal, role: Option<Role>) -> char {
+    match (v, role) {
+        (L, Some(Role::Driven)) => '0',
+        (H, Some(Role::Driven)) => '1',
+        (L, _) => 'L',
+        (H, _) => 'H',
+        (X, _) => 'X',
+        (Z, _) => 'Z',
+        (DontCare, _) => '-',
+    }
+}
+
+fn action(a: &Action) -> String {
+    match a {
+        Action::Assert(g) => format!("assert {}", g),
+        Action::Deassert(g) => format!("deassert {}", g),
+        Action::Sample(g) => format!("sample {}", g),
+        Action::Tran(d) => format!("tran {}", d),
+        Action::SetSignal(sig, v) => format!("{}={}", sig, v),
+        Action::SetBus(sig, n) => format!("{}=0x{:x}", sig, n),
+    }
+}
+
+fn actions(actions: &[Action]) -> String {
+    actions.iter().map(action).collect::<Vec<String>>().join(" ")
+}
+
+fn directive(d: &Directive) -> String {
+    match d {
+        Directive::Options(options) => {
+            let pairs: Vec<String> = options.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            format!(".options {}", pairs.join(" "))
+        }
+        Directive::Repeat(n) => format!(".repeat {}", n),
+        Directive::Pause(d) => format!(".pause {}", d),
+        Directive::Initial(values) => {
+            let sets: Vec<String> = values.iter().map(|(sig, v)| format!("{}={}", sig, v)).collect();
+            format!(".initial {}", sets.join(" "))
+        }
+        Directive::Reset(a) => format!(".reset {}", actions(a)),
+        Directive::Verilog(line) => format!(".verilog {}", line),
+    }
+}
+
+fn plot(p: &PlotDirective) -> String {
+    match p {
+        PlotDirective::BinStyle(sig) => format!(".plot B({})", sig),
+        PlotDirective::HexStyle(sig) => format!(".plot X({})", sig),
+        PlotDirective::DecStyle(sig) => format!(".plot D({})", sig),
+        PlotDirective::SimplePlot(sig) => format!(".plot {}", sig),
+        PlotDirective::PlotDefStyle(name, sig) => format!(".plot {}({})", name, sig),
+    }
+}
+
+impl ModTest {
+    // how many values each column of a vector holds: one column per bus,
+    // single wires next to each other in a group share one.
+    fn columns(&self) -> Vec<(usize, Option<Role>)> {
+        let mut columns: Vec<(usize, Option<Role>)> = vec![];
+        for name in self.column_groups() {
+            let role = self.group_role(name);
+            let mut wires = false;
+            for sig in self.groups.get(name).map(|g| g.sigs.as_slice()).unwrap_or(&[]) {
+                let width = sig.width();
+                match columns.last_mut() {
+                    Some((n, _)) if wires && width == 1 => *n += 1,
+                    _ => columns.push((width, role)),
+                }
+                wires = width == 1;
+            }
+        }
+        columns
+    }
+
+    fn vector(&self, tl: &TestLine, columns: &[(usize, Option<Role>)]) -> String {
+        let mut parts = vec![];
+        let mut start = 0;
+        for (width, role) in columns {
+            let end = (start + width).min(tl.bin_vals.len());
+            parts.push(tl.bin_vals[start..end].iter().map(|v| value_char(*v, *role)).collect::<String>());
+            start = end;
+        }
+        // values past the groups' width, kept so nothing is lost.
+        if start < tl.bin_vals.len() {
+            parts.push(tl.bin_vals[start..].iter().map(|v| value_char(*v, None)).collect());
+        }
+        parts.retain(|p| !p.is_empty());
+        parts.join(" ")
+    }
+}
+
+// where a vector sits in the source. one without a position (a generated
+// vector) comes after every source line, so no comment follows it.
+fn line(tl: &TestLine) -> usize {
+    if tl.pos.line == 0 { usize::MAX } else { tl.pos.line }
+}
+
+impl fmt::Display for ModTest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let first = self.test_lines.first().map(line).unwrap_or(usize::MAX);
+        let between = |from: usize, to: usize| {
+            let comments = self.comments.iter().filter(move |c| c.pos.line >= from && c.pos.line < to);
+            comments.map(|c| format!("//{}", c.text))
+        };
+
+        let mut header = vec![];
+        let leading: Vec<String> = between(0, first).collect();
+        if !leading.is_empty() {
+            header.push(leading);
+        }
+        if !self.power.is_empty() || self.thresholds.is_some() {
+            let mut lines = vec![];
+            if !self.power.is_empty() {
+                let supplies: Vec<String> = self.power.iter().map(|p| format!("{}={}", p.name, p.volts)).collect();
+                lines.push(format!(".power {}", supplies.join(" ")));
+            }
+            if let Some(t) = &self.thresholds {
+                lines.push(format!(".thresholds Vol={} Vil={} Vih={} Voh={}", t.vol, t.vil, t.vih, t.voh));
+            }
+            header.push(lines);
+        }
+        if !self.groups.is_empty() {
+            header.push(self.groups
+                            .iter()
+                            .map(|g| {
+                                let sigs: Vec<String> = g.sigs.iter().map(|s| s.to_string()).collect();
+                                format!(".group {} {}", g.name, sigs.join(" "))
+                            })
+                            .collect());
+        }
+        if let Some(m) = &self.mode {
+            header.push(vec![format!(".mode {}", if m.mode == Mode::Gate { "gate" } else { "device" })]);
+        }
+        if let Some(CycleLine(a, _)) = &self.cycle_line {
+            header.push(vec![format!(".cycle {}", actions(a))]);
+        }
+        // .repeat and .pause sit with the vectors they apply to.
+        let vector_directive = |d: &&DirectiveLine| matches!(d.directive, Directive::Repeat(_) | Directive::Pause(_));
+        let others: Vec<String> =
+            self.directives.iter().filter(|d| !vector_directive(d)).map(|d| directive(&d.directive)).collect();
+        if !others.is_empty() {
+            header.push(others);
+        }
+        for lines in header {
+            writeln!(f, "{}\n", lines.join("\n"))?;
+        }
+
+        let columns = self.columns();
+        let vectors: Vec<String> = self.test_lines.iter().map(|tl| self.vector(tl, &columns)).collect();
+        let width = vectors.iter().map(|v| v.len()).max().unwrap_or(0);
+        let mut after = first;
+        for (i, (tl, v)) in self.test_lines.iter().zip(vectors.iter()).enumerate() {
+            if i > 0 {
+                for c in between(after, line(tl)) {
+                    writeln!(f, "{}", c)?;
+                }
+            }
+            let lead = self.directives.iter().filter(|d| vector_directive(d));
+            for d in lead.filter(|d| (i == 0 || d.pos.line > after) && d.pos.line < line(tl)) {
+                writeln!(f, "{}", directive(&d.directive))?;
+            }
+            match &tl.comment {
+                Some(c) => writeln!(f, "{:width$} //{}", v, c, width = width)?,
+                None => writeln!(f, "{}", v)?,
+            }
+            after = line(tl);
+        }
+        if !self.test_lines.is_empty() {
+            for d in self.directives.iter().filter(|d| vector_directive(d) && d.pos.line > after) {
+                writeln!(f, "{}", directive(&d.directive))?;
+            }
+            for c in between(after.saturating_add(1), usize::MAX) {
+                writeln!(f, "{}", c)?;
+            }
+        }
+
+        if !self.plot_defs.is_empty() {
+            writeln!(f)?;
+            for d in &self.plot_defs {
+                writeln!(f, ".plotdef {} {}", d.name, d.tags.join(" "))?;
+            }
+        }
+        if !self.plot_dirs.is_empty() {
+            writeln!(f)?;
+            for p in &self.plot_dirs {
+                writeln!(f, "{}", plot(&p.plot))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::Inputs;
+
+    // the same test with every source position forgotten.
+    fn unplaced(mut t: ModTest) -> ModTest {
+        t.power.iter_mut().for_each(|p| p.pos = Pos::default());
+        t.thresholds.iter_mut().for_each(|t| t.pos = Pos::default());
+        t.groups.groups.iter_mut().for_each(|g| g.pos = Pos::default());
+        t.cycle_line.iter_mut().for_each(|c| c.1 = Pos::default());
+        t.test_lines.iter_mut().for_each(|tl| tl.pos = Pos::default());
+        t.mode.iter_mut().for_each(|m| m.pos = Pos::default());
+        t.plot_dirs.iter_mut().for_each(|p| p.pos = Pos::default());
+        t.plot_defs.iter_mut().for_each(|d| d.pos = Pos::default());
+        t.comments.iter_mut().for_each(|c| c.pos = Pos::default());
+        t.directives.iter_mut().for_each(|d| d.pos = Pos::default());
+        t
+    }
+
+    #[test]
+    fn round_trip() {
+        for entry in std::fs::read_dir("./test-data/modtests").unwrap() {
+            let path = entry.unwrap().path();
+            let test = ModTest::from_file(&path).unwrap();
+            let text = test.to_string();
+            let again = ModTest::from_str(&text).unwrap();
+            assert_eq!(again.to_string(), text, "{:?}", path);
+            assert!(unplaced(again) == unplaced(test), "{:?}", path);
+        }
+        // the boolean unit's test is already in this layout, bar a line of spaces.
+        let src = std::fs::read_to_string("./test-data/modtests/bool.test").unwrap();
+        let lines: Vec<&str> = src.lines().map(|l| l.trim_end()).collect();
+        assert_eq!(ModTest::from_str(&src).unwrap().to_string().lines().collect::<Vec<&str>>(), lines);
+    }
+
+    #[test]
+    fn layout() {
+        let src = "// two input and\n.group inputs  A B\n.group outputs Y\n.cycle assert inputs tran 9ns sample outputs\n\
+                   .options step=1p\n00 L // none\n// ones\n.repeat 2\n11 H\n// done\n.plot X(Y)\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert_eq!(test.to_string(),
+                   "// two input and\n\n\
+                    .group inputs A B\n.group outputs Y\n\n\
+                    .cycle assert inputs tran 9n sample outputs\n\n\
+                    .options step=1p\n\n\
+                    00 L // none\n// ones\n.repeat 2\n11 H\n// done\n\n\
+                    .plot X(Y)\n");
+    }
+
+    #[test]
+    fn generated() {
+        let src = "// and2 reference test\n.group inputs A B\n.group outputs Y\n\
+                   .cycle assert inputs tran 9n sample outputs\n";
+        let mut test = ModTest::from_str(src).unwrap();
+        test.generate(Inputs::Exhaustive, |i| vec![i[0] && i[1]]).unwrap();
+        // the generated vectors have no source line, the comment stays on top.
+        let text = test.to_string();
+        assert_eq!(text,
+                   "// and2 reference test\n\n\
+                    .group inputs A B\n.group outputs Y\n\n\
+                    .cycle assert inputs tran 9n sample outputs\n\n\
+                    00 L // 1: A=0, B=0 -> Y=0\n01 L // 2: A=0, B=1 -> Y=0\n\
+                    10 L // 3: A=1, B=0 -> Y=0\n11 H // 4: A=1, B=1 -> Y=1\n");
+        assert_eq!(ModTest::from_str(&text).unwrap().to_string(), text);
+    }
+}
@@ -1,5 +1,63 @@
 use serde_json::{json, Value};
 
+#[cfg(test)]
+use crate::types::{ModTest, Mode, ModeLine, Module, Project};
+
+// a project from the test-data directory.
+#[cfg(test)]
+pub fn project(file: &str) -> Project {
+    Project::from_file(std::path::Path::new(&format!("./test-data/{}", file))).unwrap()
+}
+
+// a bare icon, and a module with a raw newline in a string.
+#[cfg(test)]
+const NOT_PROJECTS: &[&str] = &["and2-icon.json", "and2.json"];
+
+// saved tests whose fixtures are wrong, rather than the code under test.
+#[cfg(test)]
+pub const BROKEN: &[(&str, &str)] = &[
+    // the pad input is never driven or asserted, so shifted in bits are X.
+    ("Shift1.json", "/alu/shiftL"),
+    ("ShiftL1.json", "/user/ShiftL1"),
+    // asserts C and D, which the schematic doesn't have.
+    ("Jumper21.json", "/user/Jumper21"),
+    // tests A[1:0] and vout, the wires are A B C and X Y Z.
+    ("Jumper8.json", "/user/Jumper8"),
+    // a 1 bit addr on a 2 bit address port.
+    ("MemUnit4x2.json", "/user/MemUnit4x2"),
+    // two values under three columns.
+    ("port-wire.json", "/user/port_wire"),
+    ("port-wire-moved-up.json", "/user/port_wire"),
+];
+
+// calls f with every gate level test saved in test-data, apart from the
+// broken ones, and returns how many there were.
+#[cfg(test)]
+pub fn each_saved_test(mut f: impl FnMut(&str, &Project, &Module, &ModTest)) -> usize {
+    let mut files: Vec<String> =
+        std::fs::read_dir("./test-data").unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    files.sort();
+    let mut n = 0;
+    for file in files {
+        if !file.ends_with(".json") || NOT_PROJECTS.contains(&file.as_str()) {
+            continue;
+        }
+        let p = project(&file);
+        for m in &p.modules {
+            if BROKEN.contains(&(file.as_str(), m.name.as_str())) || !m.tests.iter().any(|t| t.name == "test") {
+                continue;
+            }
+            let test = m.test("test").unwrap_or_else(|b| panic!("{} {}: {:?}", file, m.name, b));
+            if matches!(test.mode, Some(ModeLine { mode: Mode::Device, .. })) || test.cycle_line.is_none() {
+                continue;
+            }
+            f(&file, &p, m, test);
+            n += 1;
+        }
+    }
+    n
+}
+
 pub fn garr_inc_4_json() -> Value {
     // this json macro is slick.
     json!(["Jade", {"/user/GarrInc4":
@@ -2,7 +2,6 @@ pub use crate::bail::*;
 
 use serde::{Deserialize, Serialize};
 use serde_repr::*;
-use std::collections::HashMap;
 use std::fmt::Debug;
 use std::path;
 
@@ -10,7 +9,7 @@ use std::path;
 #[derive(Debug)]
 pub struct Project {
     pub modules: Vec<Module>,
-    pub filename: path::Path,
+    pub filename: Option<path::PathBuf>,
 }
 
 #[derive(Debug)]
@@ -19,7 +18,16 @@ pub struct Module {
     pub properties: u8,
     pub schematic: Schematic,
     pub icon: Option<Icon>,
-    pub test: u8,
+    pub tests: Vec<NamedTest>,
+}
+
+// one entry of a module's test aspect, ["test", "..."] in the json, where
+// the first element names it. A test that doesn't parse keeps its error,
+// like jade the module still loads and the error shows when it is run.
+#[derive(Debug, PartialEq)]
+pub struct NamedTest {
+    pub name: String,
+    pub test: E<ModTest>,
 }
 
 pub struct Properties;
@@ -41,6 +49,9 @@ pub enum Part {
     Jumper(Jumper),
     Terminal(Terminal),
     Text(Text),
+    Vdd(Vdd),
+    Ground(Ground),
+    Memory(Memory),
     SubModule(SubModule),
 }
 
@@ -50,8 +61,9 @@ pub enum IconPart {
     Terminal(Terminal),
     Text(Text),
     Circle(Circle),
-    // Property,
-    // Arc,
+    Arc(Arc),
+    Box(IconBox),
+    Property(Property),
 }
 
 // #[derive(Debug, PartialEq)]
@@ -70,6 +82,8 @@ pub enum IconPart {
 pub struct SubModule {
     pub name: String,
     pub coord3: Coord3,
+    // the instance's "name" property, if the user gave it one.
+    pub instance_name: Option<String>,
 }
 
 // -----------------------------------------------------------------------------
@@ -83,7 +97,7 @@ pub struct Coord5 {
 }
 
 // -----------------------------------------------------------------------------
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct Coord3 {
     pub x: i32,
     pub y: i32,
@@ -103,6 +117,29 @@ pub struct Jumper {
     pub coord3: Coord3,
 }
 
+// -----------------------------------------------------------------------------
+#[derive(Debug, PartialEq)]
+pub struct Vdd {
+    pub coord3: Coord3,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Ground {
+    pub coord3: Coord3,
+}
+
+// -----------------------------------------------------------------------------
+// ["memory", [x, y, r], {"name": "Mem1", "contents": "0\n1", "nports": "2", "naddr": "2", "ndata": "2"}]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Memory {
+    pub coord3: Coord3,
+    pub name: Option<String>,
+    pub contents: String,
+    pub nports: u32,
+    pub naddr: u32,
+    pub ndata: u32,
+}
+
 // -----------------------------------------------------------------------------
 #[derive(Debug, PartialEq, Default)]
 pub struct Signal {
@@ -119,7 +156,7 @@ pub struct Line {
 }
 
 // -----------------------------------------------------------------------------
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug)]
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum Rot {
     Rot0 = 0,
@@ -134,7 +171,7 @@ pub enum Rot {
 pub use Rot::*;
 
 // -----------------------------------------------------------------------------
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Direction {
     In,
     Out,
@@ -146,7 +183,7 @@ pub use Direction::*;
 #[derive(Debug, PartialEq)]
 pub struct Symbol(pub String);
 
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 pub enum Sig {
     SigSimple(String),
     SigIndex(String, i32),
@@ -158,6 +195,14 @@ pub enum Sig {
 }
 pub use Sig::*;
 
+// a single wire of a signal after bus expansion, A[3:0] is four bits
+// named A[3] .. A[0], and quoted constants expand to tie bits.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Bit {
+    Name(String),
+    Const(bool),
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Circle {
     pub x: u32,
@@ -166,6 +211,28 @@ pub struct Circle {
     pub radius: f64,
 }
 
+// ["arc", [x, y, r, dx, dy, cx, cy]], an arc from (x,y) to (x+dx,y+dy) through (x+cx,y+cy)
+#[derive(Debug, PartialEq)]
+pub struct Arc {
+    pub coord5: Coord5,
+    pub cx: i32,
+    pub cy: i32,
+}
+
+// ["box", [x, y, r, w, h]]
+#[derive(Debug, PartialEq)]
+pub struct IconBox {
+    pub coord5: Coord5,
+}
+
+// ["property", [x, y, r], {"format": "{name}", "align": "bottom-center"}]
+#[derive(Debug, PartialEq)]
+pub struct Property {
+    pub coord3: Coord3,
+    pub format: String,
+    pub align: Option<String>,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Text {
     pub coord3: Coord3,
@@ -190,10 +257,26 @@ pub struct Terminal {
 // JADE TESTS
 //
 
+// where an item starts in the test source, both 1-based as the jade test
+// editor counts them.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+}
+
+// a line that holds nothing but a // comment.
+#[derive(Debug, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub pos: Pos,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Power {
     pub name: String,
     pub volts: f64,
+    pub pos: Pos,
 }
 
 #[derive(Debug, PartialEq)]
@@ -202,11 +285,21 @@ pub struct Thresholds {
     pub vil: f64,
     pub vih: f64,
     pub voh: f64,
+    pub pos: Pos,
+}
+
+// a .group line, ".group inputs A[3:0] B" names A[3:0] and B inputs.
+#[derive(Debug, PartialEq)]
+pub struct Group {
+    pub name: GroupName,
+    pub sigs: Vec<Sig>,
+    pub pos: Pos,
 }
 
+// the test's groups in the order they were declared.
 #[derive(Debug, PartialEq)]
 pub struct Groups {
-    pub sig_set: HashMap<GroupName, Vec<Sig>>,
+    pub groups: Vec<Group>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -216,7 +309,15 @@ pub enum Mode {
 }
 
 #[derive(Debug, PartialEq)]
+pub struct ModeLine {
+    pub mode: Mode,
+    pub pos: Pos,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Duration {
+    Second(f64),
+    MilliSecond(f64),
     MicroSecond(f64),
     NanoSecond(f64),
     PicoSecond(f64),
@@ -233,6 +334,7 @@ pub enum Duration {
 //    sample group -- check values of signals in group with 0,1 test values
 //    tran time -- run simulation for specified time interval
 //    signal=val -- set signal to specified value
+// signal=0x2c or signal=0b101 also sets a bus, or a group, to a number.
 
 type GroupName = String;
 
@@ -243,12 +345,13 @@ pub enum Action {
     Sample(GroupName),
     Tran(Duration),
     SetSignal(Sig, f64),
+    SetBus(Sig, u64),
 }
 
 #[derive(Debug, PartialEq)]
-pub struct CycleLine(pub Vec<Action>);
+pub struct CycleLine(pub Vec<Action>, pub Pos);
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum BinVal {
     L,        // binary low
     H,        // binary high
@@ -262,6 +365,7 @@ pub use BinVal::*;
 pub struct TestLine {
     pub bin_vals: Vec<BinVal>,
     pub comment: Option<String>,
+    pub pos: Pos,
 }
 
 #[derive(Debug, PartialEq)]
@@ -273,10 +377,41 @@ pub enum PlotDirective {
     PlotDefStyle(String, Sig),
 }
 
+#[derive(Debug, PartialEq)]
+pub struct PlotLine {
+    pub plot: PlotDirective,
+    pub pos: Pos,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct PlotDef {
     pub name: String,
     pub tags: Vec<String>,
+    pub pos: Pos,
+}
+
+// the rest of jade's test directives. .repeat and .pause apply to the
+// vector that follows them.
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    // .options name=value ..., settings for the simulator.
+    Options(Vec<(String, String)>),
+    // .repeat 4, run the next vector that many times.
+    Repeat(usize),
+    // .pause 10n, let the simulation run before the next vector.
+    Pause(Duration),
+    // .initial Q=0 ..., values set before the first vector.
+    Initial(Vec<(Sig, f64)>),
+    // .reset RESET=1 tran 10n RESET=0, run once before the first vector.
+    Reset(Vec<Action>),
+    // .verilog ..., a line only verilog testbenches use.
+    Verilog(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DirectiveLine {
+    pub directive: Directive,
+    pub pos: Pos,
 }
 
 #[derive(Debug, PartialEq)]
@@ -284,9 +419,11 @@ pub struct ModTest {
     pub power: Vec<Power>,
     pub thresholds: Option<Thresholds>,
     pub groups: Groups,
-    pub mode: Option<Mode>,
+    pub mode: Option<ModeLine>,
     pub cycle_line: Option<CycleLine>,
     pub test_lines: Vec<TestLine>,
-    pub plot_dirs: Vec<PlotDirective>,
+    pub plot_dirs: Vec<PlotLine>,
     pub plot_defs: Vec<PlotDef>,
+    pub comments: Vec<Comment>,
+    pub directives: Vec<DirectiveLine>,
 }
@@ -0,0 +1,109 @@
+// Weighted quick-union with path compression, used to merge wire end
+// points into nodes and signal names into nets.
+
+#[derive(Debug)]
+pub struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    pub fn new(n: usize) -> UnionFind {
+        UnionFind { parent: (0..n).collect(), size: vec![1; n] }
+    }
+
+    // add a new singleton site, returning its index.
+    pub fn add(&mut self) -> usize {
+        let id = self.parent.len();
+        self.parent.push(id);
+        self.size.push(1);
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.parent.len()
+    }
+
+    pub fn find(&mut self, p: usize) -> usize {
+        let mut root = p;
+        while root != self.parent[root] {
+            root = self.parent[root];
+        }
+        let mut p = p;
+        while p != root {
+            let next = self.parent[p];
+            self.parent[p] = root;
+            p = next;
+        }
+        root
+    }
+
+    pub fn union(&mut self, p: usize, q: usize) {
+        let (rp, rq) = (self.find(p), self.find(q));
+        if rp == rq {
+            return;
+        }
+        if self.size[rp] < self.size[rq] {
+            self.parent[rp] = rq;
+            self.size[rq] += self.size[rp];
+        } else {
+            self.parent[rq] = rp;
+            self.size[rp] += self.size[rq];
+        }
+    }
+
+    pub fn connected(&mut self, p: usize, q: usize) -> bool {
+        self.find(p) == self.find(q)
+    }
+
+    pub fn count(&mut self) -> usize {
+        (0..self.len()).filter(|&p| self.find(p) == p).count()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the data files are the first line site count, then one pair per line.
+    fn from_file(path: &str) -> UnionFind {
+        let text = std::fs::read_to_string(path).unwrap();
+        let mut lines = text.lines();
+        let n = lines.next().unwrap().trim().parse::<usize>().unwrap();
+        let mut uf = UnionFind::new(n);
+        for line in lines {
+            let pair: Vec<usize> = line.split_whitespace().map(|x| x.parse::<usize>().unwrap()).collect();
+            if pair.len() == 2 {
+                uf.union(pair[0], pair[1]);
+            }
+        }
+        uf
+    }
+
+    #[test]
+    fn tiny_uf() {
+        let mut uf = from_file("./test-data/union-find/tinyUF.txt");
+        assert_eq!(uf.count(), 2);
+        assert!(uf.connected(0, 7));
+        assert!(!uf.connected(0, 9));
+    }
+
+    #[test]
+    fn medium_uf() {
+        let mut uf = from_file("./test-data/union-find/mediumUF.txt");
+        assert_eq!(uf.count(), 3);
+    }
+
+    #[test]
+    fn add1() {
+        let mut uf = UnionFind::new(0);
+        let a = uf.add();
+        let b = uf.add();
+        assert!(!uf.connected(a, b));
+        uf.union(a, b);
+        assert!(uf.connected(a, b));
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::netlist::name_key;
+use crate::types::*;
+
+// Checks a test against the module it is meant for before anything runs,
+// so mistakes come back as a list pointing into the test source rather
+// than as the first error the simulator trips over.
+//
+//   for d in test.validate(project.module("/user/Bool1").unwrap()) {
+//       println!("{}", d);
+//   }
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub pos: Pos,
+    pub msg: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.pos.line, self.pos.col, self.msg)
+    }
+}
+
+// the bit names a test may refer to: the module's ports and, since jade
+// lets a test probe internal nodes, its labelled wires. Keyed the way the
+// netlist matches names, without regard to case.
+fn known_bits(module: &Module) -> HashSet<String> {
+    let mut known = HashSet::new();
+    for part in &module.schematic.parts {
+        let signal = match part {
+            Part::Port(Port { signal: Some(signal), .. }) | Part::Wire(Wire { signal: Some(signal), .. }) => signal,
+            _ => continue,
+        };
+        if let Some(sig) = &signal.sig {
+            for bit in sig.bits() {
+                if let Bit::Name(name) = bit {
+                    known.insert(name_key(&name));
+                }
+            }
+        }
+    }
+    known
+}
+
+// the first bit of sig the module doesn't have.
+fn unknown_bit(sig: &Sig, known: &HashSet<String>) -> Option<String> {
+    sig.bits().into_iter().find_map(|bit| match bit {
+                              Bit::Name(name) if !known.contains(&name_key(&name)) => Some(name),
+                              Bit::Name(_) => None,
+                              Bit::Const(_) => Some(sig.to_string()),
+                          })
+}
+
+impl ModTest {
+    pub fn validate(&self, module: &Module) -> Vec<Diagnostic> {
+        let mut out = vec![];
+        let known = known_bits(module);
+
+        if let Some(t) = &self.thresholds {
+            if !(t.vol <= t.vil && t.vil < t.vih && t.vih <= t.voh) {
+                let msg = format!("Thresholds must satisfy Vol <= Vil < Vih <= Voh, got Vol={} Vil={} Vih={} Voh={}",
+                                  t.vol, t.vil, t.vih, t.voh);
+                out.push(Diagnostic { pos: t.pos, msg });
+            }
+        }
+
+        for group in self.groups.iter() {
+            for sig in &group.sigs {
+                if let Some(bit) = unknown_bit(sig, &known) {
+                    let msg = format!("Group {}: {} is not a signal of {}", group.name, bit, module.name);
+                    out.push(Diagnostic { pos: group.pos, msg });
+                }
+            }
+        }
+
+        let (actions, pos) = match &self.cycle_line {
+            Some(CycleLine(actions, pos)) => (actions, *pos),
+            None => {
+                if let Some(tl) = self.test_lines.first() {
+                    out.push(Diagnostic { pos: tl.pos, msg: "Test vectors but no .cycle line".to_string() });
+                }
+                return out;
+            }
+        };
+        let mut width = Some(0);
+        for action in actions {
+            match action {
+                Action::Assert(g) | Action::Deassert(g) | Action::Sample(g) => {
+                    if !self.groups.contains(g) {
+                        out.push(Diagnostic { pos, msg: format!(".cycle uses undeclared group {}", g) });
+                        width = None;
+                    }
+                }
+                Action::SetSignal(sig, _) | Action::SetBus(sig, _) => {
+                    let group = matches!(sig, SigSimple(name) if self.groups.contains(name));
+                    if let Some(bit) = unknown_bit(sig, &known).filter(|_| !group) {
+                        let msg = format!(".cycle sets {}, not a signal of {}", bit, module.name);
+                        out.push(Diagnostic { pos, msg });
+                    }
+                }
+                Action::Tran(_) => {}
+            }
+        }
+
+        // vector widths only mean something once every column group is known.
+        if let Some(mut width) = width {
+            for g in self.column_groups() {
+                width += self.groups.get(g).unwrap().width();
+            }
+            for tl in &self.test_lines {
+                if tl.bin_vals.len() != width {
+                    let msg = format!("Expected {} values, got {}", width, tl.bin_vals.len());
+                    out.push(Diagnostic { pos: tl.pos, msg });
+                }
+            }
+        }
+        out
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    #[test]
+    fn bool_unit() {
+        let p = project("Bool1.json");
+        let test = ModTest::from_file(Path::new("./test-data/modtests/bool.test")).unwrap();
+        assert_eq!(test.validate(p.module("/user/Bool1").unwrap()), vec![]);
+    }
+
+    #[test]
+    fn mistakes() {
+        let p = project("use-and2.json");
+        let src = ".thresholds Vol=0 Vil=0.5 Vih=0.4 Voh=1\n.group inputs in1 IN2 nope[1:0]\n.group outputs out\n\
+                   .cycle assert inputs tran 9n sample outputs CLK=1\n\n0011 L\n01 L\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got: Vec<String> = test.validate(p.module("/user/AND2").unwrap()).iter().map(|d| d.to_string()).collect();
+        assert_eq!(got,
+                   vec!["line 1, column 1: Thresholds must satisfy Vol <= Vil < Vih <= Voh, got Vol=0 Vil=0.5 Vih=0.4 Voh=1",
+                        "line 2, column 1: Group inputs: nope[1] is not a signal of /user/AND2",
+                        "line 4, column 1: .cycle sets CLK, not a signal of /user/AND2",
+                        "line 7, column 1: Expected 5 values, got 3"]);
+
+        let src = ".group inputs in1 in2\n.cycle assert inputs sample outputs\n00\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got = test.validate(p.module("/user/AND2").unwrap());
+        assert_eq!(got,
+                   vec![Diagnostic { pos: Pos { line: 2, col: 1 },
+                                     msg: ".cycle uses undeclared group outputs".to_string() }]);
+    }
+}
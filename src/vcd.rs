@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::netlist::*;
+use crate::sim::*;
+use crate::types::*;
+
+// IEEE 1364 value change dumps of a traced simulation, for GTKWave and
+// friends. Net names are instance paths, AND2_0.in1 goes in scope AND2_0,
+// and bits of one bus in a scope become a single vector.
+//
+//   sim.start_trace();
+//   run_on(&mut sim, module, &test)?;
+//   std::fs::write("and2.vcd", vcd(&sim, module, None)?)?;
+
+#[derive(Debug, PartialEq)]
+struct VcdVar {
+    scope: Vec<String>,
+    name: String,
+    range: Option<(i32, i32)>,
+    // most significant bit first.
+    nets: Vec<usize>,
+}
+
+// identifiers are short strings of the printable characters ! to ~.
+fn vcd_id(mut n: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            return id;
+        }
+        n -= 1;
+    }
+}
+
+fn vcd_char(v: Logic) -> char {
+    v.to_char().to_ascii_lowercase()
+}
+
+// "AND2_0.x.in1" -> (["AND2_0", "x"], "in1")
+fn split_path(name: &str) -> (Vec<String>, String) {
+    let mut parts: Vec<String> = name.split('.').map(|s| s.to_string()).collect();
+    let leaf = parts.pop().unwrap_or_default();
+    (parts, leaf)
+}
+
+// the bits to dump: all nets, or the named signals. A selection can name a
+// flattened net or a top level signal, buses like Y[31:0] included.
+fn selected_bits(sim: &Simulator, select: Option<&[&str]>) -> E<Vec<(String, usize)>> {
+    let select = match select {
+        Some(select) => select,
+        None => return Ok(sim.nets().iter().enumerate().map(|(i, n)| (n.clone(), i)).collect()),
+    };
+    let mut bits = vec![];
+    for name in select {
+        if let Ok(net) = sim.net_index(name) {
+            bits.push((name.to_string(), net));
+            continue;
+        }
+        let sig = match Sig::from_str(name) {
+            Ok(sig) => sig,
+            Err(_) => return bailfmt!("No net for signal: {}", name),
+        };
+        for bit in sig.bits() {
+            if let Bit::Name(bit) = bit {
+                let net = sim.net_index(&bit)?;
+                bits.push((bit, net));
+            }
+        }
+    }
+    Ok(bits)
+}
+
+fn vars(bits: &[(String, usize)]) -> Vec<VcdVar> {
+    let mut scopes: Vec<Vec<String>> = vec![];
+    let mut leaves: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+    let mut nets: HashMap<String, usize> = HashMap::new();
+    for (name, net) in bits {
+        let (scope, leaf) = split_path(name);
+        if !leaves.contains_key(&scope) {
+            scopes.push(scope.clone());
+        }
+        nets.insert(name.clone(), *net);
+        leaves.entry(scope).or_default().push(leaf);
+    }
+    scopes.sort();
+
+    let mut vars = vec![];
+    for scope in scopes {
+        let prefix: String = scope.iter().map(|s| format!("{}.", s)).collect();
+        for group in group_bits(&leaves[&scope]) {
+            let mut bits = group.bits.clone();
+            bits.sort_by_key(|b| -split_bit(b).1.unwrap_or(0));
+            let net_of = |b: &String| nets[&format!("{}{}", prefix, b)];
+            match group.range {
+                // only a bus with no gaps dumps as a vector.
+                Some((hi, lo)) if (hi - lo + 1) as usize == bits.len() && bits.len() > 1 => {
+                    vars.push(VcdVar { scope: scope.clone(),
+                                       name: group.base.clone(),
+                                       range: Some((hi, lo)),
+                                       nets: bits.iter().map(net_of).collect() });
+                }
+                _ => {
+                    for b in &bits {
+                        vars.push(VcdVar { scope: scope.clone(), name: b.clone(), range: None, nets: vec![net_of(b)] });
+                    }
+                }
+            }
+        }
+    }
+    vars
+}
+
+fn value(var: &VcdVar, values: &[Logic], id: &str) -> String {
+    if var.range.is_none() {
+        return format!("{}{}", vcd_char(values[var.nets[0]]), id);
+    }
+    let bits: String = var.nets.iter().map(|n| vcd_char(values[*n])).collect();
+    format!("b{} {}", bits, id)
+}
+
+pub fn vcd(sim: &Simulator, top: &str, select: Option<&[&str]>) -> E<String> {
+    let trace = match sim.trace() {
+        Some(trace) => trace,
+        None => return bail!("The simulator wasn't tracing"),
+    };
+    let vars = vars(&selected_bits(sim, select)?);
+    let ids: Vec<String> = (0..vars.len()).map(vcd_id).collect();
+
+    let mut out = String::new();
+    out.push_str("$version jade-parse $end\n$timescale 1fs $end\n");
+    let top = top.rsplit('/').next().unwrap_or(top);
+    writeln!(out, "$scope module {} $end", top).unwrap();
+    let mut open: Vec<String> = vec![];
+    for (var, id) in vars.iter().zip(ids.iter()) {
+        let common = open.iter().zip(var.scope.iter()).take_while(|(a, b)| a == b).count();
+        while open.len() > common {
+            open.pop();
+            out.push_str("$upscope $end\n");
+        }
+        for s in &var.scope[common..] {
+            writeln!(out, "$scope module {} $end", s).unwrap();
+            open.push(s.clone());
+        }
+        match var.range {
+            Some((hi, lo)) => writeln!(out, "$var wire {} {} {} [{}:{}] $end", var.nets.len(), id, var.name, hi, lo),
+            None => writeln!(out, "$var wire 1 {} {} $end", id, var.name),
+        }.unwrap();
+    }
+    for _ in open {
+        out.push_str("$upscope $end\n");
+    }
+    out.push_str("$upscope $end\n$enddefinitions $end\n");
+
+    // which vars look at each net.
+    let mut watchers: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (v, var) in vars.iter().enumerate() {
+        for net in &var.nets {
+            watchers.entry(*net).or_default().push(v);
+        }
+    }
+
+    let mut values = trace.initial.clone();
+    let mut last: Vec<String> = vars.iter().zip(ids.iter()).map(|(v, id)| value(v, &values, id)).collect();
+    writeln!(out, "#{}\n$dumpvars", trace.start).unwrap();
+    for l in &last {
+        writeln!(out, "{}", l).unwrap();
+    }
+    out.push_str("$end\n");
+
+    let mut written = trace.start;
+    let mut i = 0;
+    while i < trace.changes.len() {
+        let time = trace.changes[i].0;
+        let mut touched = vec![];
+        while i < trace.changes.len() && trace.changes[i].0 == time {
+            let (_, net, v) = trace.changes[i];
+            values[net] = v;
+            touched.extend(watchers.get(&net).cloned().unwrap_or_default());
+            i += 1;
+        }
+        touched.sort();
+        touched.dedup();
+        let mut lines = vec![];
+        for v in touched {
+            let now = value(&vars[v], &values, &ids[v]);
+            if now != last[v] {
+                lines.push(now.clone());
+                last[v] = now;
+            }
+        }
+        if !lines.is_empty() {
+            if time != written {
+                writeln!(out, "#{}", time).unwrap();
+                written = time;
+            }
+            writeln!(out, "{}", lines.join("\n")).unwrap();
+        }
+    }
+    writeln!(out, "#{}", sim.now()).unwrap();
+    Ok(out)
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use crate::runner::run_on;
+    use std::path::Path;
+
+    #[test]
+    fn ids() {
+        assert_eq!(vcd_id(0), "!");
+        assert_eq!(vcd_id(93), "~");
+        assert_eq!(vcd_id(94), "!!");
+        assert_eq!(vcd_id(95), "\"!");
+    }
+
+    #[test]
+    fn and2() {
+        let p = project("use-and2.json");
+        let n = Netlist::flatten(&p, "/user/UseAND2", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        assert!(vcd(&sim, "/user/UseAND2", None).is_err());
+        sim.start_trace();
+        sim.drive("AND2_0.in1", Logic::One).unwrap();
+        sim.drive("AND2_0.in2", Logic::One).unwrap();
+        sim.run_for(Duration::NanoSecond(1.0)).unwrap();
+
+        let got = vcd(&sim, "/user/UseAND2", None).unwrap();
+        assert!(got.contains("$scope module UseAND2 $end\n$var wire 1 ! vdd $end\n$var wire 1 \" gnd $end\n\
+                              $scope module AND2_0 $end\n"));
+        assert!(got.contains("$var wire 1 $ in1 $end"));
+        assert!(got.ends_with("#0\n$dumpvars\n1!\n0\"\nx#\nx$\nx%\n$end\n1$\n1%\n#50000\n1#\n#1000000\n"));
+
+        let got = vcd(&sim, "/user/UseAND2", Some(&["AND2_0.out"])).unwrap();
+        assert!(got.contains("$var wire 1 ! out $end\n$upscope $end\n$upscope $end\n"));
+        assert!(vcd(&sim, "/user/UseAND2", Some(&["nope"])).is_err());
+    }
+
+    #[test]
+    fn buses() {
+        let p = project("Bool1.json");
+        let test = ModTest::from_str(&std::fs::read_to_string("./test-data/modtests/bool.test").unwrap()).unwrap();
+        let n = Netlist::flatten(&p, "/user/Bool1", &NetConfig::new()).unwrap();
+        let mut sim = Simulator::new(&n).unwrap();
+        sim.start_trace();
+        run_on(&mut sim, "/user/Bool1", &test).unwrap();
+        let got = vcd(&sim, "/user/Bool1", Some(&["Y[31:0]", "BFN[3:0]"])).unwrap();
+        assert!(got.contains("$var wire 32 ! Y [31:0] $end\n$var wire 4 \" BFN [3:0] $end\n"));
+        assert!(got.contains("b00000000000000000000000011111111 !\n"));
+        assert!(got.ends_with("#1600000000\n"));
+    }
+}
@@ -0,0 +1,41 @@
+use serde_json::Value;
+
+use crate::common::*;
+use crate::types::*;
+
+impl Vdd {
+    pub fn from_value(val: &Value) -> E<Vdd> {
+        let mut val_iter = bailif!(tagged_array("vdd", &val), "Vdd::from_value failes to decode")?;
+
+        let coord3: Coord3 = match val_iter.next() {
+            Some(c) => serde_json::from_value::<Coord3>(c.clone())?,
+            None => {
+                return bailfmt!("Vdd expects 1 element, a 3 number array, got: {:?}", val);
+            }
+        };
+        Ok(Vdd { coord3 })
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn vdd1() {
+        let val = json!(["vdd", [-32, -32, 0]]);
+        let expect = Vdd { coord3: Coord3 { x: -32, y: -32, r: Rot0 } };
+        assert_eq!(Vdd::from_value(&val), Ok(expect));
+    }
+
+    #[test]
+    fn vdd2() {
+        let val = json!(["ground", [-32, -32, 0]]);
+        assert!(Vdd::from_value(&val).is_err());
+    }
+}
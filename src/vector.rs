@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::types::*;
+
+// A test vector read back as signals instead of columns. The .cycle line
+// decides what the columns are: groups it asserts are driven onto the
+// module, groups it samples are what the module should produce.
+//
+//   00 HLLL...LHHLL ...      ->  RESET = 0 driven
+//                                IA[31:0] = 0x8000002c expected
+//                                MOE = DontCare expected
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Role {
+    Driven,
+    Expected,
+    // a group that is asserted and sampled, a bidirectional bus say.
+    Both,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SignalValue {
+    pub group: String,
+    pub sig: Sig,
+    pub role: Role,
+    // one per bit, most significant first.
+    pub values: Vec<BinVal>,
+}
+
+impl SignalValue {
+    // the bits as a number when they are all 0 or 1.
+    pub fn number(&self) -> Option<u128> {
+        let mut n = 0u128;
+        for v in &self.values {
+            n = n.checked_mul(2)?
+                 + match v {
+                     L => 0,
+                     H => 1,
+                     _ => return None,
+                 };
+        }
+        Some(n)
+    }
+
+    // 1, 0x2c, DontCare, Z or 0b1X0- for a mix.
+    pub fn value(&self) -> String {
+        let first = self.values.first().cloned().unwrap_or(DontCare);
+        let same = self.values.iter().all(|v| *v == first);
+        match self.number() {
+            Some(n) if self.values.len() == 1 => n.to_string(),
+            Some(n) => format!("0x{:0width$x}", n, width = (self.values.len() + 3) / 4),
+            None if same && first == DontCare => "DontCare".to_string(),
+            None if same => value_char(first).to_string(),
+            None => format!("0b{}", self.values.iter().map(|v| value_char(*v)).collect::<String>()),
+        }
+    }
+}
+
+fn value_char(v: BinVal) -> char {
+    match v {
+        L => '0',
+        H => '1',
+        X => 'X',
+        Z => 'Z',
+        DontCare => '-',
+    }
+}
+
+impl fmt::Display for SignalValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let role = match self.role {
+            Role::Driven => "driven",
+            Role::Expected => "expected",
+            Role::Both => "driven and expected",
+        };
+        write!(f, "{} = {} {}", self.sig, self.value(), role)
+    }
+}
+
+impl ModTest {
+    // what a group's columns are used for, None when the .cycle line never
+    // asserts or samples it.
+    pub fn group_role(&self, name: &str) -> Option<Role> {
+        let actions = match &self.cycle_line {
+            Some(CycleLine(actions, _)) => actions,
+            None => return None,
+        };
+        let driven = actions.iter().any(|a| matches!(a, Action::Assert(g) | Action::Deassert(g) if g == name));
+        let sampled = actions.iter().any(|a| matches!(a, Action::Sample(g) if g == name));
+        match (driven, sampled) {
+            (true, true) => Some(Role::Both),
+            (true, false) => Some(Role::Driven),
+            (false, true) => Some(Role::Expected),
+            (false, false) => None,
+        }
+    }
+
+    // split a vector into one value per signal of the column groups.
+    pub fn signal_values(&self, tl: &TestLine) -> E<Vec<SignalValue>> {
+        let mut out = vec![];
+        let mut start = 0;
+        for name in self.column_groups() {
+            let role = self.group_role(name).unwrap();
+            let sigs = match self.groups.get(name) {
+                Some(group) => &group.sigs,
+                None => return bailfmt!("Unknown group in .cycle: {}", name),
+            };
+            for sig in sigs {
+                let end = start + sig.width();
+                if end > tl.bin_vals.len() {
+                    let msg = format!("Test line {}: too few values for {} in group {}", tl.pos.line, sig, name);
+                    return bail!(msg);
+                }
+                out.push(SignalValue { group: name.to_string(),
+                                       sig: sig.clone(),
+                                       role,
+                                       values: tl.bin_vals[start..end].to_vec() });
+                start = end;
+            }
+        }
+        if start != tl.bin_vals.len() {
+            let msg = format!("Test line {}: expected {} values, got {}", tl.pos.line, start, tl.bin_vals.len());
+            return bail!(msg);
+        }
+        Ok(out)
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn beta() {
+        let test = ModTest::from_file(Path::new("./test-data/modtests/beta.test")).unwrap();
+        let got: Vec<String> = test.signal_values(&test.test_lines[1]).unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(got,
+                   vec!["RESET = 0 driven",
+                        "IRQ = 0 driven",
+                        "IA[31:0] = 0x80000000 expected",
+                        "ID[31:0] = 0x73df000a expected",
+                        "MA[31:0] = DontCare expected",
+                        "MOE = DontCare expected",
+                        "MWR = 0 expected",
+                        "MRD[31:0] = DontCare expected",
+                        "MWD[31:0] = DontCare expected"]);
+        let got = test.signal_values(&test.test_lines[2]).unwrap();
+        assert_eq!(got[2].number(), Some(0x8000002c));
+    }
+
+    #[test]
+    fn mixed() {
+        let src = ".group inputs A[3:0] OE\n.group bus D[1:0]\n.cycle assert inputs assert bus sample bus\n\
+                   1X0- Z ZZ\n10\n";
+        let test = ModTest::from_str(src).unwrap();
+        let got: Vec<String> = test.signal_values(&test.test_lines[0]).unwrap().iter().map(|v| v.to_string()).collect();
+        assert_eq!(got, vec!["A[3:0] = 0b1X0- driven", "OE = Z driven", "D[1:0] = Z driven and expected"]);
+        assert!(test.signal_values(&test.test_lines[1]).is_err());
+    }
+}
@@ -0,0 +1,720 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cycle::*;
+use crate::gates::*;
+use crate::netlist::*;
+use crate::types::*;
+use crate::vhdl::entity_name;
+
+// Structural Verilog for a module hierarchy, one module per jade module,
+// children first:
+//
+//   iverilog -o design design.v
+//
+// Logic gates map to the built in primitives (and, nor, bufif1 ...), muxes
+// to continuous assigns and registers to a small behavioural jade_dreg.
+
+const KEYWORDS: &[&str] = &["always", "and", "assign", "automatic", "begin", "buf", "bufif0", "bufif1", "case",
+                            "casex", "casez", "cell", "cmos", "config", "deassign", "default", "defparam", "design",
+                            "disable", "edge", "else", "end", "endcase", "endconfig", "endfunction", "endgenerate",
+                            "endmodule", "endprimitive", "endspecify", "endtable", "endtask", "event", "for", "force",
+                            "forever", "fork", "function", "generate", "genvar", "highz0", "highz1", "if", "ifnone",
+                            "incdir", "include", "initial", "inout", "input", "instance", "integer", "join", "large",
+                            "liblist", "library", "localparam", "macromodule", "medium", "module", "nand", "negedge",
+                            "nmos", "nor", "noshowcancelled", "not", "notif0", "notif1", "or", "output", "parameter",
+                            "pmos", "posedge", "primitive", "pull0", "pull1", "pulldown", "pullup",
+                            "pulsestyle_ondetect", "pulsestyle_onevent", "rcmos", "real", "realtime", "reg",
+                            "release", "repeat", "rnmos", "rpmos", "rtran", "rtranif0", "rtranif1", "scalared",
+                            "showcancelled", "signed", "small", "specify", "specparam", "strong0", "strong1",
+                            "supply0", "supply1", "table", "task", "time", "tran", "tranif0", "tranif1", "tri",
+                            "tri0", "tri1", "triand", "trior", "trireg", "unsigned", "use", "uwire", "vectored",
+                            "wait", "wand", "weak0", "weak1", "while", "wire", "wor", "xnor", "xor"];
+
+// names that aren't legal Verilog become escaped identifiers, which end at
+// the next white space.
+pub fn verilog_ident(name: &str) -> String {
+    let pat = regex::Regex::new(r#"^[A-Za-z_][A-Za-z0-9_$]*$"#).unwrap();
+    if pat.is_match(name) && !KEYWORDS.contains(&name) { name.to_string() } else { format!("\\{} ", name) }
+}
+
+pub fn module_name(module: &str) -> String {
+    verilog_ident(entity_name(module).trim_matches('\\'))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VerilogDecl {
+    pub ident: String,
+    pub range: Option<(i32, i32)>,
+    pub bits: Vec<String>,
+}
+
+impl VerilogDecl {
+    pub fn range_str(&self) -> String {
+        match self.range {
+            Some((hi, lo)) => format!("[{}:{}] ", hi, lo),
+            None => String::new(),
+        }
+    }
+}
+
+pub fn declare(bits: &[String], used: &mut HashSet<String>) -> (Vec<VerilogDecl>, HashMap<String, String>) {
+    let mut decls = vec![];
+    let mut exprs = HashMap::new();
+    for group in group_bits(bits) {
+        let mut ident = verilog_ident(&group.base);
+        while used.contains(&ident) {
+            ident = verilog_ident(&format!("{}_", ident.trim()));
+        }
+        used.insert(ident.clone());
+        for bit in &group.bits {
+            let expr = match (group.range, split_bit(bit)) {
+                (Some(_), (_, Some(idx))) => format!("{}[{}]", ident, idx),
+                _ => ident.clone(),
+            };
+            exprs.insert(bit.clone(), expr);
+        }
+        decls.push(VerilogDecl { ident, range: group.range, bits: group.bits });
+    }
+    (decls, exprs)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VerilogPort {
+    pub decl: VerilogDecl,
+    pub direction: Direction,
+}
+
+fn direction_str(dir: Direction) -> &'static str {
+    match dir {
+        In => "input",
+        Out => "output",
+        InOut => "inout",
+    }
+}
+
+pub fn verilog_ports(m: &ModuleNetlist, used: &mut HashSet<String>) -> (Vec<VerilogPort>, HashMap<String, String>) {
+    let mut bits = vec![];
+    let mut dirs: HashMap<String, Direction> = HashMap::new();
+    for port in &m.ports {
+        for bit in port.sig.bits() {
+            if let Bit::Name(name) = bit {
+                dirs.entry(name.clone()).or_insert(port.direction.unwrap_or(In));
+                bits.push(name);
+            }
+        }
+    }
+    let (decls, exprs) = declare(&bits, used);
+    let ports = decls.into_iter().map(|decl| VerilogPort { direction: dirs[&decl.bits[0]], decl }).collect();
+    (ports, exprs)
+}
+
+fn tie_expr(kind: NetKind) -> Option<&'static str> {
+    match kind {
+        NetKind::TieHigh => Some("1'b1"),
+        NetKind::TieLow => Some("1'b0"),
+        _ => None,
+    }
+}
+
+fn primitive(kind: GateKind) -> Option<&'static str> {
+    Some(match kind {
+        GateKind::And => "and",
+        GateKind::Or => "or",
+        GateKind::Xor => "xor",
+        GateKind::Nand => "nand",
+        GateKind::Nor => "nor",
+        GateKind::Xnor => "xnor",
+        GateKind::Buffer => "buf",
+        GateKind::Inverter => "not",
+        GateKind::Tristate => "bufif1",
+        _ => return None,
+    })
+}
+
+const DREG: &str = "module jade_dreg (input D, input CLK, output reg Q);
+  always @(posedge CLK) Q <= D;
+endmodule
+";
+
+fn memory_statements(label: &str, mem: &Memory, net: &dyn Fn(&str) -> String) -> E<String> {
+    let words = mem.words()?;
+    let size = 1usize << mem.naddr.min(20);
+    let bus = |name: &str, width: u32| -> String {
+        if width == 1 {
+            net(name)
+        } else {
+            let bits: Vec<String> = (0..width).rev().map(|i| net(&format!("{}[{}]", name, i))).collect();
+            format!("{{{}}}", bits.join(", "))
+        }
+    };
+    let base = label.trim().trim_start_matches('\\');
+    let (array, i) = (verilog_ident(&format!("{}_mem", base)), verilog_ident(&format!("{}_i", base)));
+
+    let mut out = String::new();
+    out.push_str(&format!("  reg [{}:0] {} [0:{}];\n", mem.ndata - 1, array, size - 1));
+    out.push_str(&format!("  integer {};\n  initial begin\n", i));
+    out.push_str(&format!("    for ({} = 0; {} < {}; {} = {} + 1) {}[{}] = 0;\n", i, i, size, i, i, array, i));
+    for (i, w) in words.iter().take(size).enumerate() {
+        if *w != 0 {
+            out.push_str(&format!("    {}[{}] = {}'h{:x};\n", array, i, mem.ndata, w));
+        }
+    }
+    out.push_str("  end\n");
+    for p in 0..mem.nports {
+        let addr = bus(&format!("A{}", p), mem.naddr);
+        let data = bus(&format!("D{}", p), mem.ndata);
+        let (oe, we, clk) = (net(&format!("OE{}", p)), net(&format!("WE{}", p)), net(&format!("CLK{}", p)));
+        out.push_str(&format!("  always @(posedge {}) if ({}) {}[{}] <= {};\n", clk, we, array, addr, data));
+        out.push_str(&format!("  assign {} = {} ? {}[{}] : {}'bz;\n", data, oe, array, addr, mem.ndata));
+    }
+    Ok(out)
+}
+
+fn device_statement(label: &str, kind: GateKind, inst: &Instance, net: &dyn Fn(&str) -> String) -> E<String> {
+    if let Some(prim) = primitive(kind) {
+        // primitives list the output first.
+        let mut terms = vec![net("Z")];
+        for t in &["A", "E", "B", "C", "D"] {
+            if inst.connections.iter().any(|(c, _)| c == t) {
+                terms.push(net(t));
+            }
+        }
+        return Ok(format!("  {} {} ({});\n", prim, label, terms.join(", ")));
+    }
+    Ok(match kind {
+        GateKind::Mux2 => format!("  assign {} = {} ? {} : {};\n", net("Y"), net("S"), net("D1"), net("D0")),
+        GateKind::Mux4 => {
+            let (s1, s0) = (net("S[1]"), net("S[0]"));
+            format!("  assign {} = {} ? ({} ? {} : {}) : ({} ? {} : {});\n",
+                    net("Y"),
+                    s1,
+                    s0,
+                    net("D3"),
+                    net("D2"),
+                    s0,
+                    net("D1"),
+                    net("D0"))
+        }
+        GateKind::Dreg => format!("  jade_dreg {} (.D({}), .CLK({}), .Q({}));\n", label, net("D"), net("CLK"), net("Q")),
+        GateKind::Memory => match &inst.memory {
+            Some(mem) => memory_statements(label, mem, net)?,
+            None => return bailfmt!("memory instance {} has no contents", label),
+        },
+        _ => return bailfmt!("No Verilog for gate {}", inst.module),
+    })
+}
+
+// as in the VHDL backend, a net lives on its first port bit and any other
+// output port bits on it are assigned from there. The rest get wires.
+pub struct VerilogNets {
+    pub exprs: HashMap<String, String>,
+    pub copies: Vec<(String, String)>,
+    pub wires: Vec<VerilogDecl>,
+}
+
+pub fn verilog_nets(m: &ModuleNetlist,
+                    port_exprs: &HashMap<String, String>,
+                    used: &mut HashSet<String>)
+                    -> VerilogNets {
+    let mut exprs: HashMap<String, String> = HashMap::new();
+    let mut copies: Vec<(String, String)> = vec![];
+    let mut seen: HashSet<String> = HashSet::new();
+    for port in &m.ports {
+        let driven = port.direction.is_some() && port.direction != Some(In);
+        let bits: Vec<String> =
+            port.sig.bits().into_iter().filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None }).collect();
+        for (bit, net) in bits.iter().zip(port.nets.iter()) {
+            if !seen.insert(bit.clone()) {
+                continue;
+            }
+            let expr = port_exprs[bit].clone();
+            match (m.net_kind(net).and_then(tie_expr), exprs.get(net)) {
+                (Some(tie), _) if driven => copies.push((expr, tie.to_string())),
+                (Some(_), _) => {}
+                (None, Some(home)) if driven => copies.push((expr, home.clone())),
+                (None, Some(_)) => {}
+                (None, None) => {
+                    exprs.insert(net.clone(), expr);
+                }
+            }
+        }
+    }
+
+    let mut internal = vec![];
+    for net in &m.nets {
+        if exprs.contains_key(&net.name) {
+            continue;
+        }
+        match net.kind {
+            NetKind::Local => internal.push(net.name.clone()),
+            NetKind::Global => {
+                exprs.insert(net.name.clone(), format!("jade_globals.{}", verilog_ident(&net.name)));
+            }
+            _ => {}
+        }
+    }
+    let (wires, wire_exprs) = declare(&internal, used);
+    exprs.extend(wire_exprs);
+
+    VerilogNets { exprs, copies, wires }
+}
+
+pub fn module_to_verilog(m: &ModuleNetlist, netlists: &HashMap<String, ModuleNetlist>) -> E<String> {
+    let mut used = HashSet::new();
+    let (ports, port_exprs) = verilog_ports(m, &mut used);
+
+    let VerilogNets { exprs, copies, wires } = verilog_nets(m, &port_exprs, &mut used);
+
+    let net_expr = |net: &str| -> String {
+        match m.net_kind(net).and_then(tie_expr) {
+            Some(tie) => tie.to_string(),
+            None => exprs.get(net).cloned().unwrap_or_else(|| "1'bz".to_string()),
+        }
+    };
+
+    let name = module_name(&m.name);
+    let mut out = String::new();
+    out.push_str(&format!("// {}\n", m.name));
+    let decls: Vec<String> = ports.iter()
+                                  .map(|p| format!("{} wire {}{}", direction_str(p.direction), p.decl.range_str(), p.decl.ident))
+                                  .collect();
+    if decls.is_empty() {
+        out.push_str(&format!("module {} ();\n", name));
+    } else {
+        out.push_str(&format!("module {} ({});\n", name, decls.join(",\n    ")));
+    }
+    for w in &wires {
+        out.push_str(&format!("  wire {}{};\n", w.range_str(), w.ident));
+    }
+    for (to, from) in &copies {
+        out.push_str(&format!("  assign {} = {};\n", to, from));
+    }
+
+    for inst in &m.instances {
+        let label = verilog_ident(&format!("u_{}", inst.name));
+        let conn = |t: &str| -> String {
+            match inst.connections.iter().find(|(c, _)| c == t) {
+                Some((_, net)) => net_expr(net),
+                None => "1'bz".to_string(),
+            }
+        };
+
+        let kind = if inst.memory.is_some() { Some(GateKind::Memory) } else { gate_kind(&inst.module) };
+        if let Some(kind) = kind {
+            out.push_str(&device_statement(&label, kind, inst, &conn)?);
+            continue;
+        }
+
+        let child = match netlists.get(&inst.module) {
+            Some(child) => child,
+            None => return bailfmt!("No netlist for submodule {}", inst.module),
+        };
+        // named connections take whole ports, so bus bits are concatenated
+        // most significant first.
+        let (child_ports, _) = verilog_ports(child, &mut HashSet::new());
+        let mut assoc = vec![];
+        for port in &child_ports {
+            let bits: Vec<String> = match port.decl.range {
+                Some((hi, lo)) => {
+                    let base = split_bit(&port.decl.bits[0]).0;
+                    (lo..=hi).rev().map(|i| format!("{}[{}]", base, i)).collect()
+                }
+                None => port.decl.bits.clone(),
+            };
+            let actuals: Vec<Option<String>> =
+                bits.iter()
+                    .map(|bit| {
+                        let child_net = child.net(bit)?;
+                        inst.connections.iter().find(|(t, _)| child.net(t) == Some(child_net)).map(|(_, n)| net_expr(n))
+                    })
+                    .collect();
+            let actual = if actuals.iter().all(|a| a.is_none()) {
+                String::new()
+            } else if actuals.len() == 1 {
+                actuals[0].clone().unwrap()
+            } else {
+                let parts: Vec<String> = actuals.into_iter().map(|a| a.unwrap_or_else(|| "1'bz".to_string())).collect();
+                format!("{{{}}}", parts.join(", "))
+            };
+            assoc.push(format!(".{}({})", port.decl.ident, actual));
+        }
+        out.push_str(&format!("  {} {} ({});\n", module_name(&inst.module), label, assoc.join(",\n    ")));
+    }
+    out.push_str("endmodule\n");
+    Ok(out)
+}
+
+pub fn project_to_verilog(project: &Project, top: &str, config: &NetConfig) -> E<String> {
+    let (order, netlists) = hierarchy(project, top, config)?;
+
+    let mut out = String::new();
+    out.push_str(&format!("// structural Verilog for {}, generated by jade-parse\n\n", top));
+
+    let mut globals: Vec<&str> = vec![];
+    let mut dreg = false;
+    for name in &order {
+        for net in &netlists[name].nets {
+            if net.kind == NetKind::Global && !globals.contains(&net.name.as_str()) {
+                globals.push(&net.name);
+            }
+        }
+        dreg |= netlists[name].instances.iter().any(|i| gate_kind(&i.module) == Some(GateKind::Dreg));
+    }
+    // globals live in an uninstantiated top level module and are reached by
+    // hierarchical reference.
+    if !globals.is_empty() {
+        out.push_str("module jade_globals ();\n");
+        for g in &globals {
+            out.push_str(&format!("  wire {};\n", verilog_ident(g)));
+        }
+        out.push_str("endmodule\n\n");
+    }
+    if dreg {
+        out.push_str(DREG);
+        out.push_str("\n");
+    }
+
+    for name in &order {
+        out.push_str(&module_to_verilog(&netlists[name], &netlists)?);
+        out.push_str("\n");
+    }
+    Ok(out)
+}
+
+// delays are written in ns, the testbench's timescale unit.
+fn delay_ns(d: Duration) -> f64 {
+    match d {
+        Duration::Second(n) => n * 1e9,
+        Duration::MilliSecond(n) => n * 1e6,
+        Duration::MicroSecond(n) => n * 1e3,
+        Duration::NanoSecond(n) => n,
+        Duration::PicoSecond(n) => n * 1e-3,
+        Duration::FemptoSecond(n) => n * 1e-6,
+        Duration::AttoSecond(n) => n * 1e-9,
+    }
+}
+
+// test values as verilog bits: asserted '-' and X drive x, sampled '-'
+// is masked off by the care vector instead.
+fn verilog_bit(v: BinVal) -> char {
+    match v {
+        L => '0',
+        H => '1',
+        Z => 'z',
+        X | DontCare => 'x',
+    }
+}
+
+fn literal(values: &[BinVal]) -> String {
+    format!("{}'b{}", values.len(), values.iter().map(|v| verilog_bit(*v)).collect::<String>())
+}
+
+// A testbench for module `top`. Every asserted group is a reg vector wired
+// onto the port bits it names, every sampled group a wire vector gathered
+// from them, and each test line runs the .cycle actions as timed
+// statements. Mismatches print the vector index, its line and comment.
+// how the testbench reaches a test signal.
+enum TbSignal {
+    // a tb wire on a dut port.
+    Plain(String),
+    // a wire inside the dut or a global, by hierarchical name.
+    Forced(String),
+    // a net tied to vdd or ground.
+    Fixed(&'static str),
+}
+
+impl TbSignal {
+    fn expr(&self) -> &str {
+        match self {
+            TbSignal::Plain(e) | TbSignal::Forced(e) => e,
+            TbSignal::Fixed(e) => e,
+        }
+    }
+}
+
+// a Z lets go of a forced wire.
+fn force_statement(target: &str, v: BinVal) -> String {
+    match v {
+        Z => format!("    release {};\n", target),
+        _ => format!("    force {} = {};\n", target, literal(&[v])),
+    }
+}
+
+pub fn verilog_testbench(project: &Project, top: &str, config: &NetConfig, test: &ModTest) -> E<String> {
+    let m = ModuleNetlist::build(project, top, config)?;
+    // the dut's names, worked out the same way module_to_verilog does.
+    let mut used = HashSet::new();
+    let (ports, exprs) = verilog_ports(&m, &mut used);
+    let dut = verilog_nets(&m, &exprs, &mut used);
+
+    let mut net_exprs: HashMap<String, String> = HashMap::new();
+    for port in &ports {
+        for bit in &port.decl.bits {
+            if let Some(net) = m.net(bit) {
+                net_exprs.entry(net.to_string()).or_insert_with(|| exprs[bit].clone());
+            }
+        }
+    }
+    // wires inside the dut are hierarchical references, forced while driven.
+    let target = |bit: &str| -> E<TbSignal> {
+        if let Some(expr) = exprs.get(bit).or_else(|| m.net(bit).and_then(|net| net_exprs.get(net))) {
+            return Ok(TbSignal::Plain(expr.clone()));
+        }
+        let net = match m.net(bit) {
+            Some(net) => net,
+            None => return bailfmt!("Test signal {} is not a port or a wire of {}", bit, top),
+        };
+        match (m.net_kind(net).and_then(tie_expr), dut.exprs.get(net)) {
+            (Some(tie), _) => Ok(TbSignal::Fixed(tie)),
+            (None, Some(expr)) if expr.starts_with("jade_globals.") => Ok(TbSignal::Forced(expr.clone())),
+            (None, Some(expr)) => Ok(TbSignal::Forced(format!("dut.{}", expr))),
+            (None, None) => bailfmt!("Test signal {} has no wire in {}", bit, top),
+        }
+    };
+    let plain = |bit: &str| -> E<Option<String>> {
+        match target(bit)? {
+            TbSignal::Plain(e) => Ok(Some(e)),
+            TbSignal::Forced(_) => Ok(None),
+            TbSignal::Fixed(e) => bailfmt!("Test signal {} is tied to {} and can't be driven", bit, e),
+        }
+    };
+
+    // which groups are driven and which are sampled.
+    let (mut driven, mut sampled, mut set_bits) = (vec![], vec![], vec![]);
+    if let Some(CycleLine(actions, _)) = &test.cycle_line {
+        for action in actions {
+            match action {
+                Action::Assert(g) | Action::Deassert(g) if !driven.contains(g) => driven.push(g.clone()),
+                Action::Sample(g) if !sampled.contains(g) => sampled.push(g.clone()),
+                _ => {}
+            }
+        }
+    }
+    // single bits set by the .cycle line, .initial or .reset.
+    let cycles = test.cycles()?;
+    for cycle in &cycles {
+        for step in &cycle.steps {
+            if let Step::Set(bit, _) = step {
+                if !set_bits.contains(bit) {
+                    set_bits.push(bit.clone());
+                }
+            }
+        }
+    }
+    let comment_len = cycles.iter().filter_map(|c| c.comment.as_ref()).map(|c| c.len()).max().unwrap_or(0).max(1);
+
+    let name = module_name(top);
+    let tb = verilog_ident(&format!("{}_tb", name.trim().trim_start_matches('\\')));
+    let mut out = String::new();
+    out.push_str("`timescale 1ns / 1fs\n\n");
+    out.push_str(&format!("module {};\n", tb));
+    for port in &ports {
+        out.push_str(&format!("  wire {}{};\n", port.decl.range_str(), port.decl.ident));
+    }
+    out.push_str("  integer failures = 0;\n\n");
+
+    for g in &driven {
+        let bits = test.group_bits(g)?;
+        let reg = verilog_ident(&format!("drive_{}", g));
+        out.push_str(&format!("  reg [{}:0] {} = {{{}{{1'bz}}}};\n", bits.len() - 1, reg, bits.len()));
+        for (i, bit) in bits.iter().enumerate() {
+            if let Some(e) = plain(bit)? {
+                out.push_str(&format!("  assign {} = {}[{}];\n", e, reg, bits.len() - 1 - i));
+            }
+        }
+    }
+    for bit in &set_bits {
+        let reg = verilog_ident(&format!("set_{}", bit.replace(|c: char| !c.is_ascii_alphanumeric(), "_")));
+        out.push_str(&format!("  reg {} = 1'bz;\n", reg));
+        if let Some(e) = plain(bit)? {
+            out.push_str(&format!("  assign {} = {};\n", e, reg));
+        }
+    }
+    for g in &sampled {
+        let bits = test.group_bits(g)?;
+        let wire = verilog_ident(&format!("sample_{}", g));
+        let parts: Vec<String> = bits.iter().map(|b| target(b).map(|t| t.expr().to_string())).collect::<E<_>>()?;
+        out.push_str(&format!("  wire [{}:0] {} = {{{}}};\n", bits.len() - 1, wire, parts.join(", ")));
+        let task = verilog_ident(&format!("check_{}", g));
+        out.push_str(&format!("\n  task {};\n", task));
+        out.push_str(&format!("    input [{}:0] want, care;\n", bits.len() - 1));
+        out.push_str(&format!("    input integer vector, line;\n    input [{}:0] comment;\n", 8 * comment_len - 1));
+        out.push_str("    integer k, bad;\n    begin\n      bad = 0;\n");
+        out.push_str(&format!("      for (k = 0; k < {}; k = k + 1)\n", bits.len()));
+        out.push_str(&format!("        if (care[k] && {}[k] !== want[k]) bad = 1;\n", wire));
+        out.push_str("      if (bad) begin\n        failures = failures + 1;\n");
+        out.push_str(&format!("        $display(\"vector %0d (line %0d): {} expected %b got %b //%0s\", vector, line, want, {}, comment);\n",
+                              g, wire));
+        out.push_str("      end\n    end\n  endtask\n");
+    }
+
+    for d in &test.directives {
+        if let Directive::Verilog(line) = &d.directive {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+
+    out.push_str(&format!("\n  {} dut (", name));
+    let assoc: Vec<String> = ports.iter().map(|p| format!(".{}({})", p.decl.ident, p.decl.ident)).collect();
+    out.push_str(&assoc.join(",\n    "));
+    out.push_str(");\n\n  initial begin\n");
+    for (vector, cycle) in cycles.iter().enumerate() {
+        let comment = cycle.comment.clone().unwrap_or_default().replace("\\", "\\\\").replace("\"", "\\\"");
+        out.push_str(&format!("    // vector {}, line {}\n", vector + 1, cycle.line));
+        for step in &cycle.steps {
+            match step {
+                Step::Drive(g, values) => {
+                    let vals: Vec<BinVal> = values.iter().map(|(_, v)| *v).collect();
+                    out.push_str(&format!("    {} = {};\n", verilog_ident(&format!("drive_{}", g)), literal(&vals)));
+                    for (bit, v) in values {
+                        if let TbSignal::Forced(e) = target(bit)? {
+                            out.push_str(&force_statement(&e, *v));
+                        }
+                    }
+                }
+                Step::Release(g, bits) => {
+                    out.push_str(&format!("    {} = {{{}{{1'bz}}}};\n", verilog_ident(&format!("drive_{}", g)), bits.len()));
+                    for bit in bits {
+                        if let TbSignal::Forced(e) = target(bit)? {
+                            out.push_str(&force_statement(&e, Z));
+                        }
+                    }
+                }
+                Step::Set(bit, v) => {
+                    let reg = verilog_ident(&format!("set_{}", bit.replace(|c: char| !c.is_ascii_alphanumeric(), "_")));
+                    out.push_str(&format!("    {} = 1'b{};\n", reg, if *v { 1 } else { 0 }));
+                    if let TbSignal::Forced(e) = target(bit)? {
+                        out.push_str(&force_statement(&e, if *v { H } else { L }));
+                    }
+                }
+                Step::Wait(d) => out.push_str(&format!("    #{:?};\n", delay_ns(*d))),
+                Step::Check(g, values) => {
+                    let want: Vec<BinVal> = values.iter().map(|(_, v)| *v).collect();
+                    let care: Vec<BinVal> = want.iter().map(|v| if *v == DontCare { L } else { H }).collect();
+                    out.push_str(&format!("    {}({}, {}, {}, {}, \"{}\");\n",
+                                          verilog_ident(&format!("check_{}", g)),
+                                          literal(&want),
+                                          literal(&care),
+                                          vector + 1,
+                                          cycle.line,
+                                          comment));
+                }
+            }
+        }
+    }
+    out.push_str("    if (failures == 0) $display(\"all tests passed\");\n");
+    out.push_str("    else $display(\"FAILED: %0d vectors\", failures);\n");
+    out.push_str("    $finish;\n  end\nendmodule\n");
+    Ok(out)
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::{each_saved_test, project};
+    use std::path::Path;
+
+    #[test]
+    fn idents() {
+        assert_eq!(verilog_ident("vout"), "vout");
+        assert_eq!(verilog_ident("_N1"), "_N1");
+        assert_eq!(verilog_ident("output"), "\\output ");
+        assert_eq!(verilog_ident("A.b"), "\\A.b ");
+        assert_eq!(module_name("/user/And41"), "mod_user_And41");
+    }
+
+    #[test]
+    fn and2_module() {
+        let p = project("use-and2.json");
+        let v = project_to_verilog(&p, "/user/UseAND2", &NetConfig::new()).unwrap();
+        assert!(v.contains("module mod_user_AND2 (input wire in1,\n    input wire in2,\n    output wire out);"));
+        assert!(v.contains("  and u_and2_0 (out, in1, in2);"));
+        assert!(v.find("module mod_user_AND2").unwrap() < v.find("module mod_user_UseAND2").unwrap());
+        assert!(v.contains("  mod_user_AND2 u_AND2_0 (.in1(_N1),\n    .in2(_N2),\n    .out(_N3));"));
+    }
+
+    #[test]
+    fn bus_ports() {
+        let p = project("Mux4Rep1.json");
+        let v = project_to_verilog(&p, "/user/Mux4Rep1", &NetConfig::new()).unwrap();
+        assert!(v.contains("output wire [31:0] Y"));
+        assert!(v.contains("assign Y[1] = A[1] ? (B[1] ? D[3] : D[2]) : (B[1] ? D[1] : D[0]);"));
+    }
+
+    #[test]
+    fn constants() {
+        let p = project("Constant1.json");
+        let v = project_to_verilog(&p, "/user/Constant1", &NetConfig::new()).unwrap();
+        assert!(v.contains("1'b1, in1);"));
+    }
+
+    #[test]
+    fn memory() {
+        let p = project("MemUnit1.json");
+        let v = project_to_verilog(&p, "/user/MemUnit1", &NetConfig::new()).unwrap();
+        assert!(v.contains("  reg [0:0] u_Mem1_mem [0:1];"));
+        assert!(v.contains("    u_Mem1_mem[1] = 1'h1;"));
+        assert!(v.contains("  assign d = oe ? u_Mem1_mem[addr] : 1'bz;"));
+    }
+
+    #[test]
+    fn submodule_bus() {
+        let p = project("GarrInc32.json");
+        let v = project_to_verilog(&p, "/user/GarrInc32", &NetConfig::new()).unwrap();
+        assert!(v.contains("module mod_user_GarrInc4"));
+        assert!(v.contains(".A({"));
+    }
+
+    #[test]
+    fn testbench1() {
+        let p = project("use-and2.json");
+        let src = ".group inputs in1 in2\n.group outputs out\n.cycle assert inputs tran 99n sample outputs tran 1n\n\
+                   00 L\n11 - // both\n1Z H\n";
+        let test = ModTest::from_str(src).unwrap();
+        let tb = verilog_testbench(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(tb.contains("module mod_user_AND2_tb;"));
+        assert!(tb.contains("  reg [1:0] drive_inputs = {2{1'bz}};\n  assign in1 = drive_inputs[1];\n"));
+        assert!(tb.contains("  wire [0:0] sample_outputs = {out};"));
+        assert!(tb.contains("    // vector 2, line 5\n    drive_inputs = 2'b11;\n    #99.0;\n"));
+        assert!(tb.contains("    check_outputs(1'bx, 1'b0, 2, 5, \" both\");"));
+        assert!(tb.contains("    drive_inputs = 2'b1z;"));
+    }
+
+    #[test]
+    fn testbench2() {
+        // And41 has no ports, its test drives and samples labelled wires.
+        let p = project("And41.json");
+        let test = p.test("/user/And41", "test").unwrap();
+        let tb = verilog_testbench(&p, "/user/And41", &NetConfig::new(), test).unwrap();
+        assert!(tb.contains("  wire [0:0] sample_outputs = {dut.vout};\n"));
+        assert!(tb.contains("    drive_inputs = 4'b0000;\n    force dut.A = 1'b0;\n"));
+        assert!(!tb.contains("assign dut."));
+
+        let p = project("Buffer4.json");
+        let test = p.test("/user/Buffer4", "test").unwrap();
+        let tb = verilog_testbench(&p, "/user/Buffer4", &NetConfig::new(), test).unwrap();
+        assert!(tb.contains("    force dut.A[3] = 1'b0;\n"));
+
+        // a forced wire is released when the test lets go of it.
+        let src = ".group inputs A B\n.group outputs vout\n\
+                   .cycle assert inputs tran 9n deassert inputs sample outputs\n1Z H\n";
+        let test = ModTest::from_str(src).unwrap();
+        let tb = verilog_testbench(&project("And41.json"), "/user/And41", &NetConfig::new(), &test).unwrap();
+        assert!(tb.contains("    force dut.A = 1'b1;\n    release dut.B;\n"));
+        assert!(tb.contains("    drive_inputs = {2{1'bz}};\n    release dut.A;\n    release dut.B;\n"));
+    }
+
+    #[test]
+    fn saved_testbenches() {
+        // every saved test gets a testbench, wires inside the dut included.
+        let made = each_saved_test(|file, p, m, test| {
+            if let Err(b) = verilog_testbench(p, &m.name, &NetConfig::new(), test) {
+                panic!("{} {}: {:?}", file, m.name, b);
+            }
+        });
+        assert_eq!(made, 163);
+    }
+}
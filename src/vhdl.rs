@@ -0,0 +1,783 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cycle::*;
+use crate::gates::*;
+use crate::netlist::*;
+use crate::types::*;
+
+// Structural VHDL for a module hierarchy, one entity/architecture pair per
+// jade module, children first so the file analyzes top to bottom:
+//
+//   ghdl -a --std=08 design.vhdl
+//
+// Buses become std_logic_vector ports, submodules become entity
+// instantiations and /gates/* become concurrent statements.
+
+const RESERVED: &[&str] = &["abs", "access", "after", "alias", "all", "and", "architecture", "array", "assert",
+                            "attribute", "begin", "block", "body", "buffer", "bus", "case", "component",
+                            "configuration", "constant", "disconnect", "downto", "else", "elsif", "end", "entity",
+                            "exit", "file", "for", "function", "generate", "generic", "group", "guarded", "if",
+                            "impure", "in", "inertial", "inout", "is", "label", "library", "linkage", "literal",
+                            "loop", "map", "mod", "nand", "new", "next", "nor", "not", "null", "of", "on", "open",
+                            "or", "others", "out", "package", "port", "postponed", "procedure", "process", "pure",
+                            "range", "record", "register", "reject", "rem", "report", "return", "rol", "ror",
+                            "select", "severity", "signal", "shared", "sla", "sll", "sra", "srl", "subtype", "then",
+                            "to", "transport", "type", "unaffected", "units", "until", "use", "variable", "wait",
+                            "when", "while", "with", "xnor", "xor"];
+
+fn is_plain_ident(name: &str) -> bool {
+    let pat = regex::Regex::new(r#"^[A-Za-z](_?[A-Za-z0-9])*$"#).unwrap();
+    pat.is_match(name) && !RESERVED.contains(&name.to_lowercase().as_str())
+}
+
+// jade names may not be legal VHDL, those become extended identifiers.
+pub fn vhdl_ident(name: &str) -> String {
+    if is_plain_ident(name) { name.to_string() } else { format!("\\{}\\", name.replace("\\", "\\\\")) }
+}
+
+// "/user/And41" -> mod_user_And41
+pub fn entity_name(module: &str) -> String {
+    let mut name = String::from("mod");
+    for c in module.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if !(c == '_' && name.ends_with('_')) {
+            name.push(c);
+        }
+    }
+    vhdl_ident(name.trim_end_matches('_'))
+}
+
+// a declaration per bit group, A[3] .. A[0] become one std_logic_vector.
+#[derive(Debug, PartialEq)]
+pub struct VhdlDecl {
+    pub ident: String,
+    pub range: Option<(i32, i32)>,
+    pub bits: Vec<String>,
+}
+
+impl VhdlDecl {
+    pub fn vhdl_type(&self) -> String {
+        match self.range {
+            Some((hi, lo)) => format!("std_logic_vector({} downto {})", hi, lo),
+            None => "std_logic".to_string(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        match self.range {
+            Some((hi, lo)) => (hi - lo + 1) as usize,
+            None => 1,
+        }
+    }
+}
+
+pub fn declare(bits: &[String], used: &mut HashSet<String>) -> (Vec<VhdlDecl>, HashMap<String, String>) {
+    // VHDL is case insensitive, so a second "a" next to "A" has to be extended.
+    let mut unique = |name: &str| {
+        let mut ident = vhdl_ident(name);
+        if !ident.starts_with('\\') && used.contains(&ident.to_lowercase()) {
+            ident = format!("\\{}\\", name);
+        }
+        used.insert(if ident.starts_with('\\') { ident.clone() } else { ident.to_lowercase() });
+        ident
+    };
+
+    let mut decls = vec![];
+    let mut exprs = HashMap::new();
+    for group in group_bits(bits) {
+        let ident = unique(&group.base);
+        for bit in &group.bits {
+            let expr = match (group.range, split_bit(bit)) {
+                (Some(_), (_, Some(idx))) => format!("{}({})", ident, idx),
+                _ => ident.clone(),
+            };
+            exprs.insert(bit.clone(), expr);
+        }
+        decls.push(VhdlDecl { ident, range: group.range, bits: group.bits });
+    }
+    (decls, exprs)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VhdlPort {
+    pub decl: VhdlDecl,
+    pub direction: Direction,
+}
+
+fn direction_str(dir: Direction) -> &'static str {
+    match dir {
+        In => "in",
+        Out => "out",
+        InOut => "inout",
+    }
+}
+
+// the entity's ports, the bit names of every schematic port grouped by base
+// name, plus which port bit each net is reached through.
+pub fn vhdl_ports(m: &ModuleNetlist, used: &mut HashSet<String>) -> (Vec<VhdlPort>, HashMap<String, String>) {
+    let mut bits = vec![];
+    let mut dirs: HashMap<String, Direction> = HashMap::new();
+    for port in &m.ports {
+        for bit in port.sig.bits() {
+            if let Bit::Name(name) = bit {
+                dirs.entry(name.clone()).or_insert(port.direction.unwrap_or(In));
+                bits.push(name);
+            }
+        }
+    }
+    let (decls, exprs) = declare(&bits, used);
+    let ports = decls.into_iter().map(|decl| VhdlPort { direction: dirs[&decl.bits[0]], decl }).collect();
+    (ports, exprs)
+}
+
+fn tie_expr(kind: NetKind) -> Option<&'static str> {
+    match kind {
+        NetKind::TieHigh => Some("'1'"),
+        NetKind::TieLow => Some("'0'"),
+        _ => None,
+    }
+}
+
+fn gate_expr(kind: GateKind, ins: &[String]) -> String {
+    let join = |op: &str| ins.join(&format!(" {} ", op));
+    match kind {
+        GateKind::And => join("and"),
+        GateKind::Or => join("or"),
+        GateKind::Xor => join("xor"),
+        GateKind::Nand => format!("not ({})", join("and")),
+        GateKind::Nor => format!("not ({})", join("or")),
+        GateKind::Xnor => format!("not ({})", join("xor")),
+        GateKind::Inverter => format!("not {}", ins[0]),
+        _ => ins[0].clone(),
+    }
+}
+
+fn std_logic_literal(word: u64, width: u32) -> String {
+    let bits: String = (0..width).rev().map(|i| if (word >> i.min(63)) & 1 == 1 { '1' } else { '0' }).collect();
+    format!("\"{}\"", bits)
+}
+
+fn memory_block(label: &str, mem: &Memory, inst: &Instance, net: &dyn Fn(&str) -> String) -> E<String> {
+    let words = mem.words()?;
+    let size = 1usize << mem.naddr.min(20);
+    let bus = |name: &str, width: u32| -> String {
+        if width == 1 {
+            net(name)
+        } else {
+            (0..width).rev().map(|i| net(&format!("{}[{}]", name, i))).collect::<Vec<String>>().join(" & ")
+        }
+    };
+
+    let mut out = String::new();
+    out.push_str(&format!("  {} : block\n", label));
+    out.push_str(&format!("    type mem_t is array (0 to {}) of std_logic_vector({} downto 0);\n",
+                          size - 1,
+                          mem.ndata - 1));
+    let init: Vec<String> = words.iter()
+                                 .take(size)
+                                 .enumerate()
+                                 .map(|(i, w)| format!("{} => {}", i, std_logic_literal(*w, mem.ndata)))
+                                 .collect();
+    let others = format!("others => (others => '0')");
+    let init = if init.is_empty() { others } else { format!("{}, {}", init.join(", "), others) };
+    out.push_str(&format!("    signal mem : mem_t := ({});\n", init));
+    out.push_str("  begin\n");
+
+    // one process does every port's writes, in port order, so mem has a
+    // single driver and the last port to write an address wins.
+    let mut clocks: Vec<String> = vec![];
+    let mut writes = String::new();
+    let mut reads = String::new();
+    for p in 0..mem.nports {
+        let addr = bus(&format!("A{}", p), mem.naddr);
+        let addr = if mem.naddr == 1 { format!("(0 => {})", addr) } else { format!("({})", addr) };
+        let (oe, we, clk) = (net(&format!("OE{}", p)), net(&format!("WE{}", p)), net(&format!("CLK{}", p)));
+        let data: Vec<String> = if mem.ndata == 1 {
+            vec![net(&format!("D{}", p))]
+        } else {
+            (0..mem.ndata).rev().map(|i| net(&format!("D{}[{}]", p, i))).collect()
+        };
+        let index = format!("to_integer(unsigned(std_logic_vector'{}))", addr);
+
+        writes.push_str(&format!("      if rising_edge({}) and {} = '1' then\n", clk, we));
+        let d = if data.len() == 1 { format!("(0 => {})", data[0]) } else { format!("({})", data.join(" & ")) };
+        writes.push_str(&format!("        mem({}) <= {};\n", index, d));
+        writes.push_str("      end if;\n");
+        if !clocks.contains(&clk) {
+            clocks.push(clk);
+        }
+
+        for (i, d) in data.iter().enumerate() {
+            let bit = mem.ndata as usize - 1 - i;
+            reads.push_str(&format!("    {} <= mem({})({}) when {} = '1' else 'Z';\n", d, index, bit, oe));
+        }
+    }
+    if mem.nports > 0 {
+        out.push_str(&format!("    write : process ({}) is\n    begin\n", clocks.join(", ")));
+        out.push_str(&writes);
+        out.push_str("    end process;\n");
+    }
+    out.push_str(&reads);
+    out.push_str(&format!("  end block {};\n", label));
+    Ok(out)
+}
+
+fn device_statement(label: &str, kind: GateKind, inst: &Instance, net: &dyn Fn(&str) -> String) -> E<String> {
+    let term = |t: &str| net(t);
+    Ok(match kind {
+        GateKind::Dreg => {
+            let (d, clk, q) = (term("D"), term("CLK"), term("Q"));
+            format!("  {} : process ({}) is\n  begin\n    if rising_edge({}) then\n      {} <= {};\n    end if;\n  end process;\n",
+                    label, clk, clk, q, d)
+        }
+        GateKind::Tristate => format!("  {} : {} <= {} when {} = '1' else 'Z';\n", label, term("Z"), term("A"), term("E")),
+        GateKind::Mux2 => format!("  {} : {} <= {} when {} = '1' else {};\n",
+                                  label,
+                                  term("Y"),
+                                  term("D1"),
+                                  term("S"),
+                                  term("D0")),
+        GateKind::Mux4 => {
+            let (s1, s0) = (term("S[1]"), term("S[0]"));
+            format!("  {} : {} <= {} when {} = '0' and {} = '0' else\n    {} when {} = '0' else\n    {} when {} = '0' else\n    {};\n",
+                    label, term("Y"), term("D0"), s1, s0, term("D1"), s1, term("D2"), s0, term("D3"))
+        }
+        GateKind::Memory => match &inst.memory {
+            Some(mem) => memory_block(label, mem, inst, net)?,
+            None => return bailfmt!("memory instance {} has no contents", label),
+        },
+        _ => {
+            let ins: Vec<String> = ["A", "B", "C", "D"].iter()
+                                                       .filter(|t| inst.connections.iter().any(|(c, _)| c == *t))
+                                                       .map(|t| term(t))
+                                                       .collect();
+            format!("  {} : {} <= {};\n", label, term("Z"), gate_expr(kind, &ins))
+        }
+    })
+}
+
+// how an architecture reaches each of its nets: through the first port bit
+// on it, a signal of its own, or the global's package signal. Further port
+// bits on a net are copies of it.
+pub struct VhdlNets {
+    pub exprs: HashMap<String, String>,
+    pub copies: Vec<(String, String)>,
+    pub signals: Vec<VhdlDecl>,
+    pub globals: Vec<String>,
+}
+
+pub fn vhdl_nets(m: &ModuleNetlist, port_exprs: &HashMap<String, String>, used: &mut HashSet<String>) -> VhdlNets {
+    let mut exprs: HashMap<String, String> = HashMap::new();
+    let mut copies: Vec<(String, String)> = vec![];
+    let mut seen: HashSet<String> = HashSet::new();
+    for port in &m.ports {
+        let bits: Vec<String> =
+            port.sig.bits().into_iter().filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None }).collect();
+        for (bit, net) in bits.iter().zip(port.nets.iter()) {
+            if !seen.insert(bit.clone()) {
+                continue;
+            }
+            let expr = port_exprs[bit].clone();
+            let kind = m.net_kind(net).unwrap_or(NetKind::Local);
+            if let Some(tie) = tie_expr(kind) {
+                if port.direction != Some(In) && port.direction.is_some() {
+                    copies.push((expr, tie.to_string()));
+                }
+                continue;
+            }
+            match exprs.get(net) {
+                Some(home) if port.direction != Some(In) && port.direction.is_some() => {
+                    copies.push((expr, home.clone()))
+                }
+                Some(_) => {}
+                None => {
+                    exprs.insert(net.clone(), expr);
+                }
+            }
+        }
+    }
+
+    let mut internal = vec![];
+    let mut globals = vec![];
+    for net in &m.nets {
+        if exprs.contains_key(&net.name) {
+            continue;
+        }
+        match net.kind {
+            NetKind::Local => internal.push(net.name.clone()),
+            NetKind::Global => globals.push(net.name.clone()),
+            _ => {}
+        }
+    }
+    let (signals, signal_exprs) = declare(&internal, used);
+    exprs.extend(signal_exprs);
+    for g in &globals {
+        exprs.insert(g.clone(), vhdl_ident(&format!("global_{}", g)));
+    }
+
+    VhdlNets { exprs, copies, signals, globals }
+}
+
+pub fn module_to_vhdl(m: &ModuleNetlist, netlists: &HashMap<String, ModuleNetlist>) -> E<String> {
+    let mut used = HashSet::new();
+    let (ports, port_exprs) = vhdl_ports(m, &mut used);
+
+    let VhdlNets { exprs, copies, signals, globals } = vhdl_nets(m, &port_exprs, &mut used);
+
+    let net_expr = |net: &str| -> String {
+        match m.net_kind(net).and_then(tie_expr) {
+            Some(tie) => tie.to_string(),
+            None => exprs.get(net).cloned().unwrap_or_else(|| "open".to_string()),
+        }
+    };
+
+    let entity = entity_name(&m.name);
+    let mut out = String::new();
+    out.push_str(&format!("-- {} {}\n", m.name, "-".repeat(70usize.saturating_sub(m.name.len()))));
+    out.push_str("library ieee;\nuse ieee.std_logic_1164.all;\nuse ieee.numeric_std.all;\n");
+    if !globals.is_empty() {
+        out.push_str("use work.jade_globals.all;\n");
+    }
+    out.push_str(&format!("\nentity {} is\n", entity));
+    if !ports.is_empty() {
+        let decls: Vec<String> = ports.iter()
+                                      .map(|p| {
+                                          format!("{} : {} {}", p.decl.ident, direction_str(p.direction), p.decl.vhdl_type())
+                                      })
+                                      .collect();
+        out.push_str(&format!("  port ({});\n", decls.join(";\n        ")));
+    }
+    out.push_str(&format!("end {};\n\n", entity));
+
+    out.push_str(&format!("architecture struct of {} is\n", entity));
+    for s in &signals {
+        out.push_str(&format!("  signal {} : {};\n", s.ident, s.vhdl_type()));
+    }
+    out.push_str("begin\n");
+
+    for (to, from) in &copies {
+        out.push_str(&format!("  {} <= {};\n", to, from));
+    }
+
+    for inst in &m.instances {
+        let label = vhdl_ident(&format!("u_{}", inst.name));
+        let conn = |t: &str| -> String {
+            match inst.connections.iter().find(|(c, _)| c == t) {
+                Some((_, net)) => net_expr(net),
+                None => "open".to_string(),
+            }
+        };
+
+        let kind = if inst.memory.is_some() { Some(GateKind::Memory) } else { gate_kind(&inst.module) };
+        if let Some(kind) = kind {
+            out.push_str(&device_statement(&label, kind, inst, &conn)?);
+            continue;
+        }
+
+        let child = match netlists.get(&inst.module) {
+            Some(child) => child,
+            None => return bailfmt!("No netlist for submodule {}", inst.module),
+        };
+        let (child_ports, child_exprs) = vhdl_ports(child, &mut HashSet::new());
+        let mut assoc = vec![];
+        for port in &child_ports {
+            for bit in &port.decl.bits {
+                let child_net = child.net(bit);
+                let actual = inst.connections
+                                 .iter()
+                                 .find(|(t, _)| child_net.is_some() && child.net(t) == child_net)
+                                 .map(|(_, n)| net_expr(n));
+                match actual {
+                    Some(actual) => assoc.push(format!("{} => {}", child_exprs[bit], actual)),
+                    None if port.direction == In => assoc.push(format!("{} => 'Z'", child_exprs[bit])),
+                    None => assoc.push(format!("{} => open", child_exprs[bit])),
+                }
+            }
+        }
+        out.push_str(&format!("  {} : entity work.{}", label, entity_name(&inst.module)));
+        if !assoc.is_empty() {
+            out.push_str(&format!("\n    port map ({})", assoc.join(",\n              ")));
+        }
+        out.push_str(";\n");
+    }
+    out.push_str("end struct;\n");
+    Ok(out)
+}
+
+pub fn project_to_vhdl(project: &Project, top: &str, config: &NetConfig) -> E<String> {
+    let (order, netlists) = hierarchy(project, top, config)?;
+
+    let mut out = String::new();
+    out.push_str(&format!("-- structural VHDL for {}, generated by jade-parse\n\n", top));
+
+    let mut globals: Vec<&str> = vec![];
+    for name in &order {
+        for net in &netlists[name].nets {
+            if net.kind == NetKind::Global && !globals.contains(&net.name.as_str()) {
+                globals.push(&net.name);
+            }
+        }
+    }
+    if !globals.is_empty() {
+        out.push_str("library ieee;\nuse ieee.std_logic_1164.all;\n\npackage jade_globals is\n");
+        for g in &globals {
+            out.push_str(&format!("  signal {} : std_logic;\n", vhdl_ident(&format!("global_{}", g))));
+        }
+        out.push_str("end package jade_globals;\n\n");
+    }
+
+    for name in &order {
+        out.push_str(&module_to_vhdl(&netlists[name], &netlists)?);
+        out.push_str("\n");
+    }
+    Ok(out)
+}
+
+// an integer count of fs, "1e-7 ns" or "0.35 ns" aren't VHDL literals. a
+// wait shorter than that would quietly become no wait at all.
+fn vhdl_time(d: Duration) -> E<String> {
+    let fs = d.femtoseconds();
+    if fs == 0 && d.seconds() > 0.0 {
+        return bailfmt!("Time {} is under 1 fs, the finest VHDL time here", d);
+    }
+    Ok(format!("{} fs", fs))
+}
+
+fn std_logic_value(v: BinVal) -> &'static str {
+    match v {
+        L => "'0'",
+        H => "'1'",
+        Z => "'Z'",
+        X | DontCare => "'X'",
+    }
+}
+
+// a VHDL string literal, the comment is the only free text we quote.
+fn vhdl_string(s: &str) -> String {
+    format!("\"{}\"", s.replace("\"", "\"\""))
+}
+
+// A self checking testbench for module `top`: each test line drives its
+// asserted groups, waits out the tran actions and reports every sampled
+// bit that differs, tagged with the test's line number and comment. A
+// failing run ends with a severity failure so ghdl exits non-zero.
+// how the testbench reaches a test signal.
+enum TbSignal {
+    // a tb signal on a dut port, or a global's package signal.
+    Plain(String),
+    // a wire inside the dut, through an external name alias.
+    Forced(String),
+    // a net tied to vdd or ground.
+    Fixed(&'static str),
+}
+
+impl TbSignal {
+    fn expr(&self) -> &str {
+        match self {
+            TbSignal::Plain(e) | TbSignal::Forced(e) => e,
+            TbSignal::Fixed(e) => e,
+        }
+    }
+}
+
+// a wire inside the dut is forced and released, the test's other signals
+// are driven like any port.
+fn drive_statement(bit: &str, sig: TbSignal, value: &str) -> E<String> {
+    Ok(match sig {
+        TbSignal::Plain(e) => format!("    {} <= {};\n", e, value),
+        TbSignal::Forced(e) if value == "'Z'" => format!("    {} <= release;\n", e),
+        TbSignal::Forced(e) => format!("    {} <= force {};\n", e, value),
+        TbSignal::Fixed(_) if value == "'Z'" => String::new(),
+        TbSignal::Fixed(e) => return bailfmt!("Test signal {} is tied to {} and can't be driven", bit, e),
+    })
+}
+
+pub fn vhdl_testbench(project: &Project, top: &str, config: &NetConfig, test: &ModTest) -> E<String> {
+    let m = ModuleNetlist::build(project, top, config)?;
+    // the dut's names, worked out the same way module_to_vhdl does.
+    let mut used = HashSet::new();
+    let (ports, exprs) = vhdl_ports(&m, &mut used);
+    let dut = vhdl_nets(&m, &exprs, &mut used);
+
+    // test signals may name a port bit directly or any label on its net.
+    let mut net_exprs: HashMap<String, String> = HashMap::new();
+    for port in &ports {
+        for bit in &port.decl.bits {
+            if let Some(net) = m.net(bit) {
+                net_exprs.entry(net.to_string()).or_insert_with(|| exprs[bit].clone());
+            }
+        }
+    }
+
+    // labelled wires inside the dut are reached through VHDL-2008 external
+    // names, aliased in the stimulus process and forced while driven.
+    let mut taken: HashSet<String> = ports.iter().map(|p| p.decl.ident.to_lowercase()).collect();
+    taken.extend(["dut", "stimulus", "failures"].iter().map(|s| s.to_string()));
+    let mut aliases: Vec<(String, &VhdlDecl)> = vec![];
+    let mut uses_globals = false;
+    let mut target = |bit: &str| -> E<TbSignal> {
+        if let Some(expr) = exprs.get(bit).or_else(|| m.net(bit).and_then(|net| net_exprs.get(net))) {
+            return Ok(TbSignal::Plain(expr.clone()));
+        }
+        let net = match m.net(bit) {
+            Some(net) => net,
+            None => return bailfmt!("Test signal {} is not a port or a wire of {}", bit, top),
+        };
+        match m.net_kind(net) {
+            Some(kind @ NetKind::TieHigh) | Some(kind @ NetKind::TieLow) => {
+                Ok(TbSignal::Fixed(tie_expr(kind).unwrap()))
+            }
+            Some(NetKind::Global) => {
+                uses_globals = true;
+                Ok(TbSignal::Plain(dut.exprs[net].clone()))
+            }
+            _ => {
+                let decl = match dut.signals.iter().find(|d| d.bits.iter().any(|b| b == net)) {
+                    Some(decl) => decl,
+                    None => return bailfmt!("Test signal {} has no signal in {}", bit, top),
+                };
+                let alias = match aliases.iter().find(|(_, d)| d.ident == decl.ident) {
+                    Some((alias, _)) => alias.clone(),
+                    None => {
+                        let base = format!("dut_{}", decl.ident.trim_matches('\\'));
+                        let mut alias = vhdl_ident(&base);
+                        while taken.contains(&alias.to_lowercase()) {
+                            alias = vhdl_ident(&format!("{}_{}", base, taken.len()));
+                        }
+                        taken.insert(alias.to_lowercase());
+                        aliases.push((alias.clone(), decl));
+                        alias
+                    }
+                };
+                // A(3) in the dut is dut_A(3) here.
+                Ok(TbSignal::Forced(format!("{}{}", alias, &dut.exprs[net][decl.ident.len()..])))
+            }
+        }
+    };
+
+    let mut body = String::new();
+    for cycle in test.cycles()? {
+        let comment = match &cycle.comment {
+            Some(c) => format!(" //{}", c),
+            None => String::new(),
+        };
+        body.push_str(&format!("    -- line {}{}\n", cycle.line, comment));
+        for step in &cycle.steps {
+            match step {
+                Step::Drive(_, values) => {
+                    for (bit, v) in values {
+                        body.push_str(&drive_statement(bit, target(bit)?, std_logic_value(*v))?);
+                    }
+                }
+                Step::Release(_, bits) => {
+                    for bit in bits {
+                        body.push_str(&drive_statement(bit, target(bit)?, "'Z'")?);
+                    }
+                }
+                Step::Set(bit, v) => {
+                    body.push_str(&drive_statement(bit, target(bit)?, if *v { "'1'" } else { "'0'" })?);
+                }
+                Step::Wait(d) => body.push_str(&format!("    wait for {};\n", vhdl_time(*d)?)),
+                Step::Check(_, values) => {
+                    for (bit, v) in values {
+                        if *v == DontCare {
+                            continue;
+                        }
+                        let sig = target(bit)?;
+                        let what = format!("line {}: {} expected {} got ", cycle.line, bit, std_logic_value(*v));
+                        body.push_str(&format!("    if {} /= {} then\n", sig.expr(), std_logic_value(*v)));
+                        body.push_str(&format!("      report {} & std_logic'image({}) & {} severity error;\n",
+                                               vhdl_string(&what),
+                                               sig.expr(),
+                                               vhdl_string(&comment)));
+                        body.push_str("      failures := failures + 1;\n    end if;\n");
+                    }
+                }
+            }
+        }
+    }
+
+    let entity = entity_name(top);
+    let tb = vhdl_ident(&format!("{}_tb", entity.trim_matches('\\')));
+    let mut out = String::new();
+    out.push_str("library ieee;\nuse ieee.std_logic_1164.all;\n");
+    if uses_globals {
+        out.push_str("use work.jade_globals.all;\n");
+    }
+    out.push_str(&format!("\nentity {} is\nend {};\n\n", tb, tb));
+    out.push_str(&format!("architecture behaviour of {} is\n", tb));
+    for port in &ports {
+        out.push_str(&format!("  signal {} : {};\n", port.decl.ident, port.decl.vhdl_type()));
+    }
+    out.push_str("begin\n");
+    out.push_str(&format!("  dut : entity work.{}", entity));
+    if !ports.is_empty() {
+        let assoc: Vec<String> = ports.iter().map(|p| format!("{} => {}", p.decl.ident, p.decl.ident)).collect();
+        out.push_str(&format!("\n    port map ({})", assoc.join(",\n              ")));
+    }
+    out.push_str(";\n\n");
+
+    out.push_str("  stimulus : process is\n    variable failures : natural := 0;\n");
+    for (alias, decl) in &aliases {
+        out.push_str(&format!("    alias {} is <<signal dut.{} : {}>>;\n", alias, decl.ident, decl.vhdl_type()));
+    }
+    out.push_str("  begin\n");
+    out.push_str(&body);
+    out.push_str("    assert failures = 0 report integer'image(failures) & \" failed checks\" severity failure;\n");
+    out.push_str("    report \"all tests passed\";\n    wait;\n  end process;\nend behaviour;\n");
+    Ok(out)
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::{each_saved_test, project};
+    use std::path::Path;
+
+    #[test]
+    fn idents() {
+        assert_eq!(vhdl_ident("vout"), "vout");
+        assert_eq!(vhdl_ident("out"), "\\out\\");
+        assert_eq!(vhdl_ident("_N1"), "\\_N1\\");
+        assert_eq!(vhdl_ident("a__b"), "\\a__b\\");
+        assert_eq!(entity_name("/user/And41"), "mod_user_And41");
+    }
+
+    #[test]
+    fn declare1() {
+        let bits: Vec<String> = vec!["A[3]", "A[2]", "A[1]", "A[0]", "B", "a"].iter().map(|s| s.to_string()).collect();
+        let (decls, exprs) = declare(&bits, &mut HashSet::new());
+        assert_eq!(decls.len(), 3);
+        assert_eq!(decls[0].vhdl_type(), "std_logic_vector(3 downto 0)");
+        assert_eq!(exprs["A[2]"], "A(2)");
+        assert_eq!(exprs["a"], "\\a\\");
+    }
+
+    #[test]
+    fn and2_entity() {
+        let p = project("use-and2.json");
+        let vhdl = project_to_vhdl(&p, "/user/UseAND2", &NetConfig::new()).unwrap();
+        assert!(vhdl.contains("entity mod_user_AND2 is\n  port (in1 : in std_logic;\n        in2 : in std_logic;\n        out : out std_logic);"
+                    .replace("out : out", "\\out\\ : out")
+                    .as_str()));
+        assert!(vhdl.contains("u_and2_0 : \\out\\ <= in1 and in2;"));
+        // the child is analyzed before its parent.
+        assert!(vhdl.find("entity mod_user_AND2").unwrap() < vhdl.find("entity mod_user_UseAND2").unwrap());
+        assert!(vhdl.contains("u_AND2_0 : entity work.mod_user_AND2"));
+    }
+
+    #[test]
+    fn bus_ports() {
+        let p = project("Mux4Rep1.json");
+        let vhdl = project_to_vhdl(&p, "/user/Mux4Rep1", &NetConfig::new()).unwrap();
+        assert!(vhdl.contains("Y : out std_logic_vector(31 downto 0)"));
+        assert!(vhdl.contains("Y(1) <= D(0) when A(1) = '0' and B(1) = '0' else"));
+    }
+
+    #[test]
+    fn ties_and_copies() {
+        let p = project("Constant1.json");
+        let vhdl = project_to_vhdl(&p, "/user/Constant1", &NetConfig::new()).unwrap();
+        assert!(vhdl.contains("<= '1' and in1;"));
+
+        let p = project("Jumper5.json");
+        let vhdl = project_to_vhdl(&p, "/user/Jumper5", &NetConfig::new()).unwrap();
+        assert!(vhdl.contains("signal A : std_logic;"));
+    }
+
+    #[test]
+    fn memory() {
+        let p = project("MemUnit1.json");
+        let vhdl = project_to_vhdl(&p, "/user/MemUnit1", &NetConfig::new()).unwrap();
+        assert!(vhdl.contains("signal mem : mem_t := (0 => \"0\", 1 => \"1\", others => (others => '0'));"));
+
+        // both ports write from the one process, port 1 last.
+        let val = serde_json::json!(["memory", [0, 0, 0], {"contents": "0 1", "nports": "2"}]);
+        let mem = Memory::from_value(&val).unwrap();
+        let inst = Instance { name: "m".to_string(), module: MEMORY.to_string(), connections: vec![], memory: None };
+        let block = memory_block("u_m", &mem, &inst, &|t: &str| t.to_lowercase()).unwrap();
+        assert_eq!(block.matches("process").count(), 2);
+        let write = ["    write : process (clk0, clk1) is",
+                     "    begin",
+                     "      if rising_edge(clk0) and we0 = '1' then",
+                     "        mem(to_integer(unsigned(std_logic_vector'(0 => a0)))) <= (0 => d0);",
+                     "      end if;",
+                     "      if rising_edge(clk1) and we1 = '1' then",
+                     "        mem(to_integer(unsigned(std_logic_vector'(0 => a1)))) <= (0 => d1);",
+                     "      end if;",
+                     "    end process;"];
+        assert!(block.contains(&write.join("\n")), "{}", block);
+    }
+
+    #[test]
+    fn globals_package() {
+        let p = project("use-and2.json");
+        let config = NetConfig::new().with_global("in1");
+        let vhdl = project_to_vhdl(&p, "/user/UseAND2", &config).unwrap();
+        assert!(vhdl.contains("package jade_globals is\n  signal global_in1 : std_logic;"));
+    }
+
+    #[test]
+    fn testbench1() {
+        let p = project("use-and2.json");
+        let src = ".group inputs in1 in2\n.group outputs out\n.cycle assert inputs tran 99n sample outputs tran 1n\n\
+                   00 L\n11 H // both\n";
+        let test = ModTest::from_str(src).unwrap();
+        let tb = vhdl_testbench(&p, "/user/AND2", &NetConfig::new(), &test).unwrap();
+        assert!(tb.contains("entity mod_user_AND2_tb is"));
+        assert!(tb.contains("dut : entity work.mod_user_AND2"));
+        assert!(tb.contains("    -- line 5 // both\n    in1 <= '1';\n    in2 <= '1';\n    wait for 99000000 fs;\n"));
+        assert!(vhdl_time(Duration::NanoSecond(1e-7)).is_err());
+        assert!(vhdl_time(Duration::AttoSecond(100.0)).is_err());
+        assert_eq!(vhdl_time(Duration::NanoSecond(0.0)), Ok("0 fs".to_string()));
+        assert_eq!(vhdl_time(Duration::NanoSecond(0.35)), Ok("350000 fs".to_string()));
+        assert_eq!(vhdl_time(Duration::AttoSecond(2500.0)), Ok("3 fs".to_string()));
+        assert!(tb.contains("report \"line 5: out expected '1' got \" & std_logic'image(\\out\\) & \" // both\" severity error;"));
+    }
+
+    #[test]
+    fn testbench2() {
+        let p = project("use-and2.json");
+        let src = ".group inputs in1 nope\n.cycle assert inputs\n00\n";
+        let test = ModTest::from_str(src).unwrap();
+        assert!(vhdl_testbench(&p, "/user/AND2", &NetConfig::new(), &test).is_err());
+    }
+
+    #[test]
+    fn testbench3() {
+        // And41 has no ports, its test drives and samples labelled wires.
+        let p = project("And41.json");
+        let test = p.test("/user/And41", "test").unwrap();
+        let tb = vhdl_testbench(&p, "/user/And41", &NetConfig::new(), test).unwrap();
+        assert!(tb.contains("    alias dut_A is <<signal dut.A : std_logic>>;\n"));
+        assert!(tb.contains("    dut_A <= force '0';\n"));
+        assert!(tb.contains("    if dut_vout /= '0' then\n"));
+
+        let p = project("Buffer4.json");
+        let test = p.test("/user/Buffer4", "test").unwrap();
+        let tb = vhdl_testbench(&p, "/user/Buffer4", &NetConfig::new(), test).unwrap();
+        assert!(tb.contains("    alias dut_A is <<signal dut.A : std_logic_vector(3 downto 0)>>;\n  begin\n"));
+        assert!(tb.contains("    dut_A(3) <= force '0';\n"));
+
+        // a forced wire is released, not driven to Z.
+        let src = ".group inputs A B\n.group outputs vout\n\
+                   .cycle assert inputs tran 9n deassert inputs sample outputs\n11 H\n";
+        let test = ModTest::from_str(src).unwrap();
+        let tb = vhdl_testbench(&project("And41.json"), "/user/And41", &NetConfig::new(), &test).unwrap();
+        assert!(tb.contains("    dut_A <= release;\n    dut_B <= release;\n"));
+    }
+
+    #[test]
+    fn saved_testbenches() {
+        // every saved test gets a testbench, wires inside the dut included.
+        let made = each_saved_test(|file, p, m, test| {
+            if let Err(b) = vhdl_testbench(p, &m.name, &NetConfig::new(), test) {
+                panic!("{} {}: {:?}", file, m.name, b);
+            }
+        });
+        assert_eq!(made, 163);
+    }
+}
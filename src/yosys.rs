@@ -0,0 +1,319 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::gates::*;
+use crate::netlist::*;
+use crate::types::*;
+use crate::vhdl::entity_name;
+
+// The flattened netlist for synthesis and formal tools, as Yosys JSON
+//
+//   yosys -p 'read_json design.json; opt; stat'
+//
+// or as BLIF for abc and friends. Ties are the constant bits, other global
+// nets become inputs of the top module so they survive optimisation.
+
+fn top_name(netlist: &Netlist) -> String {
+    entity_name(&netlist.top).trim_matches('\\').to_string()
+}
+
+// the top level ports: schematic ports grouped into buses plus one input
+// per global net. Bits are listed least significant first, as yosys does.
+fn top_ports(netlist: &Netlist) -> Vec<(String, Direction, Vec<String>)> {
+    let mut bits = vec![];
+    let mut dirs: HashMap<String, (Direction, String)> = HashMap::new();
+    for port in &netlist.ports {
+        let names = port.sig.bits().into_iter().filter_map(|b| if let Bit::Name(n) = b { Some(n) } else { None });
+        for (bit, net) in names.zip(port.nets.iter()) {
+            if !dirs.contains_key(&bit) {
+                dirs.insert(bit.clone(), (port.direction.unwrap_or(In), net.clone()));
+                bits.push(bit);
+            }
+        }
+    }
+
+    let mut ports = vec![];
+    for group in group_bits(&bits) {
+        let mut group_bits = group.bits.clone();
+        group_bits.sort_by_key(|b| split_bit(b).1);
+        let nets: Vec<String> = group_bits.iter().map(|b| dirs[b].1.clone()).collect();
+        ports.push((group.base.clone(), dirs[&group.bits[0]].0, nets));
+    }
+    for net in &netlist.nets {
+        if net.kind == NetKind::Global && !ports.iter().any(|(_, _, nets)| nets.contains(&net.name)) {
+            ports.push((net.name.clone(), In, vec![net.name.clone()]));
+        }
+    }
+    ports
+}
+
+struct JsonCells {
+    bits: HashMap<String, Value>,
+    next: u64,
+    cells: Map<String, Value>,
+}
+
+impl JsonCells {
+    fn bit(&mut self, net: Option<&str>) -> Value {
+        let net = match net {
+            Some(net) => net,
+            None => return json!("x"),
+        };
+        if let Some(bit) = self.bits.get(net) {
+            return bit.clone();
+        }
+        let bit = json!(self.next);
+        self.next += 1;
+        self.bits.insert(net.to_string(), bit.clone());
+        bit
+    }
+
+    fn fresh(&mut self) -> Value {
+        self.next += 1;
+        json!(self.next - 1)
+    }
+
+    fn cell(&mut self, name: String, kind: &str, conns: Vec<(&str, Value, &str)>) {
+        let mut dirs = Map::new();
+        let mut connections = Map::new();
+        for (port, bit, dir) in conns {
+            dirs.insert(port.to_string(), json!(dir));
+            connections.insert(port.to_string(), json!([bit]));
+        }
+        self.cells.insert(name,
+                          json!({"hide_name": 0, "type": kind, "parameters": {}, "attributes": {},
+                                 "port_directions": dirs, "connections": connections}));
+    }
+
+    // an n input gate is a chain of two input cells, the last one inverting
+    // for nand, nor and xnor.
+    fn chain(&mut self, name: &str, op: &str, last: &str, ins: Vec<Value>, out: Value) {
+        let mut acc = ins[0].clone();
+        for (i, b) in ins[1..].iter().enumerate() {
+            let last_cell = i + 2 == ins.len();
+            let y = if last_cell { out.clone() } else { self.fresh() };
+            let kind = if last_cell { last } else { op };
+            let conns = vec![("A", acc, "input"), ("B", b.clone(), "input"), ("Y", y.clone(), "output")];
+            self.cell(format!("{}${}", name, i), kind, conns);
+            acc = y;
+        }
+    }
+}
+
+pub fn netlist_to_yosys_json(netlist: &Netlist) -> E<Value> {
+    let mut jc = JsonCells { bits: HashMap::new(), next: 2, cells: Map::new() };
+    for net in &netlist.nets {
+        match net.kind {
+            NetKind::TieHigh => jc.bits.insert(net.name.clone(), json!("1")),
+            NetKind::TieLow => jc.bits.insert(net.name.clone(), json!("0")),
+            _ => None,
+        };
+    }
+
+    let mut ports = Map::new();
+    for (name, dir, nets) in top_ports(netlist) {
+        let bits: Vec<Value> = nets.iter().map(|n| jc.bit(Some(n))).collect();
+        let direction = match dir {
+            In => "input",
+            Out => "output",
+            InOut => "inout",
+        };
+        ports.insert(name, json!({"direction": direction, "bits": bits}));
+    }
+
+    for dev in &netlist.devices {
+        let name = format!("\\{}", dev.name);
+        let bits: HashMap<&str, Value> = dev.connections.iter().map(|(t, n)| (t.as_str(), jc.bit(Some(n)))).collect();
+        let b = |t: &str| bits.get(t).cloned().unwrap_or_else(|| json!("x"));
+        let ins: Vec<Value> = ["A", "B", "C", "D"].iter().filter(|t| bits.contains_key(*t)).map(|t| b(t)).collect();
+        match dev.kind {
+            GateKind::And => jc.chain(&name, "$_AND_", "$_AND_", ins, b("Z")),
+            GateKind::Or => jc.chain(&name, "$_OR_", "$_OR_", ins, b("Z")),
+            GateKind::Xor => jc.chain(&name, "$_XOR_", "$_XOR_", ins, b("Z")),
+            GateKind::Nand => jc.chain(&name, "$_AND_", "$_NAND_", ins, b("Z")),
+            GateKind::Nor => jc.chain(&name, "$_OR_", "$_NOR_", ins, b("Z")),
+            GateKind::Xnor => jc.chain(&name, "$_XOR_", "$_XNOR_", ins, b("Z")),
+            GateKind::Buffer => jc.cell(name, "$_BUF_", vec![("A", b("A"), "input"), ("Y", b("Z"), "output")]),
+            GateKind::Inverter => jc.cell(name, "$_NOT_", vec![("A", b("A"), "input"), ("Y", b("Z"), "output")]),
+            GateKind::Tristate => {
+                jc.cell(name, "$_TBUF_", vec![("A", b("A"), "input"), ("E", b("E"), "input"), ("Y", b("Z"), "output")])
+            }
+            GateKind::Mux2 => jc.cell(name,
+                                      "$_MUX_",
+                                      vec![("A", b("D0"), "input"),
+                                           ("B", b("D1"), "input"),
+                                           ("S", b("S"), "input"),
+                                           ("Y", b("Y"), "output")]),
+            GateKind::Mux4 => jc.cell(name,
+                                      "$_MUX4_",
+                                      vec![("A", b("D0"), "input"),
+                                           ("B", b("D1"), "input"),
+                                           ("C", b("D2"), "input"),
+                                           ("D", b("D3"), "input"),
+                                           ("S", b("S[0]"), "input"),
+                                           ("T", b("S[1]"), "input"),
+                                           ("Y", b("Y"), "output")]),
+            GateKind::Dreg => jc.cell(name,
+                                      "$_DFF_P_",
+                                      vec![("C", b("CLK"), "input"), ("D", b("D"), "input"), ("Q", b("Q"), "output")]),
+            GateKind::Memory => return bailfmt!("Memory {} can't be exported to yosys json yet: {}", dev.name, dev.module),
+        }
+    }
+
+    let mut netnames = Map::new();
+    let mut names: Vec<&Net> = netlist.nets.iter().collect();
+    names.sort_by(|a, b| a.name.cmp(&b.name));
+    for net in names {
+        if let Some(bit) = jc.bits.get(&net.name) {
+            let hide = if net.anon { 1 } else { 0 };
+            netnames.insert(net.name.clone(), json!({"hide_name": hide, "bits": [bit], "attributes": {}}));
+        }
+    }
+
+    let module = json!({"attributes": {"top": "00000000000000000000000000000001"},
+                        "ports": ports,
+                        "cells": jc.cells,
+                        "netnames": netnames});
+    let mut modules = Map::new();
+    modules.insert(top_name(netlist), module);
+    Ok(json!({"creator": "jade-parse", "modules": modules}))
+}
+
+// BLIF names can't hold white space, everything else is allowed.
+fn blif_name(net: &str) -> String {
+    net.replace(char::is_whitespace, "_")
+}
+
+// the .names cover for each logic gate, one row per input pattern giving 1.
+fn cover(kind: GateKind, n: usize) -> Vec<String> {
+    let all = || (0..1usize << n).map(|i| (0..n).rev().map(|b| if (i >> b) & 1 == 1 { '1' } else { '0' }).collect::<String>());
+    let ones = |row: &String| row.chars().filter(|c| *c == '1').count();
+    let rows: Vec<String> = match kind {
+        GateKind::And => vec!["1".repeat(n)],
+        GateKind::Nand => (0..n).map(|i| (0..n).map(|j| if i == j { '0' } else { '-' }).collect()).collect(),
+        GateKind::Or => (0..n).map(|i| (0..n).map(|j| if i == j { '1' } else { '-' }).collect()).collect(),
+        GateKind::Nor => vec!["0".repeat(n)],
+        GateKind::Xor => all().filter(|r| ones(r) % 2 == 1).collect(),
+        GateKind::Xnor => all().filter(|r| ones(r) % 2 == 0).collect(),
+        GateKind::Buffer => vec!["1".to_string()],
+        GateKind::Inverter => vec!["0".to_string()],
+        // select first: S D1 D0 and S1 S0 D3 D2 D1 D0
+        GateKind::Mux2 => vec!["11-".to_string(), "0-1".to_string()],
+        GateKind::Mux4 => vec!["111---".to_string(), "10-1--".to_string(), "01--1-".to_string(), "00---1".to_string()],
+        _ => vec![],
+    };
+    rows.into_iter().map(|r| format!("{} 1", r)).collect()
+}
+
+pub fn netlist_to_blif(netlist: &Netlist) -> E<String> {
+    let mut out = format!("# {} generated by jade-parse\n.model {}\n", netlist.top, top_name(netlist));
+    let mut inputs: Vec<String> = vec![];
+    let mut outputs: Vec<String> = vec![];
+    for (_, dir, nets) in top_ports(netlist) {
+        for net in nets {
+            let list = if dir == In { &mut inputs } else { &mut outputs };
+            if !list.contains(&blif_name(&net)) {
+                list.push(blif_name(&net));
+            }
+        }
+    }
+    out.push_str(&format!(".inputs {}\n.outputs {}\n", inputs.join(" "), outputs.join(" ")));
+
+    for net in &netlist.nets {
+        match net.kind {
+            NetKind::TieHigh => out.push_str(&format!(".names {}\n1\n", blif_name(&net.name))),
+            NetKind::TieLow => out.push_str(&format!(".names {}\n", blif_name(&net.name))),
+            _ => {}
+        }
+    }
+
+    for dev in &netlist.devices {
+        let net = |t: &str| -> E<String> {
+            match dev.net(t) {
+                Some(n) => Ok(blif_name(n)),
+                None => bailfmt!("{} has nothing on terminal {}", dev.name, t),
+            }
+        };
+        let terms: Vec<&str> = match dev.kind {
+            GateKind::Mux2 => vec!["S", "D1", "D0", "Y"],
+            GateKind::Mux4 => vec!["S[1]", "S[0]", "D3", "D2", "D1", "D0", "Y"],
+            GateKind::Buffer | GateKind::Inverter => vec!["A", "Z"],
+            GateKind::Dreg => {
+                out.push_str(&format!(".latch {} {} re {} 3\n", net("D")?, net("Q")?, net("CLK")?));
+                continue;
+            }
+            GateKind::Tristate | GateKind::Memory => {
+                return bailfmt!("BLIF has no {:?}, can't export {}", dev.kind, dev.name);
+            }
+            _ => ["A", "B", "C", "D"].iter().cloned().filter(|t| dev.net(t).is_some()).chain(vec!["Z"]).collect(),
+        };
+        let names = terms.iter().map(|t| net(t)).collect::<E<Vec<String>>>()?;
+        out.push_str(&format!(".names {}\n", names.join(" ")));
+        for row in cover(dev.kind, terms.len() - 1) {
+            out.push_str(&row);
+            out.push_str("\n");
+        }
+    }
+    out.push_str(".end\n");
+    Ok(out)
+}
+
+// -----------------------------------------------------------------------------
+// TESTS
+// -----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_mod::project;
+    use std::path::Path;
+
+    fn netlist(file: &str, top: &str) -> Netlist {
+        Netlist::flatten(&project(file), top, &NetConfig::new()).unwrap()
+    }
+
+    #[test]
+    fn cover1() {
+        assert_eq!(cover(GateKind::Nand, 2), vec!["0- 1", "-0 1"]);
+        assert_eq!(cover(GateKind::Xor, 2), vec!["01 1", "10 1"]);
+        assert_eq!(cover(GateKind::Nor, 3), vec!["000 1"]);
+    }
+
+    #[test]
+    fn json1() {
+        let n = netlist("use-and2.json", "/user/AND2");
+        let got = netlist_to_yosys_json(&n).unwrap();
+        let m = &got["modules"]["mod_user_AND2"];
+        assert_eq!(m["ports"]["in1"], json!({"direction": "input", "bits": [2]}));
+        assert_eq!(m["cells"]["\\and2_0$0"]["type"], json!("$_AND_"));
+        assert_eq!(m["cells"]["\\and2_0$0"]["connections"]["Y"], m["ports"]["out"]["bits"]);
+    }
+
+    #[test]
+    fn json2() {
+        // a four input and is three cells, a constant input is a "1" bit.
+        let n = netlist("GarrInc4.json", "/user/GarrInc4");
+        let got = netlist_to_yosys_json(&n).unwrap();
+        let cells = got["modules"]["mod_user_GarrInc4"]["cells"].as_object().unwrap();
+        assert_eq!(cells.len(), 4 + 2 + 2 + 3 + 3);
+
+        let n = netlist("Constant1.json", "/user/Constant1");
+        let got = netlist_to_yosys_json(&n).unwrap();
+        assert!(got.to_string().contains(r#"["1"]"#));
+    }
+
+    #[test]
+    fn blif1() {
+        let n = netlist("use-and2.json", "/user/AND2");
+        let got = netlist_to_blif(&n).unwrap();
+        assert!(got.contains(".model mod_user_AND2\n.inputs in1 in2\n.outputs out\n"));
+        assert!(got.contains(".names in1 in2 out\n11 1\n"));
+        assert!(got.ends_with(".end\n"));
+    }
+
+    #[test]
+    fn blif2() {
+        let n = netlist("Mux4Rep1.json", "/user/Mux4Rep1");
+        let got = netlist_to_blif(&n).unwrap();
+        assert!(got.contains(".names A[1] B[1] D[3] D[2] D[1] D[0] Y[1]\n111--- 1\n"));
+    }
+}